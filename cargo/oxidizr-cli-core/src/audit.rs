@@ -0,0 +1,555 @@
+//! Tamper-evident, file-backed audit log (JSONL) shared by the oxidizr-* CLIs.
+//!
+//! Every record carries who ran the command (uid, `SUDO_USER`, tty), the operation id, a sequence
+//! number and the hash of the previous record; its own `hash` is
+//! `sha256(prev || record-without-hash)`. Editing, dropping or reordering a record therefore
+//! breaks the chain from that point on. The newest `{seq, hash}` is also kept in `<log>.head`, so
+//! cutting records off the end is detected as well.
+//!
+//! Several processes may append to one log (oxidizr-deb's apt run triggers the Post-Invoke hook,
+//! another oxidizr-deb): each append holds an exclusive lock on `<log>.lock` and continues from the
+//! head file as it is then, never from a copy cached at `open`.
+//!
+//! The log rotates by size: `audit.jsonl` becomes `audit.jsonl.1`, `.1` becomes `.2`, and so on up
+//! to `keep` files; the chain continues across rotated files. When the oldest file is deleted,
+//! the `{seq, hash}` of its last record goes to `<log>.dropped`, so the oldest surviving record
+//! is still checked against its predecessor.
+//!
+//! Records can additionally be mirrored to systemd-journald (`with_journal`); the journal copy is
+//! not part of the chain.
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use fs2::FileExt;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use switchyard::logging::{AuditSink, FactsEmitter};
+
+use crate::journald::{self, JournalSink};
+
+/// `prev` of the first record ever written.
+pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+pub const DEFAULT_MAX_BYTES: u64 = 1024 * 1024;
+pub const DEFAULT_KEEP: usize = 5;
+
+/// Who ran the command; stamped on every record.
+#[derive(Debug, Clone, Default)]
+pub struct Invoker {
+    pub uid: Option<u32>,
+    pub sudo_user: Option<String>,
+    pub tty: Option<String>,
+}
+
+impl Invoker {
+    pub fn current() -> Self {
+        let tty = fs::read_link("/proc/self/fd/0")
+            .ok()
+            .map(|p| p.display().to_string())
+            .filter(|p| p.starts_with("/dev/pts/") || p.starts_with("/dev/tty"));
+        Invoker {
+            uid: fs::metadata("/proc/self").ok().map(|m| m.uid()),
+            sudo_user: std::env::var("SUDO_USER").ok().filter(|u| !u.is_empty()),
+            tty,
+        }
+    }
+}
+
+fn record_hash(prev: &str, body: &Value) -> String {
+    let mut h = Sha256::new();
+    h.update(prev.as_bytes());
+    h.update(body.to_string().as_bytes());
+    hex::encode(h.finalize())
+}
+
+fn head_path(path: &Path) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".head");
+    PathBuf::from(s)
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".lock");
+    PathBuf::from(s)
+}
+
+fn dropped_path(path: &Path) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".dropped");
+    PathBuf::from(s)
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(format!(".{n}"));
+    PathBuf::from(s)
+}
+
+/// `(seq, hash)` of the newest record, from the head file.
+fn read_head(path: &Path) -> Result<Option<(u64, String)>, String> {
+    read_marker(&head_path(path))
+}
+
+/// `(seq, hash)` of the last record lost to rotation, from the retention marker.
+fn read_dropped(path: &Path) -> Result<Option<(u64, String)>, String> {
+    read_marker(&dropped_path(path))
+}
+
+fn read_marker(p: &Path) -> Result<Option<(u64, String)>, String> {
+    let s = match fs::read_to_string(p) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("failed to read {}: {e}", p.display())),
+    };
+    let v: Value = serde_json::from_str(&s).map_err(|e| format!("invalid {}: {e}", p.display()))?;
+    match (v["seq"].as_u64(), v["hash"].as_str()) {
+        (Some(seq), Some(hash)) => Ok(Some((seq, hash.to_string()))),
+        _ => Err(format!("invalid {}: missing seq/hash", p.display())),
+    }
+}
+
+/// Atomically write a `{seq, hash}` marker file.
+fn write_marker(p: &Path, seq: u64, hash: &str) -> Result<(), String> {
+    let mut tmp = p.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, json!({"seq": seq, "hash": hash}).to_string())
+        .and_then(|_| fs::rename(&tmp, p))
+        .map_err(|e| format!("failed to update {}: {e}", p.display()))
+}
+
+struct Inner {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    invoker: Invoker,
+    operation_id: Option<u64>,
+}
+
+impl Inner {
+    fn rotate(&mut self) -> Result<(), String> {
+        let oldest = rotated_path(&self.path, self.keep);
+        if let Ok(text) = fs::read_to_string(&oldest) {
+            let last = text
+                .lines()
+                .last()
+                .and_then(|l| serde_json::from_str::<Value>(l).ok());
+            if let Some((seq, hash)) = last
+                .as_ref()
+                .and_then(|v| Some((v["seq"].as_u64()?, v["hash"].as_str()?)))
+            {
+                write_marker(&dropped_path(&self.path), seq, hash)?;
+            }
+        }
+        let _ = fs::remove_file(&oldest);
+        for n in (1..self.keep).rev() {
+            let from = rotated_path(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.path, n + 1))
+                    .map_err(|e| format!("failed to rotate {}: {e}", from.display()))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))
+            .map_err(|e| format!("failed to rotate {}: {e}", self.path.display()))
+    }
+
+    fn append(&mut self, event: &str, data: Value) -> Result<(), String> {
+        // Held until the record and the head file are written; released when `lock` drops
+        let lock_file = lock_path(&self.path);
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_file)
+            .and_then(|f| f.lock_exclusive().map(|_| f))
+            .map_err(|e| format!("failed to lock {}: {e}", lock_file.display()))?;
+        let (seq, prev) = match read_head(&self.path)? {
+            Some((seq, hash)) => (seq + 1, hash.clone()),
+            None => (1, GENESIS.to_string()),
+        };
+        let mut body = json!({
+            "seq": seq,
+            "ts": std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            "uid": self.invoker.uid,
+            "sudo_user": self.invoker.sudo_user,
+            "tty": self.invoker.tty,
+            "operation_id": self.operation_id,
+            "event": event,
+            "data": data,
+            "prev": prev,
+        });
+        let hash = record_hash(&prev, &body);
+        body["hash"] = Value::String(hash.clone());
+        let mut line = body.to_string();
+        line.push('\n');
+
+        let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if self.keep > 0 && size > 0 && size + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("failed to open audit log {}: {e}", self.path.display()))?;
+        f.write_all(line.as_bytes())
+            .and_then(|_| f.sync_data())
+            .map_err(|e| format!("failed to append to audit log {}: {e}", self.path.display()))?;
+
+        write_marker(&head_path(&self.path), seq, &hash)?;
+        drop(lock);
+        Ok(())
+    }
+}
+
+/// Handle to an audit log; clones share the file and the chain. Also usable as Switchyard's
+/// facts emitter and audit sink. A `disabled` log (e.g. for dry runs) writes no file, but still
+/// mirrors to the journal when one is attached.
+#[derive(Clone, Default)]
+pub struct AuditLog {
+    inner: Option<Arc<Mutex<Inner>>>,
+    journal: Option<JournalSink>,
+}
+
+impl AuditLog {
+    /// Open (creating parent directories) the log at `path`, continuing its chain.
+    pub fn open(path: &Path, invoker: Invoker, operation_id: Option<u64>) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+        }
+        // Refuse a log whose head cannot be read before the run changes anything
+        read_head(path)?;
+        Ok(AuditLog {
+            inner: Some(Arc::new(Mutex::new(Inner {
+                path: path.to_path_buf(),
+                max_bytes: DEFAULT_MAX_BYTES,
+                keep: DEFAULT_KEEP,
+                invoker,
+                operation_id,
+            }))),
+            journal: None,
+        })
+    }
+
+    pub fn disabled() -> Self {
+        AuditLog::default()
+    }
+
+    /// Also send every record to journald.
+    pub fn with_journal(mut self, journal: JournalSink) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    pub fn has_journal(&self) -> bool {
+        self.journal.is_some()
+    }
+
+    /// Rotate once the live file would exceed `max_bytes`, keeping `keep` rotated files.
+    pub fn with_rotation(self, max_bytes: u64, keep: usize) -> Self {
+        if let Some(Ok(mut inner)) = self.inner.as_ref().map(|i| i.lock()) {
+            inner.max_bytes = max_bytes;
+            inner.keep = keep;
+        }
+        self
+    }
+
+    pub fn record(&self, event: &str, data: Value) -> Result<(), String> {
+        let sent = match &self.journal {
+            Some(j) => j.send_event(event, &data),
+            None => Ok(()),
+        };
+        if let Some(inner) = &self.inner {
+            inner
+                .lock()
+                .map_err(|_| "audit log lock poisoned".to_string())?
+                .append(event, data)?;
+        }
+        sent
+    }
+
+    fn record_or_warn(&self, event: &str, data: Value) {
+        if let Err(e) = self.record(event, data) {
+            eprintln!("[warn] audit: {e}");
+        }
+    }
+}
+
+impl FactsEmitter for AuditLog {
+    fn emit(&self, subsystem: &str, event: &str, decision: &str, fields: Value) {
+        self.record_or_warn(
+            event,
+            json!({"subsystem": subsystem, "decision": decision, "fields": fields}),
+        );
+    }
+}
+
+impl AuditSink for AuditLog {
+    fn log(&self, level: log::Level, msg: &str) {
+        if let Some(j) = &self.journal {
+            let priority = match level {
+                log::Level::Error => journald::PRIORITY_ERR,
+                log::Level::Warn => journald::PRIORITY_WARNING,
+                log::Level::Info => journald::PRIORITY_INFO,
+                log::Level::Debug | log::Level::Trace => journald::PRIORITY_DEBUG,
+            };
+            if let Err(e) = j.send(msg, priority, &[("OXIDIZR_EVENT", "audit.log".to_string())]) {
+                eprintln!("[warn] {e}");
+            }
+        }
+        let file_only = AuditLog {
+            inner: self.inner.clone(),
+            journal: None,
+        };
+        file_only.record_or_warn("audit.log", json!({"level": level.as_str(), "msg": msg}));
+    }
+}
+
+/// Outcome of a successful `verify`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub records: u64,
+    pub first_seq: Option<u64>,
+    pub last_seq: Option<u64>,
+    pub files: Vec<PathBuf>,
+}
+
+/// Walk the log (oldest rotated file first) and check every record's hash, its link to the
+/// previous record, the sequence numbers and the head file. The oldest surviving record must start
+/// the chain, or follow the last record rotation deleted (`<log>.dropped`).
+pub fn verify(path: &Path) -> Result<VerifyReport, String> {
+    let mut files: Vec<PathBuf> = (1..)
+        .map(|n| rotated_path(path, n))
+        .take_while(|p| p.exists())
+        .collect();
+    files.reverse();
+    if path.exists() {
+        files.push(path.to_path_buf());
+    }
+    let head = read_head(path)?;
+    let dropped = read_dropped(path)?;
+
+    let mut last: Option<(u64, String)> = None;
+    let mut first_seq = None;
+    let mut records = 0;
+    for file in &files {
+        let text = fs::read_to_string(file)
+            .map_err(|e| format!("failed to read {}: {e}", file.display()))?;
+        for (i, line) in text.lines().enumerate() {
+            let at = format!("{} line {}", file.display(), i + 1);
+            let mut body: Value = serde_json::from_str(line)
+                .map_err(|e| format!("{at}: unreadable record (truncated or edited): {e}"))?;
+            let obj: &mut Map<String, Value> = body
+                .as_object_mut()
+                .ok_or_else(|| format!("{at}: record is not an object"))?;
+            let hash = obj
+                .remove("hash")
+                .and_then(|h| h.as_str().map(str::to_string))
+                .ok_or_else(|| format!("{at}: record has no hash"))?;
+            let seq = obj
+                .get("seq")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| format!("{at}: record has no seq"))?;
+            let prev = obj
+                .get("prev")
+                .and_then(Value::as_str)
+                .ok_or_else(|| format!("{at}: record has no prev"))?
+                .to_string();
+            if record_hash(&prev, &body) != hash {
+                return Err(format!("{at}: record {seq} was modified (hash mismatch)"));
+            }
+            if let Some((last_seq, last_hash)) = &last {
+                if seq != last_seq + 1 {
+                    return Err(format!(
+                        "{at}: record {seq} follows record {last_seq}; records are missing or reordered"
+                    ));
+                }
+                if prev != *last_hash {
+                    return Err(format!(
+                        "{at}: record {seq} does not chain to record {last_seq}"
+                    ));
+                }
+            } else if seq == 1 {
+                if prev != GENESIS {
+                    return Err(format!("{at}: first record does not start the chain"));
+                }
+            } else {
+                match &dropped {
+                    Some((dseq, dhash)) if seq == dseq + 1 && prev == *dhash => {}
+                    Some((dseq, _)) if seq > dseq + 1 => {
+                        return Err(format!(
+                            "{at}: log starts at record {seq} but rotation only deleted records up to {dseq}; records {}..{} are missing",
+                            dseq + 1,
+                            seq - 1
+                        ))
+                    }
+                    Some((dseq, _)) => {
+                        return Err(format!(
+                            "{at}: record {seq} does not chain to record {dseq} deleted by rotation"
+                        ))
+                    }
+                    None => {
+                        return Err(format!(
+                            "{at}: log starts at record {seq} and no rotation deleted records; records 1..{} are missing",
+                            seq - 1
+                        ))
+                    }
+                }
+            }
+            first_seq.get_or_insert(seq);
+            records += 1;
+            last = Some((seq, hash));
+        }
+    }
+    match (&head, &last) {
+        (Some((hseq, hhash)), Some((lseq, lhash))) if hseq != lseq || hhash != lhash => {
+            return Err(format!(
+                "log ends at record {lseq} but {} records {hseq}; the log was truncated",
+                head_path(path).display()
+            ))
+        }
+        (Some((hseq, _)), None) => {
+            return Err(format!(
+                "{} records {hseq} but the log is empty or missing",
+                head_path(path).display()
+            ))
+        }
+        (None, Some(_)) => return Err(format!("{} is missing", head_path(path).display())),
+        _ => {}
+    }
+    Ok(VerifyReport {
+        records,
+        first_seq,
+        last_seq: last.map(|(s, _)| s),
+        files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_with(dir: &Path, n: usize) -> (PathBuf, AuditLog) {
+        let path = dir.join("audit.jsonl");
+        let log = AuditLog::open(&path, Invoker::default(), Some(7)).unwrap();
+        for i in 0..n {
+            log.record("pm.install", json!({"i": i})).unwrap();
+        }
+        (path, log)
+    }
+
+    #[test]
+    fn test_verify_intact_chain() {
+        let td = tempfile::tempdir().unwrap();
+        let (path, _) = log_with(td.path(), 3);
+        let report = verify(&path).unwrap();
+        assert_eq!(report.records, 3);
+        assert_eq!(report.last_seq, Some(3));
+    }
+
+    #[test]
+    fn test_chain_continues_after_reopen() {
+        let td = tempfile::tempdir().unwrap();
+        let (path, _) = log_with(td.path(), 2);
+        let (_, _) = log_with(td.path(), 2);
+        assert_eq!(verify(&path).unwrap().last_seq, Some(4));
+    }
+
+    #[test]
+    fn test_chain_survives_interleaved_writers() {
+        let td = tempfile::tempdir().unwrap();
+        let (path, parent) = log_with(td.path(), 1);
+        // e.g. the Post-Invoke hook appending while the run that triggered it has the log open
+        let (_, child) = log_with(td.path(), 2);
+        parent.record("run.finish", json!({})).unwrap();
+        child.record("hook.run", json!({})).unwrap();
+        let report = verify(&path).unwrap();
+        assert_eq!(report.records, 5);
+        assert_eq!(report.last_seq, Some(5));
+    }
+
+    #[test]
+    fn test_verify_detects_edit() {
+        let td = tempfile::tempdir().unwrap();
+        let (path, _) = log_with(td.path(), 3);
+        let s = fs::read_to_string(&path).unwrap();
+        fs::write(&path, s.replacen("\"i\":1", "\"i\":9", 1)).unwrap();
+        assert!(verify(&path).unwrap_err().contains("modified"));
+    }
+
+    #[test]
+    fn test_verify_detects_dropped_record() {
+        let td = tempfile::tempdir().unwrap();
+        let (path, _) = log_with(td.path(), 3);
+        let s = fs::read_to_string(&path).unwrap();
+        let kept: Vec<&str> = s
+            .lines()
+            .enumerate()
+            .filter(|(i, _)| *i != 1)
+            .map(|(_, l)| l)
+            .collect();
+        fs::write(&path, kept.join("\n") + "\n").unwrap();
+        assert!(verify(&path).unwrap_err().contains("missing or reordered"));
+    }
+
+    #[test]
+    fn test_verify_detects_truncation() {
+        let td = tempfile::tempdir().unwrap();
+        let (path, _) = log_with(td.path(), 3);
+        let s = fs::read_to_string(&path).unwrap();
+        let kept: Vec<&str> = s.lines().take(2).collect();
+        fs::write(&path, kept.join("\n") + "\n").unwrap();
+        assert!(verify(&path).unwrap_err().contains("truncated"));
+    }
+
+    #[test]
+    fn test_verify_detects_front_truncation() {
+        let td = tempfile::tempdir().unwrap();
+        let (path, _) = log_with(td.path(), 3);
+        let s = fs::read_to_string(&path).unwrap();
+        let kept: Vec<&str> = s.lines().skip(1).collect();
+        fs::write(&path, kept.join("\n") + "\n").unwrap();
+        assert!(verify(&path).unwrap_err().contains("records 1..1 are missing"));
+    }
+
+    #[test]
+    fn test_verify_detects_front_truncation_after_rotation() {
+        let td = tempfile::tempdir().unwrap();
+        let path = td.path().join("audit.jsonl");
+        let log = AuditLog::open(&path, Invoker::default(), None)
+            .unwrap()
+            .with_rotation(600, 2);
+        for i in 0..10 {
+            log.record("pm.purge", json!({"i": i})).unwrap();
+        }
+        let oldest = rotated_path(&path, 2);
+        let s = fs::read_to_string(&oldest).unwrap();
+        let kept: Vec<&str> = s.lines().skip(1).collect();
+        fs::write(&oldest, kept.join("\n") + "\n").unwrap();
+        assert!(verify(&path).unwrap_err().contains("are missing"));
+    }
+
+    #[test]
+    fn test_rotation_keeps_chain() {
+        let td = tempfile::tempdir().unwrap();
+        let path = td.path().join("audit.jsonl");
+        let log = AuditLog::open(&path, Invoker::default(), None)
+            .unwrap()
+            .with_rotation(600, 2);
+        for i in 0..10 {
+            log.record("pm.purge", json!({"i": i})).unwrap();
+        }
+        assert!(rotated_path(&path, 2).exists());
+        assert!(!rotated_path(&path, 3).exists());
+        let report = verify(&path).unwrap();
+        assert_eq!(report.last_seq, Some(10));
+        assert!(report.first_seq > Some(1));
+    }
+}
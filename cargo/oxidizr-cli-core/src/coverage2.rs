@@ -77,8 +77,8 @@ pub fn coverage_preflight<A: DistroAdapter>(
     let repl = discover_applets_with_allow(source_bin, &allow);
     let distro = adapter.enumerate_package_commands(root, pkg);
     if distro.is_empty() {
-        // No distro enumeration (non-live root). Accept preflight; downstream code
-        // should still perform post-apply smoke checks.
+        // No distro enumeration (package not in the root's dpkg database). Accept preflight;
+        // downstream code should still perform post-apply smoke checks.
         return Ok(());
     }
     coverage_check(&distro, &repl)
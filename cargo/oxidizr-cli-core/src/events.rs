@@ -0,0 +1,493 @@
+//! Structured events emitted by the oxidizr-* CLIs as JSON lines (on stderr, to journald and
+//! into the audit log). Every event is one `Event` variant, tagged by its `event` name and
+//! serialized with `schema_version`; `schema()` describes them all as a JSON Schema.
+//!
+//! Adding an optional field is backwards compatible; renaming or removing one, or changing a
+//! type, bumps `EVENT_SCHEMA_VERSION`.
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// Characters of a tool's stderr kept in `stderr_tail`.
+pub const STDERR_TAIL_CHARS: usize = 400;
+
+/// The last `STDERR_TAIL_CHARS` characters of a tool's stderr.
+pub fn stderr_tail(stderr: &[u8]) -> String {
+    let s = String::from_utf8_lossy(stderr);
+    let skip = s.chars().count().saturating_sub(STDERR_TAIL_CHARS);
+    s.chars().skip(skip).collect()
+}
+
+/// A package-manager tool invocation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PmCommand {
+    pub tool: String,
+    pub args: Vec<String>,
+    /// Package the invocation is for, when it is about one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+}
+
+/// Outcome of one package-manager invocation: `pm.update`, `pm.install`, `pm.purge` and the
+/// alternatives and diversion events all share this shape.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PmRun {
+    pub pm: PmCommand,
+    pub exit_code: i32,
+    /// See `stderr_tail`.
+    pub stderr_tail: String,
+}
+
+impl PmRun {
+    pub fn new(
+        tool: &str,
+        args: &[String],
+        package: Option<&str>,
+        exit_code: i32,
+        stderr: &[u8],
+    ) -> Self {
+        PmRun {
+            pm: PmCommand {
+                tool: tool.to_string(),
+                args: args.to_vec(),
+                package: package.map(str::to_string),
+            },
+            exit_code,
+            stderr_tail: stderr_tail(stderr),
+        }
+    }
+}
+
+/// One apt transaction covering every selected family.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PmTransaction {
+    /// apt only planned it (`-s`).
+    pub simulate: bool,
+    pub install: Vec<String>,
+    pub purge: Vec<String>,
+    #[serde(flatten)]
+    pub run: PmRun,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PackageSet {
+    pub install: Vec<String>,
+    pub purge: Vec<String>,
+}
+
+/// One package line of `apt-get -s` output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PkgChange {
+    pub name: String,
+    /// Currently installed version (`[...]`), if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// Version apt would install (`(...)`), for `Inst` lines.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+}
+
+/// Package delta of a simulated transaction, from its `Inst`/`Remv`/`Purg` lines.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct AptDelta {
+    pub install: Vec<PkgChange>,
+    pub remove: Vec<PkgChange>,
+    pub purge: Vec<PkgChange>,
+    /// Removed or purged packages marked `Essential: yes` in the root's dpkg status.
+    pub essential: Vec<String>,
+}
+
+impl AptDelta {
+    /// Every package apt would take away, removed or purged.
+    pub fn removals(&self) -> impl Iterator<Item = &PkgChange> {
+        self.remove.iter().chain(self.purge.iter())
+    }
+
+    /// Removals apt would make beyond the packages in `requested` (reverse dependencies and the
+    /// like). Names may carry an `:arch` qualifier in apt's output.
+    pub fn unrequested_removals(&self, requested: &[String]) -> Vec<&PkgChange> {
+        self.removals()
+            .filter(|c| {
+                let base = c.name.split(':').next().unwrap_or(&c.name);
+                !requested.iter().any(|r| r == base || r == &c.name)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PmSimulate {
+    pub root: String,
+    pub requested: PackageSet,
+    pub delta: AptDelta,
+}
+
+/// A `.deb` unpacked into a non-live root without dpkg.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PmUnpack {
+    pub deb: String,
+    pub package: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    pub root: String,
+    pub files: usize,
+    /// dpkg status recorded for the package.
+    pub status: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SourceAdd {
+    pub path: String,
+    pub entry: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SourceRemove {
+    pub path: String,
+    pub ok: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ImageMount {
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ImageUmount {
+    pub target: String,
+    pub ok: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ImageHook {
+    /// `use` or `replace`.
+    pub operation: String,
+    pub rootfs: String,
+    pub image_mode: String,
+    pub packages: Vec<String>,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EssentialDecision {
+    Allow,
+    Deny,
+}
+
+/// Whether purging Essential packages went ahead.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ReplaceEssential {
+    pub decision: EssentialDecision,
+    pub packages: Vec<String>,
+    pub reason: String,
+}
+
+/// An offline artifact checked against its signed manifest. The manifest fields are set on
+/// success, `error` on failure.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ArtifactVerify {
+    pub decision: VerifyDecision,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyDecision {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct HookInstall {
+    pub path: String,
+    pub bin: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct HookRemove {
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HookDecision {
+    /// Nothing is switched.
+    Noop,
+    /// The CLI itself drove the dpkg run and reconciles on its own.
+    Skipped,
+    Repaired,
+}
+
+/// An applet that no longer pointed at the replacement.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct DriftedApplet {
+    pub name: String,
+    /// Its status before the repair: `stock`, `foreign`, `dangling` or `missing`.
+    pub was: String,
+}
+
+/// Repair result for one switched family.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FamilyRepair {
+    pub package: String,
+    pub status_before: String,
+    pub drifted: Vec<DriftedApplet>,
+    pub planned_actions: usize,
+    pub fixed: usize,
+}
+
+/// The package-manager hook ran after a dpkg invocation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct HookRun {
+    pub decision: HookDecision,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dry_run: Option<bool>,
+    /// Per-family repair results, when `repaired`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub families: Option<Vec<FamilyRepair>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BundleFamily {
+    pub package: String,
+    pub path: String,
+    pub sha256: String,
+}
+
+/// A signed bundle of replacement binaries imported for offline use.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BundleImport {
+    pub dry_run: bool,
+    pub bundle: String,
+    pub version: String,
+    pub arch: String,
+    pub key: String,
+    pub families: Vec<BundleFamily>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The per-applet replacement binary is not present.
+    SourceMissing,
+    /// The distro ships the applet but the replacement does not provide it.
+    NotInReplacement,
+}
+
+/// An applet `use` left on the distro binary (see docs/SELINUX_APPLET_PARITY.md).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SkipApplet {
+    pub applet: String,
+    pub reason: SkipReason,
+    /// Replacement binary the applet would have linked to.
+    pub source: String,
+}
+
+/// Start or end of a committed run in the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RunPhase {
+    pub command: String,
+    pub root: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ok: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "event")]
+pub enum Event {
+    #[serde(rename = "pm.update")]
+    PmUpdate(PmRun),
+    #[serde(rename = "pm.install")]
+    PmInstall(PmRun),
+    #[serde(rename = "pm.purge")]
+    PmPurge(PmRun),
+    #[serde(rename = "pm.transaction")]
+    PmTransaction(PmTransaction),
+    #[serde(rename = "pm.simulate")]
+    PmSimulate(PmSimulate),
+    #[serde(rename = "pm.unpack")]
+    PmUnpack(PmUnpack),
+    #[serde(rename = "pm.source.add")]
+    PmSourceAdd(SourceAdd),
+    #[serde(rename = "pm.source.remove")]
+    PmSourceRemove(SourceRemove),
+    #[serde(rename = "pm.alternatives.install")]
+    PmAlternativesInstall(PmRun),
+    #[serde(rename = "pm.alternatives.set")]
+    PmAlternativesSet(PmRun),
+    #[serde(rename = "pm.alternatives.remove")]
+    PmAlternativesRemove(PmRun),
+    #[serde(rename = "pm.divert.add")]
+    PmDivertAdd(PmRun),
+    #[serde(rename = "pm.divert.remove")]
+    PmDivertRemove(PmRun),
+    #[serde(rename = "image.mount")]
+    ImageMount(ImageMount),
+    #[serde(rename = "image.umount")]
+    ImageUmount(ImageUmount),
+    #[serde(rename = "mmdebstrap.hook")]
+    MmdebstrapHook(ImageHook),
+    #[serde(rename = "replace.essential")]
+    ReplaceEssential(ReplaceEssential),
+    #[serde(rename = "artifact.verify")]
+    ArtifactVerify(ArtifactVerify),
+    #[serde(rename = "hook.install")]
+    HookInstall(HookInstall),
+    #[serde(rename = "hook.remove")]
+    HookRemove(HookRemove),
+    #[serde(rename = "hook.run")]
+    HookRun(HookRun),
+    #[serde(rename = "bundle.import")]
+    BundleImport(BundleImport),
+    #[serde(rename = "use.exec.skip_applet")]
+    SkipApplet(SkipApplet),
+    #[serde(rename = "run.start")]
+    RunStart(RunPhase),
+    #[serde(rename = "run.finish")]
+    RunFinish(RunPhase),
+}
+
+/// An event as written: `schema_version` next to the event's own fields.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(title = "oxidizr event")]
+pub struct EventRecord {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+impl Event {
+    /// The `event` name, e.g. `pm.install`.
+    pub fn name(&self) -> String {
+        self.to_value()["event"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// The event as a JSON object carrying `schema_version`.
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(EventRecord {
+            schema_version: EVENT_SCHEMA_VERSION,
+            event: self.clone(),
+        })
+        .unwrap_or(Value::Null)
+    }
+}
+
+/// Parse one event line (or object) back into its typed form.
+pub fn parse(data: Value) -> Result<EventRecord, String> {
+    serde_json::from_value(data).map_err(|e| format!("invalid event: {e}"))
+}
+
+/// JSON Schema of `EventRecord`.
+pub fn schema() -> Schema {
+    schemars::schema_for!(EventRecord)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_stderr_tail_keeps_last_chars() {
+        let long = "é".repeat(STDERR_TAIL_CHARS + 10) + "end";
+        let tail = stderr_tail(long.as_bytes());
+        assert_eq!(tail.chars().count(), STDERR_TAIL_CHARS);
+        assert!(tail.ends_with("end"));
+        assert_eq!(stderr_tail(b"short"), "short");
+    }
+
+    #[test]
+    fn test_pm_events_share_one_shape() {
+        let run = PmRun::new(
+            "apt-get",
+            &["install".into()],
+            Some("rust-coreutils"),
+            0,
+            b"",
+        );
+        let v = Event::PmInstall(run.clone()).to_value();
+        assert_eq!(v["event"], "pm.install");
+        assert_eq!(v["schema_version"], EVENT_SCHEMA_VERSION);
+        assert_eq!(v["pm"]["package"], "rust-coreutils");
+        let purge = Event::PmPurge(run).to_value();
+        let keys = |v: &Value| {
+            let mut k: Vec<String> = v.as_object().unwrap().keys().cloned().collect();
+            k.sort();
+            k
+        };
+        assert_eq!(keys(&v), keys(&purge));
+    }
+
+    #[test]
+    fn test_round_trip_skip_applet() {
+        let line = json!({"schema_version": 1, "event": "use.exec.skip_applet", "applet": "chcon", "reason": "source_missing", "source": "/usr/bin/uu-chcon"});
+        let rec = parse(line.clone()).unwrap();
+        assert_eq!(
+            rec.event,
+            Event::SkipApplet(SkipApplet {
+                applet: "chcon".into(),
+                reason: SkipReason::SourceMissing,
+                source: "/usr/bin/uu-chcon".into(),
+            })
+        );
+        assert_eq!(rec.event.to_value(), line);
+        assert!(parse(json!({"event": "pm.install"})).is_err());
+    }
+
+    #[test]
+    fn test_schema_names_every_event() {
+        let s = serde_json::to_string(&schema()).unwrap();
+        for name in [
+            "pm.install",
+            "pm.transaction",
+            "use.exec.skip_applet",
+            "run.finish",
+        ] {
+            assert!(s.contains(name), "schema lacks {name}");
+        }
+    }
+
+    #[test]
+    fn test_nested_payloads_are_typed_in_the_schema() {
+        let s = serde_json::to_value(schema()).unwrap();
+        let defs = &s["$defs"];
+        for (def, field) in [
+            ("AptDelta", "essential"),
+            ("PkgChange", "from"),
+            ("FamilyRepair", "drifted"),
+        ] {
+            assert!(
+                defs[def]["properties"][field].is_object(),
+                "schema lacks {def}.{field}"
+            );
+        }
+        let line = json!({"schema_version": 1, "event": "pm.simulate", "root": "/", "requested": {"install": [], "purge": ["coreutils"]}, "delta": {"install": [], "remove": [{"name": "coreutils", "from": "9.4-3"}], "purge": [], "essential": ["coreutils"]}});
+        let rec = parse(line.clone()).unwrap();
+        assert_eq!(rec.event.to_value(), line);
+    }
+}
@@ -0,0 +1,203 @@
+//! Minimal systemd-journald client speaking the native protocol: one datagram per entry, sent to
+//! `/run/systemd/journal/socket`, holding `KEY=value` lines. Values containing a newline use the
+//! binary form (`KEY\n`, little-endian u64 length, value, `\n`).
+//!
+//! Entries carry `SYSLOG_IDENTIFIER`, so `journalctl -t <identifier>` lists them, plus
+//! `OXIDIZR_EVENT`, `OXIDIZR_PACKAGE` (when the event names one), `PRIORITY` and the full event as
+//! JSON in `OXIDIZR_DATA`.
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+pub const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// syslog priorities used for entries.
+pub const PRIORITY_ERR: u8 = 3;
+pub const PRIORITY_WARNING: u8 = 4;
+pub const PRIORITY_INFO: u8 = 6;
+pub const PRIORITY_DEBUG: u8 = 7;
+
+/// Serialize fields in the journal's native datagram format.
+pub fn encode(fields: &[(&str, String)]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for (k, v) in fields {
+        buf.extend_from_slice(k.as_bytes());
+        if v.contains('\n') {
+            buf.push(b'\n');
+            buf.extend_from_slice(&(v.len() as u64).to_le_bytes());
+            buf.extend_from_slice(v.as_bytes());
+        } else {
+            buf.push(b'=');
+            buf.extend_from_slice(v.as_bytes());
+        }
+        buf.push(b'\n');
+    }
+    buf
+}
+
+/// Package(s) an event is about: `package`, `pm.package` or `fields.package`, else the packages
+/// of an apt transaction (`install` and `purge`), comma-separated.
+fn event_package(data: &Value) -> Option<String> {
+    let named = [
+        &data["package"],
+        &data["pm"]["package"],
+        &data["fields"]["package"],
+    ]
+    .iter()
+    .find_map(|v| match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Array(a) if !a.is_empty() => Some(
+            a.iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+        _ => None,
+    });
+    named.or_else(|| {
+        let tx: Vec<&str> = [&data["install"], &data["purge"]]
+            .iter()
+            .filter_map(|v| v.as_array())
+            .flatten()
+            .filter_map(Value::as_str)
+            // Local .deb paths (`dir/name_version_arch.deb`) are named by their package
+            .map(|e| match e.rsplit_once('/') {
+                Some((_, file)) => file.split('_').next().unwrap_or(file),
+                None => e,
+            })
+            .collect();
+        (!tx.is_empty()).then(|| tx.join(","))
+    })
+}
+
+/// Failures are errors, everything else is informational.
+fn event_priority(data: &Value) -> u8 {
+    let failed = data["exit_code"].as_i64().is_some_and(|c| c != 0)
+        || data["ok"] == Value::Bool(false)
+        || matches!(data["decision"].as_str(), Some("failure" | "fail"));
+    if failed {
+        PRIORITY_ERR
+    } else if data["decision"].as_str() == Some("warn") {
+        PRIORITY_WARNING
+    } else {
+        PRIORITY_INFO
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JournalSink {
+    identifier: String,
+    socket: PathBuf,
+}
+
+impl JournalSink {
+    pub fn new(identifier: &str) -> Self {
+        JournalSink {
+            identifier: identifier.to_string(),
+            socket: PathBuf::from(JOURNAL_SOCKET),
+        }
+    }
+
+    /// Send to another socket (a local stand-in for journald in tests).
+    pub fn with_socket(mut self, socket: &Path) -> Self {
+        self.socket = socket.to_path_buf();
+        self
+    }
+
+    /// Send one entry with the standard fields plus `extra`.
+    pub fn send(
+        &self,
+        message: &str,
+        priority: u8,
+        extra: &[(&str, String)],
+    ) -> Result<(), String> {
+        let mut fields = vec![
+            ("MESSAGE", message.to_string()),
+            ("PRIORITY", priority.to_string()),
+            ("SYSLOG_IDENTIFIER", self.identifier.clone()),
+        ];
+        fields.extend(extra.iter().cloned());
+        let sock = UnixDatagram::unbound()
+            .map_err(|e| format!("journald: failed to create socket: {e}"))?;
+        sock.send_to(&encode(&fields), &self.socket)
+            .map(|_| ())
+            .map_err(|e| format!("journald: failed to send to {}: {e}", self.socket.display()))
+    }
+
+    /// Send a structured event (`data` is the event's JSON object).
+    pub fn send_event(&self, event: &str, data: &Value) -> Result<(), String> {
+        let mut extra = vec![("OXIDIZR_EVENT", event.to_string())];
+        if let Some(p) = event_package(data) {
+            extra.push(("OXIDIZR_PACKAGE", p));
+        }
+        extra.push(("OXIDIZR_DATA", data.to_string()));
+        self.send(event, event_priority(data), &extra)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(buf: &[u8]) -> Vec<(String, String)> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < buf.len() {
+            let end = i + buf[i..]
+                .iter()
+                .position(|b| *b == b'\n' || *b == b'=')
+                .unwrap();
+            let key = String::from_utf8(buf[i..end].to_vec()).unwrap();
+            if buf[end] == b'=' {
+                let nl = end + buf[end..].iter().position(|b| *b == b'\n').unwrap();
+                out.push((key, String::from_utf8(buf[end + 1..nl].to_vec()).unwrap()));
+                i = nl + 1;
+            } else {
+                let mut len = [0u8; 8];
+                len.copy_from_slice(&buf[end + 1..end + 9]);
+                let n = u64::from_le_bytes(len) as usize;
+                let v = &buf[end + 9..end + 9 + n];
+                out.push((key, String::from_utf8(v.to_vec()).unwrap()));
+                i = end + 9 + n + 1;
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_encode_binary_form_for_multiline_values() {
+        let buf = encode(&[("A", "x".into()), ("B", "one\ntwo".into())]);
+        assert_eq!(
+            parse(&buf),
+            vec![("A".into(), "x".into()), ("B".into(), "one\ntwo".into())]
+        );
+    }
+
+    #[test]
+    fn test_send_event_to_local_socket() {
+        let td = tempfile::tempdir().unwrap();
+        let path = td.path().join("journal.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        let sink = JournalSink::new("oxidizr-deb").with_socket(&path);
+        let data = json!({"event": "pm.purge", "pm": {"package": "coreutils"}, "exit_code": 100});
+        sink.send_event("pm.purge", &data).unwrap();
+
+        let mut buf = vec![0u8; 65536];
+        let n = server.recv(&mut buf).unwrap();
+        let fields = parse(&buf[..n]);
+        let get = |k: &str| fields.iter().find(|(f, _)| f == k).map(|(_, v)| v.clone());
+        assert_eq!(get("SYSLOG_IDENTIFIER").as_deref(), Some("oxidizr-deb"));
+        assert_eq!(get("OXIDIZR_EVENT").as_deref(), Some("pm.purge"));
+        assert_eq!(get("OXIDIZR_PACKAGE").as_deref(), Some("coreutils"));
+        assert_eq!(get("PRIORITY").as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn test_send_fails_without_listener() {
+        let td = tempfile::tempdir().unwrap();
+        let sink = JournalSink::new("oxidizr-deb").with_socket(&td.path().join("missing.sock"));
+        assert!(sink.send("hello", PRIORITY_INFO, &[]).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-//! Common helpers for oxidizr-* CLIs (API builder, prompts, cross-distro core)
+//! Common helpers for oxidizr-* CLIs (API builder, prompts, audit log, cross-distro core)
 
 pub mod prompts {
     use std::io::{self, Write};
@@ -29,12 +29,22 @@ pub mod api {
     use std::path::PathBuf;
 
     use switchyard::adapters::{DefaultSmokeRunner, FileLockManager, FsOwnershipOracle};
-    use switchyard::logging::JsonlSink;
+    use switchyard::logging::{AuditSink, FactsEmitter, JsonlSink};
     use switchyard::policy::Policy;
     use switchyard::Switchyard;
 
     pub fn build_api(policy: Policy, lock_path: PathBuf) -> Switchyard<JsonlSink, JsonlSink> {
-        Switchyard::builder(JsonlSink, JsonlSink, policy)
+        build_api_with_sinks(policy, lock_path, JsonlSink, JsonlSink)
+    }
+
+    /// Like `build_api`, with caller-provided facts and audit sinks (e.g. `audit::AuditLog`).
+    pub fn build_api_with_sinks<E: FactsEmitter, A: AuditSink>(
+        policy: Policy,
+        lock_path: PathBuf,
+        facts: E,
+        audit: A,
+    ) -> Switchyard<E, A> {
+        Switchyard::builder(facts, audit, policy)
             .with_lock_manager(Box::new(FileLockManager::new(lock_path)))
             .with_smoke_runner(Box::new(DefaultSmokeRunner))
             .with_ownership_oracle(Box::new(FsOwnershipOracle))
@@ -44,8 +54,12 @@ pub mod api {
 
 // Cross-distro core modules
 pub mod adapter;
+pub mod audit;
 pub mod coverage2;
+pub mod events;
+pub mod journald;
 pub mod packages;
+pub mod report;
 
 // Re-exports for convenience
 pub use adapter::DistroAdapter;
@@ -0,0 +1,254 @@
+//! The result document an oxidizr-* CLI prints for `--output json`: one JSON object per run,
+//! saying whether the root changed (or, in a dry run, would change), which filesystem actions were
+//! planned and executed, which apt transactions were planned or ran, which applets were skipped
+//! and why, and any warnings. Commands with a JSON payload of their own (`status`, `doctor`, ...)
+//! put it under `data`.
+//!
+//! Versioned like events: adding an optional field is backwards compatible, anything else bumps
+//! `REPORT_SCHEMA_VERSION`.
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::events::{Event, PmRun, SkipApplet};
+
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// One change to the root's filesystem.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Point `target` at `source`; `changes` is false when it already does.
+    Link {
+        source: String,
+        target: String,
+        changes: bool,
+    },
+    /// Put the backed-up original back at `target`; `changes` is false when it is already there.
+    Restore { target: String, changes: bool },
+    /// Divert the distro file at `path` out of the way (`dpkg-divert --rename`).
+    Divert { path: String },
+    /// Remove the diversion of `path`, putting the distro file back.
+    Undivert { path: String },
+    /// Register (if needed) and select `select` in the alternatives group `name`.
+    Alternative { name: String, select: String },
+    /// Drop the alternatives group `name`.
+    RemoveAlternative { name: String },
+    /// A file the CLI writes itself (apt hook snippet, imported bundle).
+    Write { path: String },
+    /// A file the CLI removes itself.
+    Remove { path: String },
+}
+
+impl Action {
+    /// Whether carrying out the action alters the root.
+    pub fn changes(&self) -> bool {
+        match self {
+            Action::Link { changes, .. } | Action::Restore { changes, .. } => *changes,
+            _ => true,
+        }
+    }
+}
+
+/// An apt transaction (or a .deb unpacked without dpkg): planned in a dry run, executed otherwise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AptRecord {
+    pub tool: String,
+    pub install: Vec<String>,
+    pub purge: Vec<String>,
+    pub executed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+impl AptRecord {
+    pub fn planned(tool: &str, install: &[String], purge: &[String]) -> Self {
+        AptRecord {
+            tool: tool.to_string(),
+            install: install.to_vec(),
+            purge: purge.to_vec(),
+            executed: false,
+            exit_code: None,
+        }
+    }
+
+    fn from_run(run: &PmRun, purge: bool) -> Self {
+        // The event names the package(s); its argv may be wrapped in chroot or Dir:: options
+        let packages: Vec<String> = run
+            .pm
+            .package
+            .iter()
+            .flat_map(|p| p.split_whitespace())
+            .map(str::to_string)
+            .collect();
+        let (install, purge) = if purge {
+            (vec![], packages)
+        } else {
+            (packages, vec![])
+        };
+        AptRecord {
+            tool: run.pm.tool.clone(),
+            install,
+            purge,
+            executed: true,
+            exit_code: Some(run.exit_code),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(title = "oxidizr run result")]
+pub struct RunReport {
+    pub schema_version: u32,
+    pub command: String,
+    pub root: String,
+    pub dry_run: bool,
+    pub ok: bool,
+    /// See `RunReport::changed`.
+    pub changed: bool,
+    pub planned_actions: Vec<Action>,
+    /// Empty in a dry run.
+    pub executed_actions: Vec<Action>,
+    pub apt_transactions: Vec<AptRecord>,
+    pub skipped_applets: Vec<SkipApplet>,
+    pub warnings: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    /// `{code, exit_code, message, hint}` when the run failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<Value>,
+}
+
+impl RunReport {
+    pub fn new(command: &str, root: &str, dry_run: bool) -> Self {
+        RunReport {
+            schema_version: REPORT_SCHEMA_VERSION,
+            command: command.to_string(),
+            root: root.to_string(),
+            dry_run,
+            ok: true,
+            changed: false,
+            planned_actions: vec![],
+            executed_actions: vec![],
+            apt_transactions: vec![],
+            skipped_applets: vec![],
+            warnings: vec![],
+            data: None,
+            error: None,
+        }
+    }
+
+    /// Fold in an event: executed apt transactions and skipped applets. Other events carry nothing
+    /// the report lists.
+    pub fn observe(&mut self, event: &Event) {
+        match event {
+            Event::PmInstall(run) => self.apt_transactions.push(AptRecord::from_run(run, false)),
+            Event::PmPurge(run) => self.apt_transactions.push(AptRecord::from_run(run, true)),
+            Event::PmTransaction(tx) if !tx.simulate => self.apt_transactions.push(AptRecord {
+                tool: tx.run.pm.tool.clone(),
+                install: tx.install.clone(),
+                purge: tx.purge.clone(),
+                executed: true,
+                exit_code: Some(tx.run.exit_code),
+            }),
+            Event::PmUnpack(u) => self.apt_transactions.push(AptRecord {
+                tool: "unpack".to_string(),
+                install: vec![u.deb.clone()],
+                purge: vec![],
+                executed: true,
+                exit_code: None,
+            }),
+            Event::SkipApplet(s) if !self.skipped_applets.contains(s) => {
+                self.skipped_applets.push(s.clone())
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether the run changed the root: an executed action that alters it, or a change to the
+    /// installed packages (`packages_changed`, which only the caller can observe, since apt may
+    /// find nothing to do). A dry run reports whether committing would change it: a planned action
+    /// that alters the root, or a planned apt transaction (callers leave out apt steps that
+    /// would not change the installed packages).
+    pub fn changed(&self, packages_changed: bool) -> bool {
+        if self.dry_run {
+            self.planned_actions.iter().any(Action::changes)
+                || self
+                    .apt_transactions
+                    .iter()
+                    .any(|t| !t.executed && (!t.install.is_empty() || !t.purge.is_empty()))
+        } else {
+            self.executed_actions.iter().any(Action::changes) || packages_changed
+        }
+    }
+}
+
+/// JSON Schema of the result document.
+pub fn schema() -> Schema {
+    schemars::schema_for!(RunReport)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{PmTransaction, SkipReason};
+
+    fn run(tool: &str, args: &[&str], package: Option<&str>, exit_code: i32) -> PmRun {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        PmRun::new(tool, &args, package, exit_code, b"")
+    }
+
+    #[test]
+    fn test_observe_collects_transactions_and_skips() {
+        let mut r = RunReport::new("replace", "/", false);
+        r.observe(&Event::PmInstall(run(
+            "apt-get",
+            &["install", "-y", "rust-coreutils=0.1.0-1"],
+            Some("rust-coreutils"),
+            0,
+        )));
+        r.observe(&Event::PmTransaction(PmTransaction {
+            simulate: true,
+            install: vec![],
+            purge: vec!["coreutils".into()],
+            run: run("apt-get", &["-s"], None, 0),
+        }));
+        let skip = SkipApplet {
+            applet: "chcon".into(),
+            reason: SkipReason::NotInReplacement,
+            source: "/usr/bin/coreutils".into(),
+        };
+        r.observe(&Event::SkipApplet(skip.clone()));
+        r.observe(&Event::SkipApplet(skip.clone()));
+
+        assert_eq!(r.apt_transactions.len(), 1, "simulations are not listed");
+        assert_eq!(r.apt_transactions[0].install, vec!["rust-coreutils"]);
+        assert_eq!(r.apt_transactions[0].exit_code, Some(0));
+        assert_eq!(r.skipped_applets, vec![skip]);
+    }
+
+    #[test]
+    fn test_changed_ignores_links_already_in_place() {
+        let link = |changes| Action::Link {
+            source: "/usr/bin/coreutils".into(),
+            target: "/usr/bin/ls".into(),
+            changes,
+        };
+        let mut r = RunReport::new("use", "/", false);
+        r.executed_actions.push(link(false));
+        assert!(!r.changed(false));
+        assert!(r.changed(true), "package changes count");
+        r.executed_actions.push(link(true));
+        assert!(r.changed(false));
+
+        let mut dry = RunReport::new("use", "/", true);
+        dry.planned_actions.push(link(false));
+        assert!(!dry.changed(false));
+        dry.apt_transactions.push(AptRecord::planned(
+            "apt-get",
+            &["rust-coreutils".to_string()],
+            &[],
+        ));
+        assert!(dry.changed(false));
+    }
+}
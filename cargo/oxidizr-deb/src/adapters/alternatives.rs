@@ -1 +1,410 @@
-// Optional: update-alternatives integration (not yet implemented).
+//! update-alternatives integration: each applet becomes a link group (`/usr/bin/ls` ->
+//! `/etc/alternatives/ls`) with the GNU binary (diverted to `/usr/bin/ls.gnu` through
+//! [`divert`](crate::adapters::divert)) and the replacement registered as providers. Switching is
+//! a change of the selected alternative, so `restore --keep-replacements` leaves the replacement
+//! installed and registered, but de-preferred.
+//!
+//! On a live root we go through `update-alternatives` itself. Under a non-live `--root` we edit
+//! `var/lib/dpkg/alternatives/<name>` and `etc/alternatives/<name>` directly, in the same format.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use oxidizr_cli_core::events::{Event, PmRun};
+use oxidizr_cli_core::report::Action;
+use switchyard::types::ApplyMode;
+
+use crate::adapters::divert;
+use crate::errors::{CliError, CliResult};
+use crate::output;
+use crate::state::audit;
+use crate::state::rooted;
+use crate::util::paths::ensure_under_root;
+
+pub const ADMIN_REL: &str = "var/lib/dpkg/alternatives";
+pub const ALT_DIR_REL: &str = "etc/alternatives";
+pub const GNU_SUFFIX: &str = ".gnu";
+pub const GNU_PRIORITY: i32 = 50;
+pub const REPLACEMENT_PRIORITY: i32 = 100;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Selection {
+    Auto,
+    Manual,
+}
+
+/// One alternative of a group with its priority and one path per group slave
+/// (empty when it does not provide that slave).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Provider {
+    pub path: PathBuf,
+    pub priority: i32,
+    pub slaves: Vec<String>,
+}
+
+/// One link group, as stored in `var/lib/dpkg/alternatives/<name>`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Group {
+    pub name: String,
+    pub selection: Selection,
+    /// Master link as seen from inside the root, e.g. `/usr/bin/ls`.
+    pub link: PathBuf,
+    /// Slave `(name, link)` pairs, e.g. `("ls.1.gz", "/usr/share/man/man1/ls.1.gz")`.
+    pub slaves: Vec<(String, PathBuf)>,
+    pub providers: Vec<Provider>,
+}
+
+impl Group {
+    /// Parse the admin file format: status, master link, slave name/link pairs, blank line,
+    /// then `path`/`priority`/slave paths per alternative, terminated by a blank line.
+    pub fn parse(name: &str, s: &str) -> Result<Group, String> {
+        let mut lines = s.lines();
+        let mut next = |what: &str| {
+            lines
+                .next()
+                .map(|l| l.to_string())
+                .ok_or_else(|| format!("alternatives group {name}: missing {what}"))
+        };
+        let selection = match next("status")?.as_str() {
+            "auto" => Selection::Auto,
+            "manual" => Selection::Manual,
+            other => return Err(format!("alternatives group {name}: bad status {other:?}")),
+        };
+        let link = PathBuf::from(next("master link")?);
+        let mut slaves = Vec::new();
+        loop {
+            let l = next("slave list terminator")?;
+            if l.is_empty() {
+                break;
+            }
+            slaves.push((l, PathBuf::from(next("slave link")?)));
+        }
+        let mut providers = Vec::new();
+        loop {
+            let path = next("alternative path")?;
+            if path.is_empty() {
+                break;
+            }
+            let priority = next("priority")?
+                .trim()
+                .parse::<i32>()
+                .map_err(|e| format!("alternatives group {name}: bad priority: {e}"))?;
+            let mut paths = Vec::with_capacity(slaves.len());
+            for _ in 0..slaves.len() {
+                paths.push(next("slave path")?);
+            }
+            providers.push(Provider {
+                path: PathBuf::from(path),
+                priority,
+                slaves: paths,
+            });
+        }
+        Ok(Group {
+            name: name.to_string(),
+            selection,
+            link,
+            slaves,
+            providers,
+        })
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str(match self.selection {
+            Selection::Auto => "auto\n",
+            Selection::Manual => "manual\n",
+        });
+        out.push_str(&format!("{}\n", self.link.display()));
+        for (name, link) in &self.slaves {
+            out.push_str(&format!("{}\n{}\n", name, link.display()));
+        }
+        out.push('\n');
+        for p in &self.providers {
+            out.push_str(&format!("{}\n{}\n", p.path.display(), p.priority));
+            for i in 0..self.slaves.len() {
+                out.push_str(p.slaves.get(i).map(String::as_str).unwrap_or(""));
+                out.push('\n');
+            }
+        }
+        out.push('\n');
+        out
+    }
+
+    fn has_provider(&self, path: &Path) -> bool {
+        self.providers.iter().any(|p| p.path == path)
+    }
+
+    /// Register `path` at `priority`, keeping the slave paths of an existing registration.
+    fn set_provider(&mut self, path: &Path, priority: i32) {
+        match self.providers.iter_mut().find(|p| p.path == path) {
+            Some(p) => p.priority = priority,
+            None => self.providers.push(Provider {
+                path: path.to_path_buf(),
+                priority,
+                slaves: vec![String::new(); self.slaves.len()],
+            }),
+        }
+    }
+}
+
+pub fn read_group(root: &Path, name: &str) -> Result<Option<Group>, String> {
+    let p = root.join(ADMIN_REL).join(name);
+    match fs::read_to_string(&p) {
+        Ok(s) => Group::parse(name, &s).map(Some),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("failed to read {}: {e}", p.display())),
+    }
+}
+
+fn update_alternatives(args: &[String], event: fn(PmRun) -> Event) -> CliResult {
+    let out = Command::new("update-alternatives")
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| format!("failed to spawn update-alternatives: {e}"))?;
+    let code = out.status.code().unwrap_or(1);
+    audit::emit(event(PmRun::new(
+        "update-alternatives",
+        args,
+        None,
+        code,
+        &out.stderr,
+    )));
+    if code != 0 {
+        return Err(CliError::PackageManager(format!(
+            "update-alternatives {} failed with exit code {}",
+            args.join(" "),
+            code
+        )));
+    }
+    Ok(())
+}
+
+fn replace_symlink(link: &Path, to: &Path) -> Result<(), String> {
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    let tmp = link.with_file_name(format!(
+        ".{}.oxidizr-tmp",
+        link.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let _ = fs::remove_file(&tmp);
+    std::os::unix::fs::symlink(to, &tmp)
+        .map_err(|e| format!("failed to create {}: {e}", tmp.display()))?;
+    fs::rename(&tmp, link).map_err(|e| format!("failed to replace {}: {e}", link.display()))
+}
+
+/// Native (non-live) equivalent of `--install` + `--set`: write the admin file and both
+/// symlinks. Like the links `use` creates under `--root`, the symlinks carry host paths.
+fn write_native(root: &Path, g: &Group, choose: &Path) -> Result<(), String> {
+    let admin = root.join(ADMIN_REL).join(&g.name);
+    if let Some(parent) = admin.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    fs::write(&admin, g.render().as_bytes())
+        .map_err(|e| format!("failed to write {}: {e}", admin.display()))?;
+    let alt_link = root.join(ALT_DIR_REL).join(&g.name);
+    replace_symlink(&alt_link, &ensure_under_root(root, choose))?;
+    replace_symlink(&ensure_under_root(root, &g.link), &alt_link)
+}
+
+/// Register GNU and replacement providers for each applet and select the replacement.
+/// Applets without a GNU binary to fall back to are skipped; returns the applets now managed.
+pub fn install_applets(
+    root: &Path,
+    source_bin: &Path,
+    applets: &[String],
+    mode: ApplyMode,
+) -> CliResult<Vec<String>> {
+    let live = root == Path::new("/");
+    let rust = rooted(root, source_bin);
+    let dest_base = ensure_under_root(root, Path::new(crate::packages::DEST_DIR));
+    let mut managed = Vec::new();
+    for app in applets {
+        let dst = dest_base.join(app);
+        let link = rooted(root, &dst);
+        let gnu = PathBuf::from(format!("{}{}", link.display(), GNU_SUFFIX));
+        let host_gnu = ensure_under_root(root, &gnu);
+        let is_file = fs::symlink_metadata(&dst)
+            .map(|m| m.file_type().is_file())
+            .unwrap_or(false);
+        if !is_file && fs::symlink_metadata(&host_gnu).is_err() {
+            eprintln!(
+                "[info] {}: no GNU binary to register as fallback; skipping",
+                link.display()
+            );
+            continue;
+        }
+        managed.push(app.clone());
+        output::action(
+            mode,
+            Action::Alternative {
+                name: app.clone(),
+                select: rust.display().to_string(),
+            },
+        );
+        if matches!(mode, ApplyMode::DryRun) {
+            eprintln!(
+                "[dry-run] would register alternatives {}: {} ({}), {} ({}) and select {}",
+                app,
+                gnu.display(),
+                GNU_PRIORITY,
+                rust.display(),
+                REPLACEMENT_PRIORITY,
+                rust.display()
+            );
+            continue;
+        }
+        // The master link must be free: divert the GNU file aside to become its provider, so
+        // dpkg upgrades land on the `.gnu` name instead of replacing the master link.
+        if is_file {
+            divert::add(root, &dst)?;
+        }
+        if live {
+            for (path, prio) in [(&gnu, GNU_PRIORITY), (&rust, REPLACEMENT_PRIORITY)] {
+                update_alternatives(
+                    &[
+                        "--install".into(),
+                        link.display().to_string(),
+                        app.clone(),
+                        path.display().to_string(),
+                        prio.to_string(),
+                    ],
+                    Event::PmAlternativesInstall,
+                )?;
+            }
+            update_alternatives(
+                &["--set".into(), app.clone(), rust.display().to_string()],
+                Event::PmAlternativesSet,
+            )?;
+        } else {
+            let mut g = read_group(root, app)?.unwrap_or(Group {
+                name: app.clone(),
+                selection: Selection::Manual,
+                link: link.clone(),
+                slaves: vec![],
+                providers: vec![],
+            });
+            g.selection = Selection::Manual;
+            g.set_provider(&gnu, GNU_PRIORITY);
+            g.set_provider(&rust, REPLACEMENT_PRIORITY);
+            write_native(root, &g, &rust)?;
+        }
+    }
+    Ok(managed)
+}
+
+/// `true` for groups `install_applets` created: master link in `/usr/bin` and a `.gnu` provider.
+fn is_ours(g: &Group) -> bool {
+    let gnu = PathBuf::from(format!("{}{}", g.link.display(), GNU_SUFFIX));
+    g.link.starts_with(crate::packages::DEST_DIR) && g.has_provider(&gnu)
+}
+
+/// Split applets into (not managed, managed via an alternatives group).
+pub fn split_managed(
+    root: &Path,
+    applets: &[String],
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut plain = Vec::new();
+    let mut managed = Vec::new();
+    for app in applets {
+        if read_group(root, app)?.map(|g| is_ours(&g)).unwrap_or(false) {
+            managed.push(app.clone());
+        } else {
+            plain.push(app.clone());
+        }
+    }
+    Ok((plain, managed))
+}
+
+/// `restore --keep-replacements`: keep every provider registered but select GNU.
+pub fn prefer_gnu(root: &Path, applets: &[String], mode: ApplyMode) -> CliResult {
+    for app in applets {
+        let g = match read_group(root, app)? {
+            Some(g) => g,
+            None => continue,
+        };
+        let gnu = PathBuf::from(format!("{}{}", g.link.display(), GNU_SUFFIX));
+        if !g.has_provider(&gnu) {
+            return Err(CliError::InvariantViolation(format!(
+                "alternatives group {} has no GNU provider ({}); cannot de-prefer the replacement",
+                app,
+                gnu.display()
+            )));
+        }
+        output::action(
+            mode,
+            Action::Alternative {
+                name: app.clone(),
+                select: gnu.display().to_string(),
+            },
+        );
+        if matches!(mode, ApplyMode::DryRun) {
+            eprintln!("[dry-run] would select {} for {}", gnu.display(), app);
+        } else if root == Path::new("/") {
+            update_alternatives(
+                &["--set".into(), app.clone(), gnu.display().to_string()],
+                Event::PmAlternativesSet,
+            )?;
+        } else {
+            write_native(root, &g, &gnu)?;
+        }
+    }
+    Ok(())
+}
+
+/// Full restore: drop the link groups and undo the diversions, moving the GNU binaries back to
+/// their own names.
+pub fn remove_applets(root: &Path, applets: &[String], mode: ApplyMode) -> CliResult {
+    for app in applets {
+        let g = match read_group(root, app)? {
+            Some(g) => g,
+            None => continue,
+        };
+        let host_link = ensure_under_root(root, &g.link);
+        let host_gnu = ensure_under_root(
+            root,
+            Path::new(&format!("{}{}", g.link.display(), GNU_SUFFIX)),
+        );
+        output::action(mode, Action::RemoveAlternative { name: app.clone() });
+        if matches!(mode, ApplyMode::DryRun) {
+            eprintln!(
+                "[dry-run] would remove alternatives group {} and restore {}",
+                app,
+                g.link.display()
+            );
+            continue;
+        }
+        if root == Path::new("/") {
+            update_alternatives(
+                &["--remove-all".into(), app.clone()],
+                Event::PmAlternativesRemove,
+            )?;
+        } else {
+            let _ = fs::remove_file(root.join(ALT_DIR_REL).join(app));
+            fs::remove_file(root.join(ADMIN_REL).join(app))
+                .map_err(|e| format!("failed to remove alternatives group {app}: {e}"))?;
+        }
+        if let Ok(md) = fs::symlink_metadata(&host_link) {
+            if md.file_type().is_symlink() {
+                let _ = fs::remove_file(&host_link);
+            }
+        }
+        divert::remove(root, &host_link)?;
+        // Groups set up before the diversion was registered only renamed the GNU file.
+        if fs::symlink_metadata(&host_gnu).is_ok() && fs::symlink_metadata(&host_link).is_err() {
+            fs::rename(&host_gnu, &host_link).map_err(|e| {
+                format!(
+                    "failed to rename {} -> {}: {e}",
+                    host_gnu.display(),
+                    host_link.display()
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,297 @@
+//! Minimal `.deb` writer and reader.
+//!
+//! Written packages are an `ar` archive of `debian-binary`, an uncompressed `control.tar` and an
+//! uncompressed `data.tar`; dpkg accepts uncompressed members, so no compressor is needed. The
+//! reader also takes the `.gz`, `.xz` and `.zst` members that distribution packages ship.
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Component, Path};
+
+/// Tar of `(name, contents, mode)` regular files under `./`, with root ownership and a zero
+/// mtime so identical inputs give identical archives.
+pub fn tar_of(files: &[(&str, &[u8], u32)]) -> Result<Vec<u8>, String> {
+    let mut b = tar::Builder::new(Vec::new());
+    let mut dir = tar::Header::new_gnu();
+    dir.set_entry_type(tar::EntryType::Directory);
+    dir.set_mode(0o755);
+    dir.set_size(0);
+    dir.set_mtime(0);
+    dir.set_cksum();
+    b.append_data(&mut dir, "./", std::io::empty())
+        .map_err(|e| format!("failed to build tar: {e}"))?;
+    for (name, data, mode) in files {
+        let mut h = tar::Header::new_gnu();
+        h.set_mode(*mode);
+        h.set_size(data.len() as u64);
+        h.set_mtime(0);
+        h.set_cksum();
+        b.append_data(&mut h, format!("./{name}"), *data)
+            .map_err(|e| format!("failed to build tar: {e}"))?;
+    }
+    b.into_inner()
+        .map_err(|e| format!("failed to build tar: {e}"))
+}
+
+/// Common `ar` format as read by dpkg: global magic, then 60-byte member headers with
+/// members padded to an even length.
+fn ar_of(members: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut out = b"!<arch>\n".to_vec();
+    for (name, data) in members {
+        out.extend(
+            format!(
+                "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+                name,
+                0,
+                0,
+                0,
+                100644,
+                data.len()
+            )
+            .as_bytes(),
+        );
+        out.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            out.push(b'\n');
+        }
+    }
+    out
+}
+
+/// Assemble a `.deb` from its control and data tarballs.
+pub fn build(control_tar: &[u8], data_tar: &[u8]) -> Vec<u8> {
+    ar_of(&[
+        ("debian-binary", b"2.0\n"),
+        ("control.tar", control_tar),
+        ("data.tar", data_tar),
+    ])
+}
+
+/// Tar of the paths in a dpkg file list (`/.`, `/usr`, `/usr/bin/ls`, ...) as they exist under
+/// `root`, keeping modes, owners and symlinks. Paths that no longer exist are skipped.
+pub fn tar_of_paths(root: &Path, paths: &[String]) -> Result<Vec<u8>, String> {
+    let mut b = tar::Builder::new(Vec::new());
+    b.follow_symlinks(false);
+    for p in paths {
+        let rel = p.trim_start_matches('/');
+        let name = if rel.is_empty() || rel == "." {
+            "./".to_string()
+        } else {
+            format!("./{rel}")
+        };
+        let src = root.join(rel);
+        if src.symlink_metadata().is_err() {
+            continue;
+        }
+        b.append_path_with_name(&src, &name)
+            .map_err(|e| format!("failed to add {} to tar: {e}", src.display()))?;
+    }
+    b.into_inner()
+        .map_err(|e| format!("failed to build tar: {e}"))
+}
+
+/// Members of a common-format `ar` archive, in order.
+fn ar_members(bytes: &[u8]) -> Result<Vec<(String, &[u8])>, String> {
+    let mut rest = bytes
+        .strip_prefix(b"!<arch>\n".as_slice())
+        .ok_or("not an ar archive")?;
+    let mut out = Vec::new();
+    while rest.len() >= 60 {
+        let (header, body) = rest.split_at(60);
+        let field =
+            |r: std::ops::Range<usize>| String::from_utf8_lossy(&header[r]).trim().to_string();
+        // GNU ar terminates names with '/'
+        let name = field(0..16).trim_end_matches('/').to_string();
+        let size: usize = field(48..58)
+            .parse()
+            .map_err(|_| format!("invalid size in ar member header {name:?}"))?;
+        if body.len() < size {
+            return Err(format!("truncated ar member {name:?}"));
+        }
+        out.push((name, &body[..size]));
+        rest = &body[(size + size % 2).min(body.len())..];
+    }
+    Ok(out)
+}
+
+/// Decompress a `control.tar*`/`data.tar*` member by its suffix.
+fn decompress(name: &str, raw: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let res = match name.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("tar") => return Ok(raw.to_vec()),
+        Some("gz") => flate2::read::GzDecoder::new(raw)
+            .read_to_end(&mut out)
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        Some("xz") => lzma_rs::xz_decompress(&mut &raw[..], &mut out).map_err(|e| e.to_string()),
+        Some("zst") => ruzstd::decoding::StreamingDecoder::new(raw)
+            .map_err(|e| e.to_string())
+            .and_then(|mut d| d.read_to_end(&mut out).map_err(|e| e.to_string()))
+            .map(|_| ()),
+        _ => return Err(format!("unsupported compression for {name}")),
+    };
+    res.map(|_| out)
+        .map_err(|e| format!("failed to decompress {name}: {e}"))
+}
+
+/// `./usr/bin/ls` -> `usr/bin/ls`; `None` for the top directory. Absolute paths and `..` are
+/// rejected so nothing lands outside the root.
+fn member_path(path: &Path) -> Result<Option<String>, String> {
+    let mut parts = Vec::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::Normal(n) => parts.push(n.to_string_lossy().to_string()),
+            _ => return Err(format!("unsafe path in package: {}", path.display())),
+        }
+    }
+    Ok((!parts.is_empty()).then(|| parts.join("/")))
+}
+
+/// Whether chown is possible, i.e. owners from the package can be kept.
+fn running_as_root() -> bool {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata("/proc/self").is_ok_and(|m| m.uid() == 0)
+}
+
+/// A binary package read into memory.
+#[derive(Debug)]
+pub struct Deb {
+    /// Top-level files of `control.tar` (`control`, `md5sums`, `conffiles`, maintainer
+    /// scripts, ...) with their modes.
+    pub control_files: BTreeMap<String, (Vec<u8>, u32)>,
+    data_tar: Vec<u8>,
+}
+
+impl Deb {
+    pub fn read(path: &Path) -> Result<Deb, String> {
+        let bytes =
+            std::fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        let members = ar_members(&bytes).map_err(|e| format!("{}: {e}", path.display()))?;
+        let find = |prefix: &str| {
+            members
+                .iter()
+                .find(|(n, _)| n.starts_with(prefix))
+                .ok_or_else(|| format!("{}: no {prefix}* member", path.display()))
+        };
+        let (_, version) = find("debian-binary")?;
+        if !version.starts_with(b"2.") {
+            return Err(format!("{}: unsupported .deb format", path.display()));
+        }
+        let (cname, control) = find("control.tar")?;
+        let (dname, data) = find("data.tar")?;
+        let control_tar =
+            decompress(cname, control).map_err(|e| format!("{}: {e}", path.display()))?;
+        let data_tar = decompress(dname, data).map_err(|e| format!("{}: {e}", path.display()))?;
+
+        let mut control_files = BTreeMap::new();
+        let mut archive = tar::Archive::new(control_tar.as_slice());
+        let entries = archive
+            .entries()
+            .map_err(|e| format!("{}: invalid control.tar: {e}", path.display()))?;
+        for entry in entries {
+            let mut entry =
+                entry.map_err(|e| format!("{}: invalid control.tar: {e}", path.display()))?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let name = entry
+                .path()
+                .ok()
+                .and_then(|p| member_path(&p).ok().flatten())
+                .filter(|n| !n.contains('/'));
+            let Some(name) = name else { continue };
+            let mode = entry.header().mode().unwrap_or(0o644);
+            let mut data = Vec::new();
+            entry
+                .read_to_end(&mut data)
+                .map_err(|e| format!("{}: failed to read {name}: {e}", path.display()))?;
+            control_files.insert(name, (data, mode));
+        }
+        if !control_files.contains_key("control") {
+            return Err(format!(
+                "{}: control.tar has no control file",
+                path.display()
+            ));
+        }
+        Ok(Deb {
+            control_files,
+            data_tar,
+        })
+    }
+
+    pub fn control(&self) -> String {
+        String::from_utf8_lossy(&self.control_files["control"].0).to_string()
+    }
+
+    /// Value of a single-line control field, e.g. `Package` or `Architecture`.
+    pub fn field(&self, name: &str) -> Option<String> {
+        self.control().lines().find_map(|l| {
+            let (k, v) = l.split_once(':')?;
+            (!l.starts_with([' ', '\t']) && k.trim().eq_ignore_ascii_case(name))
+                .then(|| v.trim().to_string())
+        })
+    }
+
+    /// Absolute paths listed in the `conffiles` member.
+    pub fn conffiles(&self) -> Vec<String> {
+        self.control_files
+            .get("conffiles")
+            .map(|(b, _)| {
+                String::from_utf8_lossy(b)
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| l.starts_with('/'))
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Unpack `data.tar` into `root` keeping modes, symlinks and hard links (and owners when
+    /// running as root). Files replace whatever is at their path; an existing conffile is kept
+    /// and the packaged one written next to it as `<path>.dpkg-dist`. Returns the paths in dpkg
+    /// `.list` form: `/.`, `/usr`, `/usr/bin/ls`, ...
+    pub fn unpack(&self, root: &Path) -> Result<Vec<String>, String> {
+        let conffiles = self.conffiles();
+        let mut archive = tar::Archive::new(self.data_tar.as_slice());
+        archive.set_preserve_permissions(true);
+        archive.set_preserve_ownerships(running_as_root());
+        archive.set_overwrite(true);
+        let mut paths = vec!["/.".to_string()];
+        let entries = archive
+            .entries()
+            .map_err(|e| format!("invalid data.tar: {e}"))?;
+        for entry in entries {
+            let mut entry = entry.map_err(|e| format!("invalid data.tar: {e}"))?;
+            let path = entry
+                .path()
+                .map_err(|e| format!("invalid path in data.tar: {e}"))?
+                .into_owned();
+            let Some(rel) = member_path(&path)? else {
+                continue;
+            };
+            let abs = format!("/{rel}");
+            let dest = root.join(&rel);
+            if conffiles.contains(&abs) && dest.symlink_metadata().is_ok() {
+                let dist = root.join(format!("{rel}.dpkg-dist"));
+                entry
+                    .unpack(&dist)
+                    .map_err(|e| format!("failed to unpack {abs}: {e}"))?;
+                eprintln!(
+                    "[info] kept existing conffile {abs}; packaged version at {abs}.dpkg-dist"
+                );
+            } else if !entry
+                .unpack_in(root)
+                .map_err(|e| format!("failed to unpack {abs}: {e}"))?
+            {
+                return Err(format!(
+                    "refusing to unpack {abs}: it would land outside the root"
+                ));
+            }
+            if !paths.contains(&abs) {
+                paths.push(abs);
+            }
+        }
+        Ok(paths)
+    }
+}
@@ -1,3 +1,10 @@
+pub use oxidizr_cli_core::events::{AptDelta, PkgChange};
+use oxidizr_cli_core::events::{Event, PackageSet, PmRun, PmSimulate, PmTransaction};
+
+use crate::adapters::image;
+use crate::errors::{CliError, CliResult};
+use crate::state::audit;
+
 pub fn pm_lock_message(root: &std::path::Path) -> Option<String> {
     use fs2::FileExt;
     use std::fs::OpenOptions;
@@ -27,3 +34,241 @@ pub fn pm_lock_message(root: &std::path::Path) -> Option<String> {
     }
     None
 }
+
+/// Run `apt-get ARGS` non-interactively against `root` (see `image` for non-live roots) and emit
+/// its `pm.*` event (`Event::PmInstall` and the like).
+pub fn apt_get(
+    root: &std::path::Path,
+    args: &[String],
+    event: fn(PmRun) -> Event,
+    package: &str,
+) -> CliResult {
+    use std::process::{Command, Stdio};
+    let (program, mut full) = image::apt_invocation(root);
+    full.extend(args.iter().cloned());
+    let out = Command::new(program)
+        .args(&full)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| format!("failed to spawn {program}: {e}"))?;
+    let code = out.status.code().unwrap_or(1);
+    audit::emit(event(PmRun::new(
+        program,
+        &full,
+        Some(package),
+        code,
+        &out.stderr,
+    )));
+    if code != 0 {
+        return Err(CliError::PackageManager(format!(
+            "{} {} failed with exit code {}",
+            program,
+            full.join(" "),
+            code
+        )));
+    }
+    Ok(())
+}
+
+/// One apt-get transaction covering every selected family.
+#[derive(Debug, Default, Clone)]
+pub struct AptTransaction {
+    pub install: Vec<String>,
+    pub purge: Vec<String>,
+    /// Pass `--allow-remove-essential`.
+    pub allow_remove_essential: bool,
+    /// Pass `--no-download`: only local `.deb`s and apt's cache may be used.
+    pub no_download: bool,
+}
+
+impl AptTransaction {
+    /// `install -y A B --purge C- D-`, or `purge -y C D` when nothing is installed.
+    fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.allow_remove_essential {
+            args.push("--allow-remove-essential".to_string());
+        }
+        if self.no_download {
+            args.push("--no-download".to_string());
+        }
+        if self.install.is_empty() {
+            args.push("purge".to_string());
+            args.push("-y".to_string());
+            args.extend(self.purge.iter().cloned());
+        } else {
+            args.push("install".to_string());
+            args.push("-y".to_string());
+            args.extend(self.install.iter().cloned());
+            if !self.purge.is_empty() {
+                args.push("--purge".to_string());
+                args.extend(self.purge.iter().map(|p| format!("{p}-")));
+            }
+        }
+        args
+    }
+}
+
+/// Run `tx` as one apt-get invocation. With `simulate`, apt only plans it (`-s`), which validates
+/// the whole set before anything changes; under a non-live root apt is pointed at that root's
+/// configuration and dpkg database (see `image::apt_invocation`), and local `.deb` paths are
+/// given as apt sees them. Emits one `pm.transaction` event per run and returns apt's stdout.
+pub fn apt_transaction(
+    root: &std::path::Path,
+    tx: &AptTransaction,
+    simulate: bool,
+) -> CliResult<String> {
+    use std::process::{Command, Stdio};
+    let (program, mut args) = image::apt_invocation(root);
+    if simulate {
+        args.push("-s".to_string());
+    }
+    let mut aimed = tx.clone();
+    for entry in aimed.install.iter_mut().filter(|e| e.contains('/')) {
+        *entry = image::apt_path(root, std::path::Path::new(entry.as_str()))?;
+    }
+    args.extend(aimed.args());
+    let out = Command::new(program)
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| format!("failed to spawn {program}: {e}"))?;
+    let code = out.status.code().unwrap_or(1);
+    audit::emit(Event::PmTransaction(PmTransaction {
+        simulate,
+        install: tx.install.clone(),
+        purge: tx.purge.clone(),
+        run: PmRun::new(program, &args, None, code, &out.stderr),
+    }));
+    if code != 0 {
+        return Err(CliError::PackageManager(format!(
+            "{} {} failed with exit code {}{}",
+            program,
+            args.join(" "),
+            code,
+            if simulate {
+                " (simulation; nothing was changed)"
+            } else {
+                ""
+            }
+        )));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).to_string())
+}
+
+/// Parse `apt-get -s` output. `Conf` and unrelated lines are ignored.
+pub fn parse_simulation(stdout: &str) -> AptDelta {
+    let mut delta = AptDelta::default();
+    for line in stdout.lines() {
+        let mut words = line.splitn(3, ' ');
+        let (kind, name) = match (words.next(), words.next()) {
+            (Some(k @ ("Inst" | "Remv" | "Purg")), Some(n)) if !n.is_empty() => (k, n),
+            _ => continue,
+        };
+        let rest = words.next().unwrap_or("");
+        let from = rest
+            .strip_prefix('[')
+            .and_then(|r| r.split_once(']'))
+            .map(|(v, _)| v.to_string());
+        let to = rest
+            .split_once('(')
+            .and_then(|(_, r)| r.split_whitespace().next())
+            .map(|v| v.trim_end_matches(')').to_string());
+        let change = PkgChange {
+            name: name.to_string(),
+            from,
+            to,
+        };
+        match kind {
+            "Inst" => delta.install.push(change),
+            "Remv" => delta.remove.push(change),
+            _ => delta.purge.push(change),
+        }
+    }
+    delta
+}
+
+/// Simulate `tx` against `root` and return the full package delta. Essential removals are
+/// allowed in the simulation so the delta is complete; callers decide whether to go ahead.
+/// Emits a `pm.simulate` event carrying the delta.
+pub fn simulate(root: &std::path::Path, tx: &AptTransaction) -> CliResult<AptDelta> {
+    let sim = AptTransaction {
+        allow_remove_essential: true,
+        ..tx.clone()
+    };
+    let mut delta = parse_simulation(&apt_transaction(root, &sim, true)?);
+    let db = super::dpkg::StatusDb::load(root)?;
+    delta.essential = delta
+        .removals()
+        .filter(|c| db.get(&c.name).is_some_and(|p| p.essential))
+        .map(|c| c.name.clone())
+        .collect();
+    audit::emit(Event::PmSimulate(PmSimulate {
+        root: root.display().to_string(),
+        requested: PackageSet {
+            install: tx.install.clone(),
+            purge: tx.purge.clone(),
+        },
+        delta: delta.clone(),
+    }));
+    Ok(delta)
+}
+
+/// Human rendering of a simulated delta, one line per package. Removals the caller did not
+/// ask for (reverse dependencies) and Essential packages are called out.
+pub fn describe_delta(tx: &AptTransaction, delta: &AptDelta) -> Vec<String> {
+    let mut lines = vec![format!(
+        "apt would install {}, remove {}, purge {} package(s)",
+        delta.install.len(),
+        delta.remove.len(),
+        delta.purge.len()
+    )];
+    for c in &delta.install {
+        let ver = match (&c.from, &c.to) {
+            (Some(f), Some(t)) => format!(" {f} -> {t}"),
+            (None, Some(t)) => format!(" {t}"),
+            _ => String::new(),
+        };
+        lines.push(format!("  Inst {}{}", c.name, ver));
+    }
+    let unrequested = delta.unrequested_removals(&tx.purge);
+    for (kind, list) in [("Remv", &delta.remove), ("Purg", &delta.purge)] {
+        for c in list {
+            let mut notes = Vec::new();
+            if unrequested.contains(&c) {
+                notes.push("not requested; pulled in by dependencies".to_string());
+            }
+            if delta.essential.contains(&c.name) {
+                notes.push("Essential: yes; needs --allow-remove-essential".to_string());
+            }
+            let ver = c
+                .from
+                .as_deref()
+                .map(|v| format!(" {v}"))
+                .unwrap_or_default();
+            let notes = if notes.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", notes.join("; "))
+            };
+            lines.push(format!("  {} {}{}{}", kind, c.name, ver, notes));
+        }
+    }
+    lines
+}
+
+/// Dry-run preview: simulate `tx` and print its delta. A root without usable apt configuration
+/// (e.g. a bare test root) only gets a note; the preview never fails the dry-run.
+pub fn preview_transaction(root: &std::path::Path, tx: &AptTransaction) {
+    match simulate(root, tx) {
+        Ok(delta) => {
+            for line in describe_delta(tx, &delta) {
+                eprintln!("[dry-run] {line}");
+            }
+        }
+        Err(e) => eprintln!("[dry-run] apt simulation unavailable: {e}"),
+    }
+}
@@ -1 +1,290 @@
-// Optional: dpkg-divert integration (not yet implemented).
+//! dpkg-divert integration: move the GNU file aside (`/usr/bin/ls` -> `/usr/bin/ls.gnu`) so dpkg
+//! upgrades land on the diverted name and never clobber the replacement link.
+//!
+//! On a live root we go through `dpkg-divert` itself. Under a non-live `--root` we edit
+//! `var/lib/dpkg/diversions` directly with the same semantics as `--rename`, so fakeroots work
+//! without dpkg.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use oxidizr_cli_core::events::{Event, PmRun};
+use oxidizr_cli_core::report::Action;
+
+use crate::errors::{CliError, CliResult};
+use crate::output;
+use crate::state::audit;
+use crate::state::rooted;
+use crate::util::paths::ensure_under_root;
+
+pub const DIVERSIONS_REL: &str = "var/lib/dpkg/diversions";
+pub const DIVERT_PACKAGE: &str = "oxidizr-deb";
+pub const DIVERT_SUFFIX: &str = ".gnu";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diversion {
+    /// Path as seen from inside the root, e.g. `/usr/bin/ls`.
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Owning package; `:` marks a local diversion.
+    pub package: String,
+}
+
+/// Parse `var/lib/dpkg/diversions`: three lines per entry (from, to, package).
+pub fn read_diversions(root: &Path) -> Result<Vec<Diversion>, String> {
+    let p = root.join(DIVERSIONS_REL);
+    let s = match fs::read_to_string(&p) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("failed to read {}: {e}", p.display())),
+    };
+    let lines: Vec<&str> = s.lines().collect();
+    if !lines.len().is_multiple_of(3) {
+        return Err(format!(
+            "{} is truncated ({} lines, expected a multiple of 3)",
+            p.display(),
+            lines.len()
+        ));
+    }
+    Ok(lines
+        .chunks(3)
+        .map(|c| Diversion {
+            from: PathBuf::from(c[0]),
+            to: PathBuf::from(c[1]),
+            package: c[2].to_string(),
+        })
+        .collect())
+}
+
+fn write_diversions(root: &Path, list: &[Diversion]) -> Result<(), String> {
+    let p = root.join(DIVERSIONS_REL);
+    if let Some(parent) = p.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    let mut body = String::new();
+    for d in list {
+        body.push_str(&format!(
+            "{}\n{}\n{}\n",
+            d.from.display(),
+            d.to.display(),
+            d.package
+        ));
+    }
+    let tmp = p.with_extension("oxidizr-tmp");
+    fs::write(&tmp, body.as_bytes())
+        .map_err(|e| format!("failed to write {}: {e}", tmp.display()))?;
+    fs::rename(&tmp, &p).map_err(|e| format!("failed to write {}: {e}", p.display()))
+}
+
+/// Diverted name for a target (`/usr/bin/ls` -> `/usr/bin/ls.gnu`).
+pub fn diverted_path(target: &Path) -> PathBuf {
+    let mut s = target.as_os_str().to_owned();
+    s.push(DIVERT_SUFFIX);
+    PathBuf::from(s)
+}
+
+/// Our diversion for `target` (a host path under `root`), if any.
+pub fn ours(root: &Path, target: &Path) -> Result<Option<Diversion>, String> {
+    let from = rooted(root, target);
+    Ok(read_diversions(root)?
+        .into_iter()
+        .find(|d| d.from == from && d.package == DIVERT_PACKAGE))
+}
+
+fn dpkg_divert(args: &[String], event: fn(PmRun) -> Event) -> CliResult {
+    let out = Command::new("dpkg-divert")
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| format!("failed to spawn dpkg-divert: {e}"))?;
+    let code = out.status.code().unwrap_or(1);
+    audit::emit(event(PmRun::new(
+        "dpkg-divert",
+        args,
+        None,
+        code,
+        &out.stderr,
+    )));
+    if code != 0 {
+        return Err(CliError::PackageManager(format!(
+            "dpkg-divert {} failed with exit code {}",
+            args.join(" "),
+            code
+        )));
+    }
+    Ok(())
+}
+
+/// Divert the file at `target` (host path under `root`) to `<target>.gnu`, renaming it.
+/// Idempotent for our own diversions; refuses paths diverted by another package.
+pub fn add(root: &Path, target: &Path) -> CliResult {
+    let from = rooted(root, target);
+    let to = diverted_path(&from);
+    let mut list = read_diversions(root)?;
+    if let Some(d) = list.iter().find(|d| d.from == from) {
+        if d.package == DIVERT_PACKAGE {
+            return Ok(());
+        }
+        return Err(CliError::InvariantViolation(format!(
+            "{} is already diverted by {}; refusing to divert it again",
+            from.display(),
+            d.package
+        )));
+    }
+    if root == Path::new("/") {
+        return dpkg_divert(
+            &[
+                "--package".to_string(),
+                DIVERT_PACKAGE.to_string(),
+                "--divert".to_string(),
+                to.display().to_string(),
+                "--rename".to_string(),
+                "--add".to_string(),
+                from.display().to_string(),
+            ],
+            Event::PmDivertAdd,
+        );
+    }
+    let host_to = ensure_under_root(root, &to);
+    if fs::symlink_metadata(target).is_ok() && fs::symlink_metadata(&host_to).is_err() {
+        fs::rename(target, &host_to).map_err(|e| {
+            format!(
+                "failed to rename {} -> {}: {e}",
+                target.display(),
+                host_to.display()
+            )
+        })?;
+    }
+    list.push(Diversion {
+        from,
+        to,
+        package: DIVERT_PACKAGE.to_string(),
+    });
+    Ok(write_diversions(root, &list)?)
+}
+
+/// Undo our diversion of `target`: drop whatever sits at `target` if it is a symlink
+/// (our replacement link) and rename `<target>.gnu` back.
+pub fn remove(root: &Path, target: &Path) -> CliResult {
+    let d = match ours(root, target)? {
+        Some(d) => d,
+        None => return Ok(()),
+    };
+    if let Ok(md) = fs::symlink_metadata(target) {
+        if md.file_type().is_symlink() {
+            fs::remove_file(target)
+                .map_err(|e| format!("failed to remove {}: {e}", target.display()))?;
+        }
+    }
+    if root == Path::new("/") {
+        return dpkg_divert(
+            &[
+                "--package".to_string(),
+                DIVERT_PACKAGE.to_string(),
+                "--rename".to_string(),
+                "--remove".to_string(),
+                d.from.display().to_string(),
+            ],
+            Event::PmDivertRemove,
+        );
+    }
+    let host_to = ensure_under_root(root, &d.to);
+    if fs::symlink_metadata(&host_to).is_ok() && fs::symlink_metadata(target).is_err() {
+        fs::rename(&host_to, target).map_err(|e| {
+            format!(
+                "failed to rename {} -> {}: {e}",
+                host_to.display(),
+                target.display()
+            )
+        })?;
+    }
+    let list: Vec<Diversion> = read_diversions(root)?
+        .into_iter()
+        .filter(|x| x != &d)
+        .collect();
+    Ok(write_diversions(root, &list)?)
+}
+
+/// Divert every applet target that is not already a symlink; returns the in-root paths now diverted.
+pub fn divert_applets(
+    root: &Path,
+    applets: &[String],
+    mode: switchyard::types::ApplyMode,
+) -> CliResult<Vec<PathBuf>> {
+    let dest_base = ensure_under_root(root, Path::new(crate::packages::DEST_DIR));
+    let mut out = Vec::new();
+    for app in applets {
+        let dst = dest_base.join(app);
+        let is_link = fs::symlink_metadata(&dst)
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false);
+        if is_link && ours(root, &dst)?.is_none() {
+            continue;
+        }
+        output::action(
+            mode,
+            Action::Divert {
+                path: rooted(root, &dst).display().to_string(),
+            },
+        );
+        if matches!(mode, switchyard::types::ApplyMode::DryRun) {
+            eprintln!(
+                "[dry-run] would divert {} -> {}",
+                rooted(root, &dst).display(),
+                diverted_path(&rooted(root, &dst)).display()
+            );
+        } else {
+            add(root, &dst)?;
+        }
+        out.push(rooted(root, &dst));
+    }
+    Ok(out)
+}
+
+/// Split applets into (not diverted, diverted by oxidizr-deb).
+pub fn split_diverted(
+    root: &Path,
+    applets: &[String],
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let dest_base = ensure_under_root(root, Path::new(crate::packages::DEST_DIR));
+    let mut plain = Vec::new();
+    let mut diverted = Vec::new();
+    for app in applets {
+        if ours(root, &dest_base.join(app))?.is_some() {
+            diverted.push(app.clone());
+        } else {
+            plain.push(app.clone());
+        }
+    }
+    Ok((plain, diverted))
+}
+
+/// Remove our diversions for these applets, putting the GNU files back in place.
+pub fn undivert_applets(
+    root: &Path,
+    applets: &[String],
+    mode: switchyard::types::ApplyMode,
+) -> CliResult {
+    let dest_base = ensure_under_root(root, Path::new(crate::packages::DEST_DIR));
+    for app in applets {
+        let dst = dest_base.join(app);
+        output::action(
+            mode,
+            Action::Undivert {
+                path: rooted(root, &dst).display().to_string(),
+            },
+        );
+        if matches!(mode, switchyard::types::ApplyMode::DryRun) {
+            eprintln!(
+                "[dry-run] would remove diversion of {}",
+                rooted(root, &dst).display()
+            );
+        } else {
+            remove(root, &dst)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,342 @@
+//! Native readers for the dpkg database under a root, so queries work on chroots and images
+//! without spawning `dpkg-query`, and a minimal writer that records packages unpacked without
+//! dpkg (see `unpack`).
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const INFO_REL: &str = "var/lib/dpkg/info";
+
+/// Paths owned by `package`, from `var/lib/dpkg/info/<package>.list` and any multi-arch
+/// `<package>:<arch>.list`. Empty when the package is not installed in `root`.
+pub fn package_files(root: &Path, package: &str) -> Vec<String> {
+    let dir = root.join(INFO_REL);
+    let entries = match fs::read_dir(&dir) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+    let arch_prefix = format!("{package}:");
+    let mut out = Vec::new();
+    for e in entries.filter_map(|e| e.ok()) {
+        let fname = e.file_name().to_string_lossy().to_string();
+        let stem = match fname.strip_suffix(".list") {
+            Some(s) => s,
+            None => continue,
+        };
+        if stem != package && !stem.starts_with(&arch_prefix) {
+            continue;
+        }
+        if let Ok(s) = fs::read_to_string(e.path()) {
+            out.extend(
+                s.lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .map(str::to_string),
+            );
+        }
+    }
+    out
+}
+
+pub const STATUS_REL: &str = "var/lib/dpkg/status";
+
+/// One stanza of `var/lib/dpkg/status`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageStatus {
+    pub package: String,
+    /// `Status:` triplet, e.g. `install ok installed`.
+    pub status: String,
+    pub version: Option<String>,
+    pub architecture: Option<String>,
+    pub essential: bool,
+    /// Names from `Provides:`, version constraints stripped.
+    pub provides: Vec<String>,
+    /// Raw `Depends:` clauses, e.g. `libc6 (>= 2.34)` or `a | b`.
+    pub depends: Vec<String>,
+    /// Raw `Pre-Depends:` clauses.
+    pub pre_depends: Vec<String>,
+}
+
+impl PackageStatus {
+    /// Fully unpacked and configured; `dpkg -s` also succeeds for removed-but-configured packages.
+    pub fn is_installed(&self) -> bool {
+        self.status.split_whitespace().last() == Some("installed")
+    }
+}
+
+/// Package names in a relationship field, alternatives flattened, versions and arch qualifiers
+/// stripped: `a (>= 1) | b:any, c` -> `[a, b, c]`.
+pub fn relation_names(clauses: &[String]) -> Vec<String> {
+    clauses
+        .iter()
+        .flat_map(|c| c.split('|'))
+        .filter_map(|alt| {
+            let name = alt.trim().split([' ', '(', '[', ':']).next()?.trim();
+            (!name.is_empty()).then(|| name.to_string())
+        })
+        .collect()
+}
+
+fn split_clauses(v: &str) -> Vec<String> {
+    v.split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse the text of a dpkg status file.
+pub fn parse_status(text: &str) -> Vec<PackageStatus> {
+    let mut out = Vec::new();
+    for stanza in text.split("\n\n") {
+        let mut fields: Vec<(String, String)> = Vec::new();
+        for line in stanza.lines() {
+            if line.starts_with([' ', '\t']) {
+                // Continuation of the previous (multi-line) field.
+                if let Some((_, v)) = fields.last_mut() {
+                    v.push('\n');
+                    v.push_str(line.trim());
+                }
+            } else if let Some((k, v)) = line.split_once(':') {
+                fields.push((k.trim().to_string(), v.trim().to_string()));
+            }
+        }
+        let mut p = PackageStatus::default();
+        for (k, v) in fields {
+            match k.as_str() {
+                "Package" => p.package = v,
+                "Status" => p.status = v,
+                "Version" => p.version = Some(v),
+                "Architecture" => p.architecture = Some(v),
+                "Essential" => p.essential = v.eq_ignore_ascii_case("yes"),
+                "Provides" => p.provides = relation_names(&split_clauses(&v)),
+                "Depends" => p.depends = split_clauses(&v),
+                "Pre-Depends" => p.pre_depends = split_clauses(&v),
+                _ => {}
+            }
+        }
+        if !p.package.is_empty() {
+            out.push(p);
+        }
+    }
+    out
+}
+
+/// The dpkg status database of a root.
+#[derive(Debug, Default)]
+pub struct StatusDb {
+    pub packages: Vec<PackageStatus>,
+}
+
+impl StatusDb {
+    /// Read `<root>/var/lib/dpkg/status`; a missing file is an empty database.
+    pub fn load(root: &Path) -> Result<StatusDb, String> {
+        let p = root.join(STATUS_REL);
+        match fs::read_to_string(&p) {
+            Ok(s) => Ok(StatusDb {
+                packages: parse_status(&s),
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(StatusDb::default()),
+            Err(e) => Err(format!("failed to read {}: {e}", p.display())),
+        }
+    }
+
+    /// Entry for `name` (`pkg` or `pkg:arch`), preferring an installed instance.
+    pub fn get(&self, name: &str) -> Option<&PackageStatus> {
+        let (pkg, arch) = match name.split_once(':') {
+            Some((p, a)) => (p, Some(a)),
+            None => (name, None),
+        };
+        let mut matching = self.packages.iter().filter(|p| {
+            p.package == pkg && arch.is_none_or(|a| p.architecture.as_deref() == Some(a))
+        });
+        let first = matching.clone().next();
+        matching.find(|p| p.is_installed()).or(first)
+    }
+
+    pub fn is_installed(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|p| p.is_installed())
+    }
+}
+
+/// `true` when `name` is installed in `root`; unreadable databases count as not installed.
+pub fn is_installed(root: &Path, name: &str) -> bool {
+    StatusDb::load(root)
+        .map(|db| db.is_installed(name))
+        .unwrap_or(false)
+}
+
+/// Installed version of `name` in `root`.
+pub fn installed_version(root: &Path, name: &str) -> Option<String> {
+    let db = StatusDb::load(root).ok()?;
+    db.get(name)
+        .filter(|p| p.is_installed())
+        .and_then(|p| p.version.clone())
+}
+
+/// The installed stanza of `name` as a `DEBIAN/control` file: the status-only fields
+/// (`Status`, `Config-Version`, `Conffiles`) dropped, everything else verbatim.
+pub fn control_stanza(root: &Path, name: &str) -> Option<String> {
+    let text = fs::read_to_string(root.join(STATUS_REL)).ok()?;
+    let stanza = text.split("\n\n").find(|stanza| {
+        parse_status(stanza)
+            .first()
+            .is_some_and(|p| p.package == name && p.is_installed())
+    })?;
+    let mut out = String::new();
+    let mut skipping = false;
+    for line in stanza.lines() {
+        if !line.starts_with([' ', '\t']) {
+            let key = line.split_once(':').map(|(k, _)| k.trim()).unwrap_or("");
+            skipping = matches!(key, "Status" | "Config-Version" | "Conffiles");
+        }
+        if !skipping {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    Some(out)
+}
+
+/// Contents of `var/lib/dpkg/info/<name>.<ext>` (or the multi-arch `<name>:<arch>.<ext>`), e.g.
+/// a maintainer script or `conffiles`.
+pub fn info_file(root: &Path, name: &str, ext: &str) -> Option<Vec<u8>> {
+    let dir = root.join(INFO_REL);
+    if let Ok(b) = fs::read(dir.join(format!("{name}.{ext}"))) {
+        return Some(b);
+    }
+    let prefix = format!("{name}:");
+    let suffix = format!(".{ext}");
+    fs::read_dir(&dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .find(|e| {
+            let f = e.file_name().to_string_lossy().to_string();
+            f.strip_prefix(&prefix)
+                .and_then(|r| r.strip_suffix(&suffix))
+                .is_some_and(|arch| !arch.is_empty() && !arch.contains('.'))
+        })
+        .and_then(|e| fs::read(e.path()).ok())
+}
+
+/// Name of a package's `info/` files: `<name>` or, for `Multi-Arch: same`, `<name>:<arch>`.
+fn info_name(control: &str, name: &str, arch: &str) -> String {
+    let multi_arch_same = control.lines().any(|l| {
+        l.split_once(':')
+            .is_some_and(|(k, v)| k.trim() == "Multi-Arch" && v.trim() == "same")
+    });
+    if multi_arch_same {
+        format!("{name}:{arch}")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Record a package in `root`'s dpkg database the way dpkg does after unpacking it: the file
+/// list as `info/<pkg>.list`, the other control members (`md5sums`, `conffiles`, maintainer
+/// scripts, ...) as `info/<pkg>.<member>`, and the control stanza with `status` (e.g.
+/// `install ok installed`) in `status`, replacing any previous entry for the package.
+pub fn register(
+    root: &Path,
+    control: &str,
+    files: &[String],
+    control_files: &BTreeMap<String, (Vec<u8>, u32)>,
+    conffiles: &[String],
+    status: &str,
+) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let parsed = parse_status(control);
+    let pkg = parsed.first().ok_or("control file has no Package field")?;
+    let arch = pkg
+        .architecture
+        .clone()
+        .unwrap_or_else(|| "all".to_string());
+    let info = root.join(INFO_REL);
+    fs::create_dir_all(&info).map_err(|e| format!("failed to create {}: {e}", info.display()))?;
+    let base = info_name(control, &pkg.package, &arch);
+
+    // Members of a previously registered version must not outlive it
+    if let Ok(entries) = fs::read_dir(&info) {
+        let prefix = format!("{base}.");
+        for e in entries.filter_map(|e| e.ok()) {
+            let f = e.file_name().to_string_lossy().to_string();
+            if f.strip_prefix(&prefix)
+                .is_some_and(|ext| !ext.contains('.'))
+            {
+                let _ = fs::remove_file(e.path());
+            }
+        }
+    }
+    let write = |ext: &str, data: &[u8], mode: u32| -> Result<(), String> {
+        let p = info.join(format!("{base}.{ext}"));
+        fs::write(&p, data).map_err(|e| format!("failed to write {}: {e}", p.display()))?;
+        fs::set_permissions(&p, fs::Permissions::from_mode(mode))
+            .map_err(|e| format!("failed to chmod {}: {e}", p.display()))
+    };
+    let mut list = files.join("\n");
+    list.push('\n');
+    write("list", list.as_bytes(), 0o644)?;
+    for (member, (data, mode)) in control_files.iter().filter(|(m, _)| *m != "control") {
+        write(member, data, *mode)?;
+    }
+
+    let mut stanza = String::new();
+    for line in control.trim_end().lines() {
+        stanza.push_str(line);
+        stanza.push('\n');
+        if line.starts_with("Package:") {
+            stanza.push_str(&format!("Status: {status}\n"));
+        }
+    }
+    if !conffiles.is_empty() {
+        // dpkg's marker for conffiles that have not been through a configure yet
+        stanza.push_str("Conffiles:\n");
+        for c in conffiles {
+            stanza.push_str(&format!(" {c} newconffile\n"));
+        }
+    }
+    replace_stanza(root, &pkg.package, &arch, Some(&stanza))
+}
+
+/// Rewrite `status` with the entry for `name` (`arch`) replaced by `stanza`, or dropped when
+/// `None`. The file is swapped in with a rename so readers never see a partial database.
+pub fn replace_stanza(
+    root: &Path,
+    name: &str,
+    arch: &str,
+    stanza: Option<&str>,
+) -> Result<(), String> {
+    let p = root.join(STATUS_REL);
+    let text = match fs::read_to_string(&p) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("failed to read {}: {e}", p.display())),
+    };
+    let mut stanzas: Vec<String> = text
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .filter(|s| {
+            !parse_status(s).first().is_some_and(|e| {
+                e.package == name
+                    && (e.architecture.as_deref() == Some(arch)
+                        || arch == "all"
+                        || e.architecture.as_deref() == Some("all"))
+            })
+        })
+        .map(str::to_string)
+        .collect();
+    if let Some(s) = stanza {
+        stanzas.push(s.trim().to_string());
+    }
+    let mut out = stanzas.join("\n\n");
+    out.push('\n');
+    if let Some(parent) = p.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    let tmp = p.with_extension("oxidizr-new");
+    fs::write(&tmp, out).map_err(|e| format!("failed to write {}: {e}", tmp.display()))?;
+    fs::rename(&tmp, &p).map_err(|e| format!("failed to replace {}: {e}", p.display()))
+}
@@ -0,0 +1,168 @@
+//! Image mode: apt and dpkg run against a non-live `--root` (a rootfs being built) the same way
+//! they run on the live system, so `use`, `replace` and `restore` behave identically there.
+//!
+//! - `dir`: `apt-get -o Dir=<root> -o DPkg::Options::=--root=<root>`. apt reads the root's sources,
+//!   lists and dpkg database; dpkg unpacks into the root and chroots for maintainer scripts.
+//! - `chroot`: `chroot <root> apt-get ...`, with the host's `/proc`, `/dev` and `/sys`
+//!   bind-mounted into the root while the command runs (see `ChrootMounts`).
+//!
+//! The mode is chosen with the global `--image-mode` flag, passed down like `--apt-version`.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use oxidizr_cli_core::events::{Event, ImageMount, ImageUmount};
+
+use crate::cli::args::ImageMode;
+use crate::state::audit;
+
+pub const IMAGE_MODE_ENV: &str = "OXIDIZR_DEB_IMAGE_MODE";
+
+/// Bind-mounted into the root for `chroot` mode, in mount order.
+const CHROOT_MOUNTS: &[&str] = &["proc", "dev", "sys"];
+
+impl ImageMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ImageMode::Dir => "dir",
+            ImageMode::Chroot => "chroot",
+        }
+    }
+}
+
+/// The image mode for `root`; the live root never uses one.
+pub fn mode(root: &Path) -> Option<ImageMode> {
+    if root == Path::new("/") {
+        return None;
+    }
+    match std::env::var(IMAGE_MODE_ENV).ok()?.as_str() {
+        "dir" => Some(ImageMode::Dir),
+        "chroot" => Some(ImageMode::Chroot),
+        _ => None,
+    }
+}
+
+/// Whether apt/dpkg can be run against `root`: the live root, or any root in image mode.
+pub fn apt_reachable(root: &Path) -> bool {
+    root == Path::new("/") || mode(root).is_some()
+}
+
+/// Program and leading arguments that aim `apt-get` at `root`. Without an image mode a non-live
+/// root only gets `-o Dir=<root>`, which is enough for simulations.
+pub fn apt_invocation(root: &Path) -> (&'static str, Vec<String>) {
+    let r = root.display();
+    match (root == Path::new("/"), mode(root)) {
+        (true, _) => ("apt-get", vec![]),
+        (false, Some(ImageMode::Chroot)) => ("chroot", vec![r.to_string(), "apt-get".to_string()]),
+        (false, Some(ImageMode::Dir)) => (
+            "apt-get",
+            vec![
+                "-o".to_string(),
+                format!("Dir={r}"),
+                "-o".to_string(),
+                format!("DPkg::Options::=--root={r}"),
+            ],
+        ),
+        (false, None) => ("apt-get", vec!["-o".to_string(), format!("Dir={r}")]),
+    }
+}
+
+/// How apt sees the host path `p`: unchanged, except inside a chroot, where it must lie under
+/// `root` and is given relative to it.
+pub fn apt_path(root: &Path, p: &Path) -> Result<String, String> {
+    if mode(root) != Some(ImageMode::Chroot) {
+        return Ok(p.display().to_string());
+    }
+    let canon = |x: &Path| fs::canonicalize(x).map_err(|e| format!("{}: {e}", x.display()));
+    let rel = canon(p)?
+        .strip_prefix(canon(root)?)
+        .map(Path::to_path_buf)
+        .map_err(|_| {
+            format!(
+                "{} is outside the chroot {}; copy it into the root first",
+                p.display(),
+                root.display()
+            )
+        })?;
+    Ok(Path::new("/").join(rel).display().to_string())
+}
+
+/// `true` when `target` is already a mount point (per `/proc/self/mountinfo`).
+fn is_mount_point(target: &Path) -> bool {
+    let Ok(info) = fs::read_to_string("/proc/self/mountinfo") else {
+        return false;
+    };
+    let target = target.display().to_string();
+    info.lines()
+        .filter_map(|l| l.split_whitespace().nth(4))
+        .any(|m| m == target)
+}
+
+fn run_mount(args: &[&str]) -> Result<(), String> {
+    let out = Command::new(args[0])
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("failed to spawn {}: {e}", args[0]))?;
+    if out.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        ))
+    }
+}
+
+/// The bind mounts of a `chroot`-mode run; unmounted again (in reverse order) when dropped,
+/// including on errors. Mount points that were already mounted are left alone.
+pub struct ChrootMounts {
+    mounted: Vec<PathBuf>,
+}
+
+impl ChrootMounts {
+    pub fn setup(root: &Path) -> Result<Self, String> {
+        let root = fs::canonicalize(root).map_err(|e| format!("{}: {e}", root.display()))?;
+        let mut guard = ChrootMounts { mounted: vec![] };
+        for name in CHROOT_MOUNTS {
+            let target = root.join(name);
+            if is_mount_point(&target) {
+                continue;
+            }
+            fs::create_dir_all(&target)
+                .map_err(|e| format!("failed to create {}: {e}", target.display()))?;
+            let source = format!("/{name}");
+            // On failure the guard drops here and unmounts what was already mounted
+            run_mount(&["mount", "--bind", &source, &target.display().to_string()])?;
+            audit::emit(Event::ImageMount(ImageMount {
+                source,
+                target: target.display().to_string(),
+            }));
+            guard.mounted.push(target);
+        }
+        Ok(guard)
+    }
+}
+
+impl Drop for ChrootMounts {
+    fn drop(&mut self) {
+        for target in self.mounted.iter().rev() {
+            let t = target.display().to_string();
+            // A process still holding the mount busy must not leave it attached to the image
+            let res = run_mount(&["umount", &t]).or_else(|_| run_mount(&["umount", "-l", &t]));
+            audit::emit(Event::ImageUmount(ImageUmount {
+                target: t,
+                ok: res.is_ok(),
+            }));
+        }
+    }
+}
+
+/// Mounts needed for the run: `Some` only in `chroot` mode.
+pub fn enter(root: &Path) -> Result<Option<ChrootMounts>, String> {
+    match mode(root) {
+        Some(ImageMode::Chroot) => ChrootMounts::setup(root).map(Some),
+        _ => Ok(None),
+    }
+}
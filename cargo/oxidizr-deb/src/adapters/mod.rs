@@ -1,7 +1,89 @@
 #[cfg(feature = "debian-alternatives")]
 pub mod alternatives;
+/// Without `debian-alternatives`, no applet is ever managed by update-alternatives.
+#[cfg(not(feature = "debian-alternatives"))]
+pub mod alternatives {
+    use std::path::Path;
+
+    use crate::errors::CliResult;
+
+    pub fn install_applets(
+        _root: &Path,
+        _source_bin: &Path,
+        _applets: &[String],
+        _mode: switchyard::types::ApplyMode,
+    ) -> CliResult<Vec<String>> {
+        Err(
+            "--alternatives requires oxidizr-deb built with the `debian-alternatives` feature"
+                .to_string()
+                .into(),
+        )
+    }
+
+    pub fn split_managed(
+        _root: &Path,
+        applets: &[String],
+    ) -> Result<(Vec<String>, Vec<String>), String> {
+        Ok((applets.to_vec(), vec![]))
+    }
+
+    pub fn prefer_gnu(
+        _root: &Path,
+        _applets: &[String],
+        _mode: switchyard::types::ApplyMode,
+    ) -> CliResult {
+        Ok(())
+    }
+
+    pub fn remove_applets(
+        _root: &Path,
+        _applets: &[String],
+        _mode: switchyard::types::ApplyMode,
+    ) -> CliResult {
+        Ok(())
+    }
+}
+pub mod debfile;
 pub mod debian;
 pub mod debian_adapter;
 #[cfg(feature = "debian-divert")]
 pub mod divert;
+pub mod dpkg;
+pub mod image;
+/// Without `debian-divert`, nothing is ever diverted and asking for it is an error.
+#[cfg(not(feature = "debian-divert"))]
+pub mod divert {
+    use std::path::{Path, PathBuf};
+
+    use crate::errors::CliResult;
+
+    pub fn divert_applets(
+        _root: &Path,
+        _applets: &[String],
+        _mode: switchyard::types::ApplyMode,
+    ) -> CliResult<Vec<PathBuf>> {
+        Err(
+            "--divert requires oxidizr-deb built with the `debian-divert` feature"
+                .to_string()
+                .into(),
+        )
+    }
+
+    pub fn split_diverted(
+        _root: &Path,
+        applets: &[String],
+    ) -> Result<(Vec<String>, Vec<String>), String> {
+        Ok((applets.to_vec(), vec![]))
+    }
+
+    pub fn undivert_applets(
+        _root: &Path,
+        _applets: &[String],
+        _mode: switchyard::types::ApplyMode,
+    ) -> CliResult {
+        Ok(())
+    }
+}
 pub mod preflight;
+pub mod provider_deb;
+pub mod unpack;
@@ -0,0 +1,63 @@
+//! Local "provider" shim packages, built in pure Rust.
+//!
+//! After `replace` purges a GNU package, everything that `Depends: coreutils` (or `sudo`) would
+//! leave apt's dependency graph broken. The shim `oxidizr-<gnu>-provider` declares
+//! `Provides`/`Replaces`/`Conflicts` for the GNU package and `Depends` on the replacement, so it
+//! is installed in the same transaction that purges the GNU package and keeps apt consistent.
+//! The shim has no files; see `debfile` for the archive format.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::adapters::{debfile, dpkg};
+use crate::cli::args::Package;
+use crate::fetch::fallback::apt_pkg_name;
+use crate::packages::distro_pkg_name;
+
+/// Where built shims are kept, so `restore` and later runs can find what was installed.
+pub const SHIM_DIR_REL: &str = "var/lib/oxidizr-deb/shims";
+
+pub fn shim_name(pkg: Package) -> String {
+    format!("oxidizr-{}-provider", distro_pkg_name(pkg))
+}
+
+/// The shim's control fields. `Provides` carries the installed GNU version (when known) so
+/// versioned dependencies such as `coreutils (>= 8.0)` stay satisfied.
+pub fn control(root: &Path, pkg: Package) -> String {
+    let gnu = distro_pkg_name(pkg);
+    let gnu_version = dpkg::installed_version(root, gnu);
+    let provides = match &gnu_version {
+        Some(v) => format!("{gnu} (= {v})"),
+        None => gnu.to_string(),
+    };
+    let version = match &gnu_version {
+        Some(v) => format!("{v}+oxidizr1"),
+        None => "1.0+oxidizr1".to_string(),
+    };
+    format!(
+        "Package: {name}\n\
+         Version: {version}\n\
+         Architecture: all\n\
+         Maintainer: oxidizr-deb <root@localhost>\n\
+         Priority: optional\n\
+         Section: admin\n\
+         Depends: {rs}\n\
+         Provides: {provides}\n\
+         Replaces: {gnu}\n\
+         Conflicts: {gnu}\n\
+         Description: {gnu} provided by {rs} (generated by oxidizr-deb)\n \
+         Keeps packages that depend on {gnu} installable after oxidizr-deb replaced it.\n",
+        name = shim_name(pkg),
+        rs = apt_pkg_name(pkg),
+    )
+}
+
+/// Build the shim `.deb` for `pkg` into `dir` and return its path.
+pub fn build(root: &Path, pkg: Package, dir: &Path) -> Result<PathBuf, String> {
+    let control = control(root, pkg);
+    let control_tar = debfile::tar_of(&[("control", control.as_bytes(), 0o644)])?;
+    let deb = debfile::build(&control_tar, &debfile::tar_of(&[])?);
+    fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+    let path = dir.join(format!("{}_all.deb", shim_name(pkg)));
+    fs::write(&path, deb).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+    Ok(path)
+}
@@ -0,0 +1,101 @@
+//! Install a `.deb` into a root without dpkg: unpack its `data.tar` and record the package in the
+//! root's dpkg database. This is how replacements are staged into chroots and images, where
+//! apt/dpkg do not run against `--root`.
+//!
+//! Maintainer scripts are not run. A package that has a `preinst` or `postinst` is recorded as
+//! `unpacked`, so `dpkg --configure -a` inside the image finishes it.
+use std::path::{Path, PathBuf};
+
+use oxidizr_cli_core::events::{Event, PmUnpack};
+
+use crate::adapters::debfile::Deb;
+use crate::adapters::dpkg;
+use crate::fetch::verifier::host_arch;
+use crate::output;
+use crate::state::archive::APT_CACHE_REL;
+use crate::state::audit;
+
+/// Newest `<name>_*.deb` for the root's architecture (or `all`) in `dir`, honouring an
+/// `OXIDIZR_DEB_APT_VERSION` pin.
+pub fn find_deb(dir: &Path, name: &str, arch: &str) -> Option<PathBuf> {
+    let prefix = match std::env::var("OXIDIZR_DEB_APT_VERSION") {
+        Ok(v) => format!("{}_{}_", name, v.replace(':', "%3a")),
+        Err(_) => format!("{name}_"),
+    };
+    let mut found: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|f| f.to_str())
+                .and_then(|f| f.strip_prefix(&prefix))
+                .is_some_and(|rest| {
+                    rest.ends_with(&format!("_{arch}.deb")) || rest.ends_with("_all.deb")
+                })
+        })
+        .collect();
+    // Versions do not sort lexically in general, but cache entries of one package usually do
+    found.sort();
+    found.pop()
+}
+
+/// `<name>`'s `.deb` in the root's apt cache, then the host's.
+pub fn find_cached_deb(root: &Path, name: &str) -> Option<PathBuf> {
+    let arch = host_arch(root);
+    [root.join(APT_CACHE_REL), Path::new("/").join(APT_CACHE_REL)]
+        .iter()
+        .find_map(|dir| find_deb(dir, name, &arch))
+}
+
+/// Unpack `deb` into `root` and register it; returns the package name.
+pub fn install_deb(root: &Path, deb_path: &Path) -> Result<String, String> {
+    let deb = Deb::read(deb_path)?;
+    let name = deb
+        .field("Package")
+        .ok_or_else(|| format!("{}: control file has no Package field", deb_path.display()))?;
+    let arch = deb.field("Architecture").unwrap_or_default();
+    let root_arch = host_arch(root);
+    if arch != "all" && arch != root_arch {
+        return Err(format!(
+            "{}: architecture {} does not match the root ({})",
+            deb_path.display(),
+            arch,
+            root_arch
+        ));
+    }
+    let files = deb.unpack(root)?;
+    let scripts = ["preinst", "postinst"]
+        .into_iter()
+        .filter(|s| deb.control_files.contains_key(*s))
+        .collect::<Vec<_>>();
+    let status = if scripts.is_empty() {
+        "install ok installed"
+    } else {
+        "install ok unpacked"
+    };
+    dpkg::register(
+        root,
+        &deb.control(),
+        &files,
+        &deb.control_files,
+        &deb.conffiles(),
+        status,
+    )?;
+    audit::emit(Event::PmUnpack(PmUnpack {
+        deb: deb_path.display().to_string(),
+        package: name.clone(),
+        version: deb.field("Version"),
+        root: root.display().to_string(),
+        files: files.len(),
+        status: status.to_string(),
+    }));
+    if !scripts.is_empty() {
+        output::warning(&format!(
+            "{} has maintainer scripts ({}) that were not run; run `dpkg --configure -a` inside the root to finish it",
+            name,
+            scripts.join(", ")
+        ));
+    }
+    Ok(name)
+}
@@ -1,7 +1,9 @@
 use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Package {
     Coreutils,
     Findutils,
@@ -31,6 +33,20 @@ pub struct Cli {
     #[arg(long, global = true)]
     pub apt_version: Option<String>,
 
+    /// Run apt/dpkg against a non-live --root (a rootfs being built): `dir` aims them at the root
+    /// with Dir:: options, `chroot` runs them inside it with /proc, /dev and /sys bind-mounted
+    #[arg(long, global = true, value_enum, value_name = "MODE")]
+    pub image_mode: Option<ImageMode>,
+
+    /// Where structured events go: `stderr` (JSON lines) or `journald` (`journalctl -t oxidizr-deb`)
+    #[arg(long, global = true, value_enum, value_name = "SINK")]
+    pub log_sink: Option<LogSink>,
+
+    /// `json` prints one result document on stdout: whether the root changed (or would), the
+    /// planned and executed actions, apt transactions, skipped applets and warnings
+    #[arg(long, global = true, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -48,6 +64,20 @@ pub enum Commands {
         /// Local artifact path when --offline (still validated)
         #[arg(long, value_name = "PATH")]
         use_local: Option<PathBuf>,
+        /// Install the replacement from a local .deb file instead of the configured APT sources
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["offline", "use_local", "apt_repo"])]
+        deb: Option<PathBuf>,
+        /// Install the replacement from a local flat APT repository (temporary `file:` source for this run)
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["offline", "use_local"])]
+        apt_repo: Option<PathBuf>,
+        /// Divert the GNU files with dpkg-divert so upgrades never overwrite the links
+        /// (requires the `debian-divert` feature)
+        #[arg(long, default_value_t = false)]
+        divert: bool,
+        /// Switch through update-alternatives link groups instead of direct symlinks
+        /// (requires the `debian-alternatives` feature)
+        #[arg(long, default_value_t = false, conflicts_with = "divert")]
+        alternatives: bool,
     },
     /// Restore GNU/stock tools for a package (or all)
     Restore {
@@ -69,6 +99,80 @@ pub enum Commands {
         /// Target all known packages
         #[arg(long, conflicts_with = "package")]
         all: bool,
+        /// Confirm purging packages marked Essential (coreutils) with --allow-remove-essential
+        #[arg(long, default_value_t = false)]
+        confirm_essential_removal: bool,
+    },
+    /// Re-link applets that drifted from the recorded state (e.g. after an apt upgrade)
+    Repair {
+        /// Package to repair; when omitted, repairs every recorded package
+        #[arg(value_enum)]
+        package: Option<Package>,
+        /// Output machine-readable JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Import a signed bundle of replacement binaries into /opt/oxidizr/replacements for offline use
+    ImportBundle {
+        /// Bundle file (.tar or .tar.zst) containing manifest.json, manifest.json.sig and binaries
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+    /// Manage the apt DPkg::Post-Invoke hook that re-asserts replacements after dpkg runs
+    Hooks {
+        #[command(subcommand)]
+        op: HooksOp,
+    },
+    /// Write a reviewable plan for use/restore/replace without touching the system
+    Plan {
+        /// Where to write the plan file (JSON)
+        #[arg(
+            long,
+            value_name = "FILE",
+            global = true,
+            default_value = "oxidizr-deb-plan.json"
+        )]
+        out: PathBuf,
+        #[command(subcommand)]
+        op: PlanOp,
+    },
+    /// Execute a plan file written by `plan`, refusing if the system drifted since
+    Apply {
+        /// Plan file produced by `oxidizr-deb plan`
+        #[arg(long, value_name = "FILE")]
+        plan: PathBuf,
+        /// Hash the plan was approved under (printed by `plan`)
+        #[arg(long, value_name = "HASH")]
+        expect_hash: String,
+    },
+    /// List the recorded history of mutating runs
+    History {
+        /// Output machine-readable JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Revert a recorded `replace`, reinstalling the archived GNU packages offline
+    Undo {
+        /// Operation id from `oxidizr-deb history`
+        #[arg(value_name = "ID")]
+        id: u64,
+    },
+    /// Entry point for image builders' customize hooks, e.g.
+    /// `mmdebstrap --customize-hook='oxidizr-deb mmdebstrap-hook replace "$1" --all'`:
+    /// commits against the rootfs without prompts, in image mode
+    MmdebstrapHook {
+        #[command(subcommand)]
+        op: ImageHookOp,
+    },
+    /// Inspect the audit log of committed runs (`var/log/oxidizr-deb/audit.jsonl`)
+    Audit {
+        #[command(subcommand)]
+        op: AuditOp,
+    },
+    /// Describe the structured events written to stderr, journald and the audit log
+    Events {
+        #[command(subcommand)]
+        op: EventsOp,
     },
     /// Report current active state
     Status {
@@ -90,6 +194,166 @@ pub enum Commands {
     },
 }
 
+impl Commands {
+    /// The command as typed, e.g. `hooks install`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Commands::Use { .. } => "use",
+            Commands::Restore { .. } => "restore",
+            Commands::Replace { .. } => "replace",
+            Commands::Repair { .. } => "repair",
+            Commands::ImportBundle { .. } => "import-bundle",
+            Commands::Hooks { op } => match op {
+                HooksOp::Install { .. } => "hooks install",
+                HooksOp::Remove => "hooks remove",
+                HooksOp::Run => "hooks run",
+            },
+            Commands::Plan { .. } => "plan",
+            Commands::Apply { .. } => "apply",
+            Commands::History { .. } => "history",
+            Commands::Undo { .. } => "undo",
+            Commands::MmdebstrapHook { .. } => "mmdebstrap-hook",
+            Commands::Audit {
+                op: AuditOp::Verify { .. },
+            } => "audit verify",
+            Commands::Events {
+                op: EventsOp::Schema,
+            } => "events schema",
+            Commands::Status { .. } => "status",
+            Commands::Doctor { .. } => "doctor",
+            Commands::Completions { .. } => "completions",
+        }
+    }
+
+    /// Whether the command was asked for machine-readable JSON (`--json`).
+    pub fn json(&self) -> bool {
+        match self {
+            Commands::Repair { json, .. }
+            | Commands::History { json }
+            | Commands::Status { json }
+            | Commands::Doctor { json } => *json,
+            Commands::Audit {
+                op: AuditOp::Verify { json },
+            } => *json,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HooksOp {
+    /// Write the apt.conf.d snippet into the root
+    Install {
+        /// oxidizr-deb binary path as seen from inside the root
+        #[arg(long, value_name = "PATH", default_value = "/usr/bin/oxidizr-deb")]
+        bin: PathBuf,
+    },
+    /// Remove the apt.conf.d snippet written by `hooks install`
+    Remove,
+    /// Invoked by the hook after each dpkg run; repairs recorded families
+    #[command(hide = true)]
+    Run,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AuditOp {
+    /// Check the hash chain for edited, missing or truncated records
+    Verify {
+        /// Output machine-readable JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum EventsOp {
+    /// Print the JSON Schema every event line validates against
+    Schema,
+}
+
+/// What `mmdebstrap-hook` runs against the rootfs.
+#[derive(Debug, Subcommand)]
+pub enum ImageHookOp {
+    /// `use` one or more packages
+    Use {
+        /// Root filesystem the hook is given (`$1`)
+        #[arg(value_name = "ROOTFS")]
+        rootfs: PathBuf,
+        /// Packages to use, in order
+        #[arg(value_enum, required = true)]
+        packages: Vec<Package>,
+    },
+    /// `replace` a package (or all)
+    Replace {
+        /// Root filesystem the hook is given (`$1`)
+        #[arg(value_name = "ROOTFS")]
+        rootfs: PathBuf,
+        /// Package to replace
+        #[arg(value_enum)]
+        package: Option<Package>,
+        /// Target all known packages
+        #[arg(long, conflicts_with = "package")]
+        all: bool,
+        /// Confirm purging packages marked Essential (coreutils) with --allow-remove-essential
+        #[arg(long, default_value_t = false)]
+        confirm_essential_removal: bool,
+    },
+}
+
+/// Operations that can be planned; arguments mirror the top-level commands.
+#[derive(Debug, Subcommand)]
+pub enum PlanOp {
+    /// Plan `use` for a package
+    Use {
+        #[arg(value_enum)]
+        package: Package,
+        /// Offline mode: use a local artifact instead of fetching
+        #[arg(long, default_value_t = false)]
+        offline: bool,
+        /// Local artifact path when --offline
+        #[arg(long, value_name = "PATH")]
+        use_local: Option<PathBuf>,
+    },
+    /// Plan `restore` for a package (or all)
+    Restore {
+        #[arg(value_enum)]
+        package: Option<Package>,
+        #[arg(long, conflicts_with = "package")]
+        all: bool,
+        /// Keep RS packages installed but de-preferred
+        #[arg(long, default_value_t = false)]
+        keep_replacements: bool,
+    },
+    /// Plan `replace` for a package (or all)
+    Replace {
+        #[arg(value_enum)]
+        package: Option<Package>,
+        #[arg(long, conflicts_with = "package")]
+        all: bool,
+        /// Record in the plan that purging Essential packages (coreutils) is confirmed
+        #[arg(long, default_value_t = false)]
+        confirm_essential_removal: bool,
+    },
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum ImageMode {
+    Dir,
+    Chroot,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum LogSink {
+    Stderr,
+    Journald,
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
 pub enum Shell {
     Bash,
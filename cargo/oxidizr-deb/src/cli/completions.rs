@@ -1,18 +1,34 @@
+use std::io::Write;
+
 use clap::CommandFactory;
 use clap_complete::shells::{Bash, Fish, Zsh};
+use serde_json::json;
 
 use crate::cli::args::{Cli, Shell};
+use crate::output;
 
 pub fn emit(shell: Shell) -> Result<(), String> {
     let mut cmd = Cli::command();
-    match shell {
+    let mut script = Vec::new();
+    let name = match shell {
         Shell::Bash => {
-            clap_complete::generate(Bash, &mut cmd, "oxidizr-deb", &mut std::io::stdout())
+            clap_complete::generate(Bash, &mut cmd, "oxidizr-deb", &mut script);
+            "bash"
+        }
+        Shell::Zsh => {
+            clap_complete::generate(Zsh, &mut cmd, "oxidizr-deb", &mut script);
+            "zsh"
         }
-        Shell::Zsh => clap_complete::generate(Zsh, &mut cmd, "oxidizr-deb", &mut std::io::stdout()),
         Shell::Fish => {
-            clap_complete::generate(Fish, &mut cmd, "oxidizr-deb", &mut std::io::stdout())
+            clap_complete::generate(Fish, &mut cmd, "oxidizr-deb", &mut script);
+            "fish"
         }
+    };
+    if output::active() {
+        let script = String::from_utf8_lossy(&script);
+        return output::print_json(&json!({"shell": name, "script": script}), false);
     }
-    Ok(())
+    std::io::stdout()
+        .write_all(&script)
+        .map_err(|e| format!("failed to write completions: {e}"))
 }
@@ -1,16 +1,179 @@
-use oxidizr_cli_core::api::build_api;
+use oxidizr_cli_core::api::build_api_with_sinks;
+use oxidizr_cli_core::audit::AuditLog;
 use oxidizr_cli_core::prompts::should_proceed;
-use switchyard::logging::JsonlSink;
 use switchyard::policy::Policy;
 use switchyard::types::ApplyMode;
 use switchyard::Switchyard;
 
-use crate::cli::args::{Cli, Commands};
+use std::path::Path;
+
+use crate::adapters::image;
+use crate::cli::args::{AuditOp, Cli, Commands, HooksOp, Package, PlanOp};
+use crate::commands::audit;
 use crate::commands::doctor;
+use crate::commands::events;
+use crate::commands::history;
+use crate::commands::hooks;
+use crate::commands::import_bundle;
+use crate::commands::mmdebstrap_hook;
+use crate::commands::repair;
 use crate::commands::replace;
+use crate::commands::undo;
+use crate::commands::{apply, plan};
 use crate::commands::{r#use, restore, status};
+use crate::errors::{CliError, CliResult};
+use crate::fetch::sources::Source;
+use crate::output;
+use crate::plan::PlanFile;
+use crate::state::audit as audit_log;
+use crate::state::history as history_log;
+
+fn selected(package: Option<Package>) -> Vec<Package> {
+    match package {
+        Some(p) => vec![p],
+        None => vec![Package::Coreutils, Package::Findutils, Package::Sudo],
+    }
+}
+
+/// Start a history record for a mutating command: its name and families.
+/// Read-only commands and the automatic `hooks run` are not recorded.
+fn history_run(root: &Path, command: &Commands) -> Option<history_log::Run> {
+    let (operation, packages, undoes) = match command {
+        Commands::Use { package, .. } => ("use", vec![*package], None),
+        Commands::Replace { package, all, .. } => {
+            ("replace", replace::targets_for(*package, *all).ok()?, None)
+        }
+        Commands::Restore { package, .. } => ("restore", selected(*package), None),
+        Commands::Apply { plan, .. } => {
+            let file = PlanFile::read(plan).ok()?;
+            let packages = file.plan.packages.iter().map(|pp| pp.package).collect();
+            ("apply", packages, None)
+        }
+        Commands::Repair { package, .. } => ("repair", selected(*package), None),
+        Commands::Undo { id } => {
+            let packages = history_log::load(root)
+                .ok()
+                .and_then(|es| es.into_iter().find(|e| e.id == *id))
+                .map(|e| e.packages)
+                .unwrap_or_default();
+            ("undo", packages, Some(*id))
+        }
+        Commands::ImportBundle { .. } => ("import-bundle", vec![], None),
+        Commands::Hooks {
+            op: HooksOp::Install { .. },
+        } => ("hooks install", vec![], None),
+        Commands::Hooks {
+            op: HooksOp::Remove,
+        } => ("hooks remove", vec![], None),
+        _ => return None,
+    };
+    Some(history_log::begin(root, operation, packages, undoes))
+}
+
+/// Hash of the plan a recorded command carries out: the hash `plan` gives for the same arguments,
+/// or the approved hash for `apply`. Building it verifies artifacts, so this runs once the audit
+/// log is open.
+fn plan_hash(root: &Path, command: &Commands) -> Option<String> {
+    let op = match command {
+        // Offline plans verify the artifact; `use` itself reports that, so skip the hash
+        Commands::Use {
+            package,
+            offline: false,
+            use_local,
+            ..
+        } => PlanOp::Use {
+            package: *package,
+            offline: false,
+            use_local: use_local.clone(),
+        },
+        Commands::Replace {
+            package,
+            all,
+            confirm_essential_removal,
+        } => PlanOp::Replace {
+            package: *package,
+            all: *all,
+            confirm_essential_removal: *confirm_essential_removal,
+        },
+        Commands::Restore {
+            package,
+            all,
+            keep_replacements,
+        } => PlanOp::Restore {
+            package: *package,
+            all: *all,
+            keep_replacements: *keep_replacements,
+        },
+        Commands::Apply { plan, .. } => return PlanFile::read(plan).ok().map(|f| f.hash),
+        _ => return None,
+    };
+    plan::build(root, op).ok().map(|p| p.hash())
+}
+
+/// Commands that ask before committing (`replace` runs its own Essential confirmation).
+fn confirms(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Use { .. }
+            | Commands::Restore { .. }
+            | Commands::Repair { .. }
+            | Commands::ImportBundle { .. }
+            | Commands::Hooks { .. }
+            | Commands::Apply { .. }
+            | Commands::Undo { .. }
+    )
+}
+
+/// Close out a run, whatever its result: the audited finish record, the audit log and the
+/// history entry.
+fn finish(
+    root: &Path,
+    audited: Option<&str>,
+    run: Option<history_log::Run>,
+    result: CliResult,
+) -> CliResult {
+    let outcome = result.clone().map_err(|e| e.to_string());
+    if let Some(name) = audited {
+        audit_log::record_run(name, root, Some(&outcome));
+    }
+    audit_log::close();
+    if let Some(run) = run {
+        match run.finish(root, &outcome) {
+            Ok(id) => eprintln!("[history] recorded operation #{id}"),
+            Err(e) if result.is_ok() => return Err(e.into()),
+            Err(e) => output::warning(&e),
+        }
+    }
+    result
+}
+
+/// Name of a command that changes the root when committed; `None` for read-only commands.
+fn mutating_command(command: &Commands) -> Option<&'static str> {
+    match command {
+        Commands::Use { .. }
+        | Commands::Restore { .. }
+        | Commands::Replace { .. }
+        | Commands::Repair { .. }
+        | Commands::ImportBundle { .. }
+        | Commands::Hooks { .. }
+        | Commands::Apply { .. }
+        | Commands::Undo { .. } => Some(command.name()),
+        _ => None,
+    }
+}
+
+pub fn dispatch(cli: Cli) -> CliResult {
+    // Re-enters dispatch once per step, against the rootfs
+    if let Commands::MmdebstrapHook { op } = cli.command {
+        return mmdebstrap_hook::exec(
+            op,
+            cli.image_mode,
+            cli.apt_version,
+            cli.log_sink,
+            cli.output,
+        );
+    }
 
-pub fn dispatch(cli: Cli) -> Result<(), String> {
     // Default policy: conservative, disallow degraded EXDEV for built-ins
     let mut policy = Policy::coreutils_switch_preset();
 
@@ -23,52 +186,159 @@ pub fn dispatch(cli: Cli) -> Result<(), String> {
     if let Some(v) = &cli.apt_version {
         std::env::set_var("OXIDIZR_DEB_APT_VERSION", v);
     }
-    let api: Switchyard<JsonlSink, JsonlSink> = build_api(policy, lock_path);
-
+    if let Some(m) = cli.image_mode {
+        if cli.root == Path::new("/") {
+            return Err(CliError::InvalidRoot(
+                "--image-mode applies to a non-live --root (a rootfs being built)".to_string(),
+            ));
+        }
+        std::env::set_var(image::IMAGE_MODE_ENV, m.as_str());
+    }
+    if let Some(s) = cli.log_sink {
+        std::env::set_var(audit_log::LOG_SINK_ENV, s.as_str());
+    }
+    output::watch(&cli.root);
+    // apt/dpkg children inherit this; the Post-Invoke hook skips runs driven by oxidizr-deb itself
+    if !matches!(cli.command, Commands::Hooks { .. }) {
+        std::env::set_var(hooks::ACTIVE_ENV, "1");
+    }
     let apply_mode = if cli.commit {
         ApplyMode::Commit
     } else {
         ApplyMode::DryRun
     };
 
+    let mut run = if matches!(apply_mode, ApplyMode::Commit) {
+        history_run(&cli.root, &cli.command)
+    } else {
+        None
+    };
+
+    // Committed changes are audited; a root whose audit log cannot be written is not touched.
+    // A hook run triggered by oxidizr-deb's own apt run does nothing, and writes nothing either.
+    let deferred_hook =
+        matches!(cli.command, Commands::Hooks { op: HooksOp::Run }) && hooks::deferred(&cli.root);
+    let audited = match (apply_mode, mutating_command(&cli.command)) {
+        (ApplyMode::Commit, Some(name)) if !deferred_hook => Some(name),
+        _ => None,
+    };
+    let audit = audit_log::open(&cli.root, audited.is_some(), run.as_ref().map(|r| r.id()))?;
+    if let Some(name) = audited {
+        audit_log::record_run(name, &cli.root, None);
+    }
+    if let Some(run) = run.as_mut() {
+        run.set_plan_hash(plan_hash(&cli.root, &cli.command));
+    }
+    let api: Switchyard<AuditLog, AuditLog> =
+        build_api_with_sinks(policy, lock_path, audit.clone(), audit);
+
+    let root = cli.root.clone();
+    let result = execute(cli, &api, apply_mode);
+    finish(&root, audited, run, result)
+}
+
+/// Run the command itself; `dispatch` records its result whichever way it ends.
+fn execute(cli: Cli, api: &Switchyard<AuditLog, AuditLog>, apply_mode: ApplyMode) -> CliResult {
+    // Commands with a JSON payload put it in the result document
+    let json = |flag: bool| flag || output::active();
+    // chroot mode: /proc, /dev and /sys stay bind-mounted until the command returns
+    let _mounts = match (&cli.command, apply_mode) {
+        (
+            Commands::Use { .. }
+            | Commands::Replace { .. }
+            | Commands::Restore { .. }
+            | Commands::Apply { .. }
+            | Commands::Undo { .. },
+            ApplyMode::Commit,
+        ) => image::enter(&cli.root)?,
+        _ => None,
+    };
+
+    if matches!(apply_mode, ApplyMode::Commit)
+        && confirms(&cli.command)
+        && !should_proceed(cli.assume_yes, &cli.root)
+    {
+        return Err(CliError::UserAborted("aborted by user".to_string()));
+    }
+
     match cli.command {
         Commands::Use {
             package,
             offline,
             use_local,
+            deb,
+            apt_repo,
+            divert,
+            alternatives,
         } => {
-            if matches!(apply_mode, ApplyMode::Commit) {
-                if !should_proceed(cli.assume_yes, &cli.root) {
-                    return Err("aborted by user".to_string());
-                }
-            }
-            r#use::exec(&api, &cli.root, package, offline, use_local, apply_mode)
+            let opts = r#use::UseOptions {
+                offline,
+                use_local,
+                source: Source::from_args(deb, apt_repo),
+                divert,
+                alternatives,
+            };
+            r#use::exec(api, &cli.root, package, opts, apply_mode)
         }
         Commands::Restore {
             package,
             all,
             keep_replacements,
         } => {
-            if matches!(apply_mode, ApplyMode::Commit) {
-                if !should_proceed(cli.assume_yes, &cli.root) {
-                    return Err("aborted by user".to_string());
-                }
-            }
-            restore::exec(
-                &api,
-                &cli.root,
-                package,
-                all,
+            let opts = restore::RestoreOptions {
                 keep_replacements,
-                apply_mode,
-                cli.assume_yes,
-            )
+                ..Default::default()
+            };
+            restore::exec(api, &cli.root, package, all, opts, apply_mode)
+        }
+        Commands::Repair {
+            package,
+            json: flag,
+        } => repair::exec(api, &cli.root, package, apply_mode, json(flag)),
+        Commands::ImportBundle { file } => {
+            import_bundle::exec(&cli.root, &file, apply_mode).map_err(CliError::from)
+        }
+        Commands::Hooks { op } => match op {
+            HooksOp::Install { bin } => {
+                hooks::install(&cli.root, &bin, apply_mode).map_err(CliError::from)
+            }
+            HooksOp::Remove => hooks::remove(&cli.root, apply_mode).map_err(CliError::from),
+            HooksOp::Run => hooks::run(api, &cli.root, apply_mode),
+        },
+        Commands::Plan { out, op } => plan::exec(&cli.root, op, &out).map_err(CliError::from),
+        Commands::Apply { plan, expect_hash } => {
+            apply::exec(api, &cli.root, &plan, &expect_hash, apply_mode)
+        }
+        Commands::History { json: flag } => {
+            history::exec(&cli.root, json(flag)).map_err(CliError::from)
+        }
+        Commands::Undo { id } => undo::exec(api, &cli.root, id, apply_mode),
+        Commands::Status { json: flag } => {
+            status::exec(&cli.root, json(flag)).map_err(CliError::from)
+        }
+        Commands::Audit {
+            op: AuditOp::Verify { json: flag },
+        } => audit::exec(&cli.root, AuditOp::Verify { json: json(flag) }).map_err(CliError::from),
+        Commands::Events { op } => events::exec(op).map_err(CliError::from),
+        Commands::Doctor { json: flag } => {
+            doctor::exec(&cli.root, json(flag)).map_err(CliError::from)
         }
-        Commands::Status { json } => status::exec(&cli.root, json),
-        Commands::Doctor { json } => doctor::exec(&cli.root, json),
-        Commands::Replace { package, all } => {
-            replace::exec(&api, &cli.root, package, all, apply_mode, cli.assume_yes)
+        Commands::Replace {
+            package,
+            all,
+            confirm_essential_removal,
+        } => replace::exec(
+            api,
+            &cli.root,
+            package,
+            all,
+            apply_mode,
+            cli.assume_yes,
+            confirm_essential_removal,
+        ),
+        Commands::Completions { shell } => {
+            crate::cli::completions::emit(shell).map_err(CliError::from)
         }
-        Commands::Completions { shell } => crate::cli::completions::emit(shell),
+        Commands::MmdebstrapHook { .. } => unreachable!("handled before dispatching"),
     }
 }
@@ -0,0 +1,267 @@
+use std::path::Path;
+
+use oxidizr_cli_core::audit::AuditLog;
+use oxidizr_cli_core::events::Event;
+use switchyard::types::safepath::SafePath;
+use switchyard::types::{ApplyMode, LinkRequest, PlanInput, RestoreRequest};
+use switchyard::Switchyard;
+
+use crate::adapters::debian::{apt_get, pm_lock_message};
+use crate::adapters::dpkg;
+use crate::adapters::image;
+use crate::adapters::preflight::sudo_guard;
+use crate::cli::args::Package;
+use crate::commands::replace::{self, is_active};
+use crate::commands::{r#use, restore};
+use crate::errors::{CliError, CliResult};
+use crate::fetch::fallback::{apt_pkg_name, ensure_artifact_available};
+use crate::fetch::sources::Source;
+use crate::output;
+use crate::plan::drift;
+use crate::plan::{AptAction, AptStep, Operation, PackagePlan, PlanFile};
+use crate::state;
+
+pub fn exec(
+    api: &Switchyard<AuditLog, AuditLog>,
+    root: &Path,
+    plan_path: &Path,
+    expect_hash: &str,
+    mode: ApplyMode,
+) -> CliResult {
+    let file = PlanFile::read(plan_path)?;
+    file.verify(expect_hash)?;
+    let plan = &file.plan;
+    if plan.root != root {
+        return Err(CliError::InvalidRoot(format!(
+            "plan was made for --root {} but this run targets {}",
+            plan.root.display(),
+            root.display()
+        )));
+    }
+
+    let apt_root = image::apt_reachable(root);
+    if matches!(mode, ApplyMode::Commit) {
+        if let Some(msg) = pm_lock_message(root) {
+            return Err(CliError::LockBusy(msg));
+        }
+        if plan.operation == Operation::Replace && !apt_root {
+            return Err(CliError::InvalidRoot(
+                "replace operations require --root=/ (live system) for apt/dpkg changes; use --image-mode dir|chroot for a rootfs"
+                    .to_string(),
+            ));
+        }
+    }
+
+    let drifted = drift::detect(plan);
+    if !drifted.is_empty() {
+        let mut lines: Vec<String> = drifted.iter().take(20).cloned().collect();
+        if drifted.len() > lines.len() {
+            lines.push(format!("... and {} more", drifted.len() - lines.len()));
+        }
+        return Err(CliError::InvariantViolation(format!(
+            "system drifted since the plan was made; re-run `oxidizr-deb plan`:\n  {}",
+            lines.join("\n  ")
+        )));
+    }
+
+    let commit = matches!(mode, ApplyMode::Commit);
+    let targets: Vec<Package> = plan.packages.iter().map(|pp| pp.package).collect();
+    let steps = |action: AptAction| -> Vec<String> {
+        plan.packages
+            .iter()
+            .flat_map(|pp| pp.apt.iter())
+            .filter(|s| s.action == action)
+            .map(AptStep::target)
+            .collect()
+    };
+    // The apt side goes through the same transactions (and guardrails) as `replace`/`restore`
+    match plan.operation {
+        Operation::Use => {
+            for pp in &plan.packages {
+                install_replacement(root, pp, mode)?;
+                switch(api, root, pp, mode)?;
+            }
+        }
+        Operation::Replace => {
+            let mut tx = replace::Transaction::new(
+                root,
+                &targets,
+                steps(AptAction::Install),
+                plan.confirm_essential_removal,
+                mode,
+            )?;
+            tx.install(root, mode)?;
+            for pp in &plan.packages {
+                if commit {
+                    check_installed_artifact(root, pp)?;
+                }
+                switch(api, root, pp, mode)?;
+                if commit && !is_active(root, pp.package) {
+                    return Err(CliError::InvariantViolation(format!(
+                        "replacement for {:?} is not active after use; aborting replace",
+                        pp.package
+                    )));
+                }
+                if let Some(a) = &pp.artifact {
+                    replace::coverage_gate(root, pp.package, &a.path)?;
+                }
+            }
+            tx.purge(root, mode)?;
+        }
+        Operation::Restore => {
+            let purge = steps(AptAction::Purge)
+                .into_iter()
+                .filter(|n| !(commit && apt_root) || dpkg::is_installed(root, n))
+                .collect();
+            let tx =
+                restore::Transaction::new(root, &targets, steps(AptAction::Install), purge, false);
+            tx.install(root, mode)?;
+            for pp in &plan.packages {
+                switch(api, root, pp, mode)?;
+            }
+            tx.purge(root, mode)?;
+            if commit {
+                for p in &targets {
+                    state::record_restore(root, *p)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The replacement a `replace` transaction installed must be the artifact the plan links.
+fn check_installed_artifact(root: &Path, pp: &PackagePlan) -> CliResult {
+    if !image::apt_reachable(root) {
+        return Ok(());
+    }
+    let (located, _) = ensure_artifact_available(root, pp.package, true, &Source::Installed)?;
+    match &pp.artifact {
+        Some(a) if located != a.path => Err(CliError::InvariantViolation(format!(
+            "replacement installed at {} but the plan links {}; re-run `oxidizr-deb plan`",
+            located.display(),
+            a.path.display()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// apt must have installed the version the plan pins, not whatever is the candidate now.
+fn check_installed_version(root: &Path, step: &AptStep) -> CliResult {
+    let Some(want) = &step.version else {
+        return Ok(());
+    };
+    match dpkg::installed_version(root, &step.package) {
+        Some(got) if &got == want => Ok(()),
+        got => Err(CliError::InvariantViolation(format!(
+            "apt installed {} {} but the plan pins {}; re-run `oxidizr-deb plan`",
+            step.package,
+            got.as_deref().unwrap_or("(nothing)"),
+            want
+        ))),
+    }
+}
+
+/// APT installs of a `use` plan, run before its Switchyard actions.
+fn install_replacement(root: &Path, pp: &PackagePlan, mode: ApplyMode) -> CliResult {
+    let apt_root = image::apt_reachable(root);
+    for step in pp.apt.iter().filter(|s| s.action == AptAction::Install) {
+        let args = step.args();
+        if matches!(mode, ApplyMode::DryRun) {
+            eprintln!("[dry-run] would run: apt-get {}", args.join(" "));
+            output::planned_apt(root, "apt-get", std::slice::from_ref(&step.package), &[]);
+            continue;
+        }
+        if !apt_root {
+            eprintln!(
+                "[info] skipping apt-get {} under non-live root: {}",
+                args.join(" "),
+                root.display()
+            );
+            continue;
+        }
+        if step.package == apt_pkg_name(pp.package) {
+            apt_get(root, &["update".to_string()], Event::PmUpdate, &step.package)?;
+        }
+        apt_get(root, &args, Event::PmInstall, &step.package)?;
+        check_installed_version(root, step)?;
+        if step.package == apt_pkg_name(pp.package) {
+            check_installed_artifact(root, pp)?;
+        }
+    }
+    Ok(())
+}
+
+/// The plan's Switchyard actions for one package, exactly as planned.
+fn switch(
+    api: &Switchyard<AuditLog, AuditLog>,
+    root: &Path,
+    pp: &PackagePlan,
+    mode: ApplyMode,
+) -> CliResult {
+    let apt_root = image::apt_reachable(root);
+    let commit = matches!(mode, ApplyMode::Commit);
+    if commit && !pp.links.is_empty() {
+        if let Some(a) = &pp.artifact {
+            if !a.path.exists() {
+                return Err(CliError::InvalidRoot(format!(
+                    "replacement artifact missing at {}; installing requires --root=/ (live system)",
+                    a.path.display()
+                )));
+            }
+            if matches!(pp.package, Package::Sudo) {
+                sudo_guard(root, &a.path)?;
+            }
+        }
+    }
+    let mut link = Vec::new();
+    for l in &pp.links {
+        link.push(LinkRequest {
+            source: SafePath::from_rooted(root, &l.source)
+                .map_err(|e| format!("invalid source_bin: {e:?}"))?,
+            target: SafePath::from_rooted(root, &l.target)
+                .map_err(|e| format!("invalid dest: {e:?}"))?,
+        });
+    }
+    let mut restore_reqs = Vec::new();
+    for r in &pp.restores {
+        restore_reqs.push(RestoreRequest {
+            target: SafePath::from_rooted(root, &r.target)
+                .map_err(|e| format!("invalid target: {e:?}"))?,
+        });
+    }
+    let plan = api.plan(PlanInput {
+        link,
+        restore: restore_reqs,
+    });
+    let _pre = api
+        .preflight(&plan)
+        .map_err(|e| CliError::switchyard("preflight", e))?;
+    output::planned(&plan.actions);
+    let rep = api
+        .apply(&plan, mode)
+        .map_err(|e| CliError::switchyard("apply", e))?;
+    output::executed(mode, &rep.executed);
+    if !commit {
+        eprintln!("dry-run: planned {} actions", rep.executed.len());
+    } else {
+        if let Some(a) = pp.artifact.as_ref().filter(|_| !pp.links.is_empty()) {
+            r#use::ensure_links(root, &a.path, &pp.applets)?;
+            r#use::smoke_check(root, pp.package, &a.path, &pp.applets)?;
+            let step = pp
+                .apt
+                .iter()
+                .find(|s| s.action == AptAction::Install && apt_root);
+            state::record_use(
+                root,
+                pp.package,
+                &pp.applets,
+                &a.path,
+                step.map(|s| s.package.as_str()),
+                step.and_then(|s| s.version.as_deref()),
+            )?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use std::path::Path;
+
+use oxidizr_cli_core::audit;
+use serde_json::json;
+
+use crate::cli::args::AuditOp;
+use crate::output;
+use crate::state::audit as audit_log;
+use crate::state::rooted;
+
+pub fn exec(root: &Path, op: AuditOp) -> Result<(), String> {
+    match op {
+        AuditOp::Verify { json } => verify(root, json),
+    }
+}
+
+fn verify(root: &Path, json: bool) -> Result<(), String> {
+    let path = audit_log::path(root);
+    let result = audit::verify(&path);
+    // A failure is reported as the `--json` error object
+    let report = result.map_err(|e| format!("audit log verification failed: {e}"))?;
+    if json {
+        let doc = json!({
+            "ok": true,
+            "path": rooted(root, &path).display().to_string(),
+            "records": report.records,
+            "first_seq": report.first_seq,
+            "last_seq": report.last_seq,
+            "files": report.files.iter().map(|f| rooted(root, f).display().to_string()).collect::<Vec<_>>(),
+        });
+        output::print_json(&doc, true)?;
+    } else {
+        match (report.first_seq, report.last_seq) {
+            (Some(first), Some(last)) => println!(
+                "audit log intact: {} record(s), #{first}..#{last}, in {} file(s)",
+                report.records,
+                report.files.len()
+            ),
+            _ => println!("No audit records under {}", root.display()),
+        }
+    }
+    Ok(())
+}
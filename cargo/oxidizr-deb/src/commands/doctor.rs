@@ -4,6 +4,19 @@ use std::path::Path;
 
 use serde::Serialize;
 
+use crate::adapters::dpkg::StatusDb;
+use crate::cli::args::Package;
+use crate::fetch::fallback::apt_pkg_name;
+use crate::output;
+use crate::packages::distro_pkg_name;
+
+#[derive(Serialize)]
+pub struct PackageInfo {
+    installed: bool,
+    version: Option<String>,
+    essential: bool,
+}
+
 #[derive(Serialize)]
 pub struct DoctorReport {
     distro_id: String,
@@ -11,6 +24,8 @@ pub struct DoctorReport {
     locks_present: bool,
     locks: Vec<String>,
     paths_ok: bool,
+    /// Distro and replacement packages as recorded in the root's dpkg status database.
+    packages: BTreeMap<String, PackageInfo>,
     tips: Vec<String>,
 }
 
@@ -59,6 +74,27 @@ fn check_locks(root: &Path) -> (bool, Vec<String>) {
     (!held.is_empty(), held)
 }
 
+fn check_packages(root: &Path) -> Result<BTreeMap<String, PackageInfo>, String> {
+    let db = StatusDb::load(root)?;
+    let mut out = BTreeMap::new();
+    for p in [Package::Coreutils, Package::Findutils, Package::Sudo] {
+        for name in [distro_pkg_name(p), apt_pkg_name(p)] {
+            let entry = db.get(name);
+            out.insert(
+                name.to_string(),
+                PackageInfo {
+                    installed: entry.is_some_and(|e| e.is_installed()),
+                    version: entry
+                        .filter(|e| e.is_installed())
+                        .and_then(|e| e.version.clone()),
+                    essential: entry.is_some_and(|e| e.essential),
+                },
+            );
+        }
+    }
+    Ok(out)
+}
+
 fn check_paths(root: &Path) -> bool {
     root.join("usr").is_dir() && root.join("usr/bin").is_dir()
 }
@@ -67,6 +103,7 @@ pub fn exec(root: &Path, json: bool) -> Result<(), String> {
     let (distro_id, distro_version) = detect_distro(root);
     let (locks_present, locks) = check_locks(root);
     let paths_ok = check_paths(root);
+    let packages = check_packages(root)?;
     let mut tips = vec![];
     if locks_present {
         tips.push("Package manager busy (dpkg/apt lock detected); retry after current operation finishes.".to_string());
@@ -85,12 +122,10 @@ pub fn exec(root: &Path, json: bool) -> Result<(), String> {
             locks_present,
             locks,
             paths_ok,
+            packages,
             tips,
         };
-        println!(
-            "{}",
-            serde_json::to_string(&rep).map_err(|e| e.to_string())?
-        );
+        output::print_json(&rep, false)?;
     } else {
         println!(
             "Detected distro: {} {}",
@@ -109,6 +144,16 @@ pub fn exec(root: &Path, json: bool) -> Result<(), String> {
             "Paths ok (usr/bin): {}",
             if paths_ok { "yes" } else { "no" }
         );
+        println!("Packages (dpkg status):");
+        for (name, p) in &packages {
+            let state = match (&p.version, p.installed) {
+                (Some(v), true) => format!("installed {}", v),
+                (None, true) => "installed".to_string(),
+                _ => "not installed".to_string(),
+            };
+            let essential = if p.essential { " (Essential)" } else { "" };
+            println!("  - {}: {}{}", name, state, essential);
+        }
         if !tips.is_empty() {
             println!("Tips:");
             for t in &tips {
@@ -0,0 +1,10 @@
+use oxidizr_cli_core::events;
+
+use crate::cli::args::EventsOp;
+use crate::output;
+
+pub fn exec(op: EventsOp) -> Result<(), String> {
+    match op {
+        EventsOp::Schema => output::print_json(&events::schema(), true),
+    }
+}
@@ -0,0 +1,49 @@
+use std::path::Path;
+
+use crate::output;
+use crate::state::history;
+
+fn versions(v: &std::collections::BTreeMap<String, String>) -> String {
+    if v.is_empty() {
+        return "-".to_string();
+    }
+    v.iter()
+        .map(|(n, ver)| format!("{n} {ver}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn exec(root: &Path, json: bool) -> Result<(), String> {
+    let entries = history::load(root)?;
+    if json {
+        return output::print_json(&entries, true);
+    }
+    if entries.is_empty() {
+        println!("No recorded operations under {}", root.display());
+        return Ok(());
+    }
+    for e in &entries {
+        let packages: Vec<String> = e.packages.iter().map(|p| format!("{:?}", p)).collect();
+        let outcome = match (&e.error, e.undoes) {
+            (Some(err), _) => format!("failed: {err}"),
+            (None, Some(id)) => format!("ok (undid #{id})"),
+            (None, None) => "ok".to_string(),
+        };
+        println!(
+            "#{} {} {} [{}]",
+            e.id,
+            e.operation,
+            outcome,
+            packages.join(", ")
+        );
+        println!("    before: {}", versions(&e.versions_before));
+        println!("    after:  {}", versions(&e.versions_after));
+        if let Some(h) = &e.plan_hash {
+            println!("    plan:   {h}");
+        }
+        for a in &e.archived {
+            println!("    archived: {}", a.display());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use oxidizr_cli_core::audit::AuditLog;
+use oxidizr_cli_core::events::{Event, HookDecision, HookInstall, HookRemove, HookRun};
+use oxidizr_cli_core::report::Action;
+use switchyard::types::ApplyMode;
+use switchyard::Switchyard;
+
+use crate::commands::repair;
+use crate::errors::CliResult;
+use crate::output;
+use crate::state::audit;
+use crate::state::State;
+
+pub const HOOK_REL: &str = "etc/apt/apt.conf.d/80oxidizr-deb";
+const LOCK_REL: &str = "var/lock/oxidizr-deb.lock";
+const MARKER: &str = "// Managed by oxidizr-deb";
+/// Set for the lifetime of every non-hook oxidizr-deb invocation (inherited by apt-get/dpkg).
+pub const ACTIVE_ENV: &str = "OXIDIZR_DEB_ACTIVE";
+
+fn hook_path(root: &Path) -> PathBuf {
+    root.join(HOOK_REL)
+}
+
+/// The apt.conf snippet. Paths are as seen from inside the root, since apt/dpkg run there.
+pub fn snippet(bin: &Path) -> String {
+    let bin = bin.display();
+    format!(
+        "{MARKER}; remove with `oxidizr-deb --commit hooks remove`.\n\
+         // Re-asserts recorded replacements after every dpkg run (no-op when nothing is switched).\n\
+         DPkg::Post-Invoke {{ \"if [ -x {bin} ] && [ -f /{state} ]; then {bin} --commit --assume-yes hooks run || true; fi\"; }};\n",
+        state = crate::state::STATE_REL,
+    )
+}
+
+pub fn install(root: &Path, bin: &Path, mode: ApplyMode) -> Result<(), String> {
+    let p = hook_path(root);
+    let body = snippet(bin);
+    output::action(
+        mode,
+        Action::Write {
+            path: p.display().to_string(),
+        },
+    );
+    if matches!(mode, ApplyMode::DryRun) {
+        eprintln!("[dry-run] would write {}:", p.display());
+        eprint!("{}", body);
+        return Ok(());
+    }
+    if let Some(parent) = p.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    fs::write(&p, body.as_bytes()).map_err(|e| format!("failed to write {}: {e}", p.display()))?;
+    audit::emit(Event::HookInstall(HookInstall {
+        path: p.display().to_string(),
+        bin: bin.display().to_string(),
+    }));
+    Ok(())
+}
+
+pub fn remove(root: &Path, mode: ApplyMode) -> Result<(), String> {
+    let p = hook_path(root);
+    let current = match fs::read_to_string(&p) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            eprintln!("[info] no hook installed at {}", p.display());
+            return Ok(());
+        }
+        Err(e) => return Err(format!("failed to read {}: {e}", p.display())),
+    };
+    if !current.starts_with(MARKER) {
+        return Err(format!(
+            "{} was not written by oxidizr-deb; refusing to remove it",
+            p.display()
+        ));
+    }
+    output::action(
+        mode,
+        Action::Remove {
+            path: p.display().to_string(),
+        },
+    );
+    if matches!(mode, ApplyMode::DryRun) {
+        eprintln!("[dry-run] would remove {}", p.display());
+        return Ok(());
+    }
+    fs::remove_file(&p).map_err(|e| format!("failed to remove {}: {e}", p.display()))?;
+    audit::emit(Event::HookRemove(HookRemove {
+        path: p.display().to_string(),
+    }));
+    Ok(())
+}
+
+/// `true` when another oxidizr-deb holds its lock, i.e. dpkg was started by oxidizr-deb itself.
+fn oxidizr_lock_held(root: &Path) -> bool {
+    use fs2::FileExt;
+    let p = root.join(LOCK_REL);
+    match fs::OpenOptions::new().read(true).write(true).open(&p) {
+        Ok(f) => match f.try_lock_exclusive() {
+            Ok(_) => {
+                let _ = f.unlock();
+                false
+            }
+            Err(_) => true,
+        },
+        Err(_) => false,
+    }
+}
+
+/// `true` when the hook was triggered by an oxidizr-deb operation (it set `ACTIVE_ENV` or holds its
+/// lock). That operation reconciles on its own, so the hook does nothing and is not audited.
+pub fn deferred(root: &Path) -> bool {
+    std::env::var_os(ACTIVE_ENV).is_some() || oxidizr_lock_held(root)
+}
+
+/// Entry point for the DPkg::Post-Invoke hook.
+pub fn run(api: &Switchyard<AuditLog, AuditLog>, root: &Path, mode: ApplyMode) -> CliResult {
+    let st = match State::load(root)? {
+        Some(s) if !s.families.is_empty() => s,
+        _ => {
+            audit::emit(Event::HookRun(HookRun {
+                decision: HookDecision::Noop,
+                reason: Some("nothing switched".to_string()),
+                dry_run: None,
+                families: None,
+            }));
+            return Ok(());
+        }
+    };
+    if deferred(root) {
+        audit::emit(Event::HookRun(HookRun {
+            decision: HookDecision::Skipped,
+            reason: Some("oxidizr-deb operation in progress".to_string()),
+            dry_run: None,
+            families: None,
+        }));
+        return Ok(());
+    }
+    let results = repair::run(api, root, &st, None, mode)?;
+    audit::emit(Event::HookRun(HookRun {
+        decision: HookDecision::Repaired,
+        reason: None,
+        dry_run: Some(matches!(mode, ApplyMode::DryRun)),
+        families: Some(results.iter().map(repair::FamilyRepair::to_event).collect()),
+    }));
+    Ok(())
+}
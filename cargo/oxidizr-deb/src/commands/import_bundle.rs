@@ -0,0 +1,239 @@
+//! `import-bundle`: unpack a signed bundle of replacement binaries into the staged layout
+//! `opt/oxidizr/replacements/<family>/<version>/bin/<bin>` that `use --offline` defaults to.
+//!
+//! A bundle is a tar archive (optionally zstd-compressed) holding `manifest.json`,
+//! `manifest.json.sig` and the binaries listed in the manifest, all at the top level. The manifest
+//! and signature use the same format as single offline artifacts (see `fetch::verifier`).
+//! Versions are immutable once imported; `<family>/bin` is a symlink to the current version's
+//! `bin` and is swapped with a rename, so readers never observe a half-imported family.
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path};
+
+use oxidizr_cli_core::events::{BundleFamily, BundleImport, Event};
+use oxidizr_cli_core::report::Action;
+use switchyard::types::ApplyMode;
+
+use crate::cli::args::Package;
+use crate::fetch::resolver::{staged_default_path, staged_family_dir, staged_layout};
+use crate::fetch::verifier::{verify_manifest, MANIFEST_SUFFIX, SIG_SUFFIX};
+use crate::output;
+use crate::packages::distro_pkg_name;
+use crate::state::audit;
+use crate::util::hash::{sha256_bytes, sha256_file};
+
+pub const BUNDLE_MANIFEST: &str = "manifest.json";
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Read every regular file of the bundle into memory, keyed by its top-level name.
+fn read_entries(file: &Path) -> Result<BTreeMap<String, Vec<u8>>, String> {
+    let mut f =
+        fs::File::open(file).map_err(|e| format!("failed to open {}: {e}", file.display()))?;
+    let mut magic = [0u8; 4];
+    let n = f.read(&mut magic).unwrap_or(0);
+    f.seek(SeekFrom::Start(0))
+        .map_err(|e| format!("failed to read {}: {e}", file.display()))?;
+    let reader: Box<dyn Read> = if n == 4 && magic == ZSTD_MAGIC {
+        Box::new(
+            ruzstd::decoding::StreamingDecoder::new(f)
+                .map_err(|e| format!("{}: invalid zstd stream: {e}", file.display()))?,
+        )
+    } else {
+        Box::new(f)
+    };
+
+    let mut out = BTreeMap::new();
+    let mut archive = tar::Archive::new(reader);
+    let entries = archive
+        .entries()
+        .map_err(|e| format!("{}: invalid tar archive: {e}", file.display()))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("{}: invalid tar entry: {e}", file.display()))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry
+            .path()
+            .map_err(|e| format!("{}: invalid tar path: {e}", file.display()))?
+            .into_owned();
+        let parts: Vec<Component> = path
+            .components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .collect();
+        let name = match parts.as_slice() {
+            [Component::Normal(n)] => n.to_string_lossy().to_string(),
+            _ => {
+                return Err(format!(
+                    "{}: unexpected entry {} (bundles are flat)",
+                    file.display(),
+                    path.display()
+                ))
+            }
+        };
+        let mut data = Vec::new();
+        entry
+            .read_to_end(&mut data)
+            .map_err(|e| format!("{}: failed to read {name}: {e}", file.display()))?;
+        if out.insert(name.clone(), data).is_some() {
+            return Err(format!("{}: duplicate entry {name}", file.display()));
+        }
+    }
+    Ok(out)
+}
+
+fn package_for_bin(name: &str) -> Option<Package> {
+    [Package::Coreutils, Package::Findutils, Package::Sudo]
+        .into_iter()
+        .find(|p| staged_layout(*p).1 == name)
+}
+
+fn write_file(path: &Path, data: &[u8], exec: bool) -> Result<(), String> {
+    fs::write(path, data).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+    if exec {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("failed to chmod {}: {e}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// A version becomes a directory next to the `bin` link and the `.<version>.tmp` /
+/// `.bin.tmp` staging entries, so it must be a plain name that collides with none of them.
+fn valid_version(version: &str) -> bool {
+    !version.is_empty() && version != "bin" && !version.starts_with('.') && !version.contains('/')
+}
+
+/// Stage one family's binary under `<family>/<version>/bin` and point `<family>/bin` at it.
+fn stage(
+    root: &Path,
+    pkg: Package,
+    version: &str,
+    data: &[u8],
+    manifest: &[u8],
+    sig: &[u8],
+) -> Result<(), String> {
+    let (_, bin) = staged_layout(pkg);
+    let fam_dir = staged_family_dir(root, pkg);
+    let final_dir = fam_dir.join(version);
+    let existing = final_dir.join("bin").join(bin);
+    if final_dir.exists() {
+        let same = sha256_file(&existing)
+            .map(|s| s == sha256_bytes(data))
+            .unwrap_or(false);
+        if !same {
+            return Err(format!(
+                "{} already exists with different contents; versions are immutable, remove it first",
+                final_dir.display()
+            ));
+        }
+    } else {
+        let tmp = fam_dir.join(format!(".{version}.tmp"));
+        let _ = fs::remove_dir_all(&tmp);
+        fs::create_dir_all(tmp.join("bin"))
+            .map_err(|e| format!("failed to create {}: {e}", tmp.display()))?;
+        write_file(&tmp.join("bin").join(bin), data, true)?;
+        let manifest_name = format!("{bin}{MANIFEST_SUFFIX}");
+        write_file(&tmp.join("bin").join(&manifest_name), manifest, false)?;
+        write_file(
+            &tmp.join("bin").join(format!("{manifest_name}{SIG_SUFFIX}")),
+            sig,
+            false,
+        )?;
+        fs::rename(&tmp, &final_dir).map_err(|e| {
+            format!(
+                "failed to rename {} -> {}: {e}",
+                tmp.display(),
+                final_dir.display()
+            )
+        })?;
+    }
+
+    // Relative target so the layout stays valid when the root is later booted or chrooted.
+    let link = fam_dir.join("bin");
+    let tmp_link = fam_dir.join(".bin.tmp");
+    let _ = fs::remove_file(&tmp_link);
+    std::os::unix::fs::symlink(Path::new(version).join("bin"), &tmp_link)
+        .map_err(|e| format!("failed to create {}: {e}", tmp_link.display()))?;
+    fs::rename(&tmp_link, &link).map_err(|e| {
+        let _ = fs::remove_file(&tmp_link);
+        format!("failed to switch {} to {version}: {e}", link.display())
+    })
+}
+
+pub fn exec(root: &Path, file: &Path, mode: ApplyMode) -> Result<(), String> {
+    let fail = |msg: String| format!("bundle verification failed for {}: {msg}", file.display());
+    let entries = read_entries(file)?;
+    let manifest_bytes = entries
+        .get(BUNDLE_MANIFEST)
+        .ok_or_else(|| fail(format!("missing {BUNDLE_MANIFEST}")))?;
+    let sig_name = format!("{BUNDLE_MANIFEST}{SIG_SUFFIX}");
+    let sig = entries
+        .get(&sig_name)
+        .ok_or_else(|| fail(format!("missing {sig_name}")))?;
+    let (manifest, key) =
+        verify_manifest(root, manifest_bytes, &String::from_utf8_lossy(sig)).map_err(fail)?;
+    let version = manifest.version.as_str();
+    if !valid_version(version) {
+        return Err(fail(format!("invalid version {:?}", version)));
+    }
+
+    let mut staged = Vec::new();
+    for (name, expected) in &manifest.files {
+        let data = entries
+            .get(name)
+            .ok_or_else(|| fail(format!("manifest lists {name} but the bundle lacks it")))?;
+        let actual = sha256_bytes(data);
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Err(fail(format!(
+                "sha256 mismatch for {name}: manifest {expected}, file {actual}"
+            )));
+        }
+        match package_for_bin(name) {
+            Some(pkg) => staged.push((pkg, data, actual)),
+            None => eprintln!("[info] {name}: not a known replacement binary; skipping"),
+        }
+    }
+    if staged.is_empty() {
+        return Err(format!(
+            "{} contains no known replacement binaries",
+            file.display()
+        ));
+    }
+
+    let mut families = Vec::new();
+    for (pkg, data, sha) in &staged {
+        let dest = staged_default_path(root, *pkg);
+        output::action(
+            mode,
+            Action::Write {
+                path: dest.display().to_string(),
+            },
+        );
+        if matches!(mode, ApplyMode::DryRun) {
+            eprintln!(
+                "[dry-run] would import {:?} {} -> {}",
+                pkg,
+                version,
+                dest.display()
+            );
+        } else {
+            stage(root, *pkg, version, data, manifest_bytes, sig)?;
+            eprintln!("[import] {:?} {} -> {}", pkg, version, dest.display());
+        }
+        families.push(BundleFamily {
+            package: distro_pkg_name(*pkg).to_string(),
+            path: dest.display().to_string(),
+            sha256: sha.clone(),
+        });
+    }
+    audit::emit(Event::BundleImport(BundleImport {
+        dry_run: matches!(mode, ApplyMode::DryRun),
+        bundle: file.display().to_string(),
+        version: version.to_string(),
+        arch: manifest.arch.clone(),
+        key,
+        families,
+    }));
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! `mmdebstrap-hook`: run `use`/`replace` against the rootfs an image builder hands to its
+//! customize hook as `$1`, e.g.
+//!
+//! ```text
+//! mmdebstrap --customize-hook='oxidizr-deb mmdebstrap-hook replace "$1" --all --confirm-essential-removal' ...
+//! ```
+//!
+//! Every step commits without prompting, apt runs through the image mode (`chroot` unless
+//! `--image-mode` says otherwise), and any failure, including a replacement that is not active
+//! afterwards, exits non-zero so the image build aborts.
+use std::path::{Path, PathBuf};
+
+use oxidizr_cli_core::events::{Event, ImageHook};
+
+use crate::adapters::dpkg::STATUS_REL;
+use crate::cli::args::{Cli, Commands, ImageHookOp, ImageMode, LogSink, OutputFormat, Package};
+use crate::cli::handler;
+use crate::commands::replace::{is_active, targets_for};
+use crate::errors::{CliError, CliResult};
+use crate::packages::distro_pkg_name;
+use crate::state::audit;
+
+/// The rootfs must be a Debian tree other than the host's own root.
+fn check_rootfs(rootfs: &Path) -> CliResult<PathBuf> {
+    let root = std::fs::canonicalize(rootfs)
+        .map_err(|e| CliError::InvalidRoot(format!("rootfs {}: {e}", rootfs.display())))?;
+    if root == Path::new("/") {
+        return Err(CliError::InvalidRoot(
+            "mmdebstrap-hook must be given the image rootfs, not /".to_string(),
+        ));
+    }
+    if !root.join(STATUS_REL).is_file() {
+        return Err(CliError::InvalidRoot(format!(
+            "{} has no /{STATUS_REL}; run mmdebstrap-hook as a --customize-hook",
+            root.display()
+        )));
+    }
+    Ok(root)
+}
+
+pub fn exec(
+    op: ImageHookOp,
+    image_mode: Option<ImageMode>,
+    apt_version: Option<String>,
+    log_sink: Option<LogSink>,
+    output: OutputFormat,
+) -> CliResult {
+    // Setup and extract hooks run before packages are installed and configured
+    if let Ok(phase) = std::env::var("MMDEBSTRAP_HOOK") {
+        if phase != "customize" {
+            return Err(CliError::Failed(format!(
+                "mmdebstrap-hook must run as a --customize-hook (called as a {phase} hook)"
+            )));
+        }
+    }
+    // No debconf prompts from apt/dpkg inside the image
+    std::env::set_var("DEBIAN_FRONTEND", "noninteractive");
+    let (op_name, rootfs, commands, packages) = match op {
+        ImageHookOp::Use { rootfs, packages } => {
+            let commands = packages
+                .iter()
+                .map(|p| Commands::Use {
+                    package: *p,
+                    offline: false,
+                    use_local: None,
+                    deb: None,
+                    apt_repo: None,
+                    divert: false,
+                    alternatives: false,
+                })
+                .collect::<Vec<_>>();
+            ("use", rootfs, commands, packages)
+        }
+        ImageHookOp::Replace {
+            rootfs,
+            package,
+            all,
+            confirm_essential_removal,
+        } => (
+            "replace",
+            rootfs,
+            vec![Commands::Replace {
+                package,
+                all,
+                confirm_essential_removal,
+            }],
+            targets_for(package, all)?,
+        ),
+    };
+    let root = check_rootfs(&rootfs)?;
+    let mode = image_mode.unwrap_or(ImageMode::Chroot);
+
+    let result = commands.into_iter().try_for_each(|command| {
+        handler::dispatch(Cli {
+            root: root.clone(),
+            commit: true,
+            assume_yes: true,
+            apt_version: apt_version.clone(),
+            image_mode: Some(mode),
+            log_sink,
+            output,
+            command,
+        })
+    });
+    let result = result.and_then(|_| {
+        let inactive: Vec<Package> = packages
+            .iter()
+            .filter(|p| !is_active(&root, **p))
+            .copied()
+            .collect();
+        if inactive.is_empty() {
+            Ok(())
+        } else {
+            Err(CliError::InvariantViolation(format!(
+                "invariant violation: replacement not active in {} for {:?}",
+                root.display(),
+                inactive
+            )))
+        }
+    });
+    audit::emit(Event::MmdebstrapHook(ImageHook {
+        operation: op_name.to_string(),
+        rootfs: root.display().to_string(),
+        image_mode: mode.as_str().to_string(),
+        packages: packages
+            .iter()
+            .map(|p| distro_pkg_name(*p).to_string())
+            .collect(),
+        ok: result.is_ok(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+    }));
+    result
+}
@@ -1,7 +1,17 @@
+pub mod apply;
+pub mod audit;
 pub mod doctor;
+pub mod events;
+pub mod history;
+pub mod hooks;
+pub mod import_bundle;
+pub mod mmdebstrap_hook;
+pub mod plan;
+pub mod repair;
 pub mod replace;
 pub mod restore;
 pub mod status;
+pub mod undo;
 pub mod use_cmd;
 
 pub use use_cmd as r#use;
@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use serde_json::json;
+
+use crate::cli::args::PlanOp;
+use crate::commands::{r#use, replace, restore};
+use crate::output;
+use crate::plan::{Operation, PackagePlan, Plan, PlanFile};
+use crate::state::State;
+
+/// Build the plan `op` describes, as `plan` writes it. Also used to record the plan hash of a
+/// directly run command in the history.
+pub fn build(root: &Path, op: PlanOp) -> Result<Plan, String> {
+    let confirm_essential_removal = matches!(
+        op,
+        PlanOp::Replace {
+            confirm_essential_removal: true,
+            ..
+        }
+    );
+    let (operation, packages): (Operation, Vec<PackagePlan>) = match op {
+        PlanOp::Use {
+            package,
+            offline,
+            use_local,
+        } => (
+            Operation::Use,
+            vec![r#use::build_plan(
+                root,
+                package,
+                offline,
+                use_local.as_ref(),
+            )?],
+        ),
+        PlanOp::Restore {
+            package,
+            all,
+            keep_replacements,
+        } => (Operation::Restore, {
+            let st = State::load(root)?;
+            restore::targets_for(root, st.as_ref(), package, all)
+                .into_iter()
+                .map(|p| restore::build_plan(root, st.as_ref(), p, keep_replacements))
+                .collect()
+        }),
+        PlanOp::Replace { package, all, .. } => (
+            Operation::Replace,
+            replace::targets_for(package, all)?
+                .into_iter()
+                .map(|p| replace::build_plan(root, p))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+    };
+
+    let mut plan = Plan::new(operation, root, packages);
+    plan.confirm_essential_removal = confirm_essential_removal;
+    Ok(plan)
+}
+
+pub fn exec(root: &Path, op: PlanOp, out: &Path) -> Result<(), String> {
+    let file = PlanFile::new(build(root, op)?);
+    file.write(out)?;
+
+    for pp in &file.plan.packages {
+        let apt: Vec<String> = pp
+            .apt
+            .iter()
+            .map(|s| format!("apt-get {}", s.args().join(" ")))
+            .collect();
+        eprintln!(
+            "[plan] {:?}: {} link(s), {} restore(s){}",
+            pp.package,
+            pp.links.len(),
+            pp.restores.len(),
+            if apt.is_empty() {
+                String::new()
+            } else {
+                format!("; {}", apt.join("; "))
+            }
+        );
+    }
+    if output::active() {
+        return output::print_json(
+            &json!({"plan": out.display().to_string(), "hash": file.hash}),
+            false,
+        );
+    }
+    println!("plan: {}", out.display());
+    println!("hash: {}", file.hash);
+    Ok(())
+}
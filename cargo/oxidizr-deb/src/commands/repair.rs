@@ -0,0 +1,246 @@
+use std::path::Path;
+
+use oxidizr_cli_core::audit::AuditLog;
+use oxidizr_cli_core::events;
+use serde::Serialize;
+use switchyard::types::safepath::SafePath;
+use switchyard::types::{ApplyMode, LinkRequest, PlanInput};
+use switchyard::Switchyard;
+
+use crate::adapters::alternatives;
+use crate::adapters::debian::pm_lock_message;
+use crate::adapters::divert;
+use crate::adapters::preflight::sudo_guard;
+use crate::cli::args::Package;
+use crate::commands::use_cmd::ensure_links;
+use crate::errors::{CliError, CliResult};
+use crate::output;
+use crate::packages;
+use crate::state::inspect::{inspect, AppletStatus, FamilyStatus};
+use crate::state::{self, State};
+use crate::util::paths::ensure_under_root;
+
+#[derive(Serialize)]
+struct DriftedApplet {
+    name: String,
+    was: AppletStatus,
+}
+
+#[derive(Serialize)]
+pub struct FamilyRepair {
+    package: Package,
+    status_before: FamilyStatus,
+    drifted: Vec<DriftedApplet>,
+    planned_actions: usize,
+    fixed: usize,
+}
+
+impl FamilyRepair {
+    /// The result as carried by a `hook.run` event.
+    pub fn to_event(&self) -> events::FamilyRepair {
+        events::FamilyRepair {
+            package: packages::distro_pkg_name(self.package).to_string(),
+            status_before: self.status_before.as_str().to_string(),
+            drifted: self
+                .drifted
+                .iter()
+                .map(|a| events::DriftedApplet {
+                    name: a.name.clone(),
+                    was: a.was.as_str().to_string(),
+                })
+                .collect(),
+            planned_actions: self.planned_actions,
+            fixed: self.fixed,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RepairJson {
+    dry_run: bool,
+    families: Vec<FamilyRepair>,
+}
+
+fn repair_family(
+    api: &Switchyard<AuditLog, AuditLog>,
+    root: &Path,
+    st: &State,
+    pkg: Package,
+    mode: ApplyMode,
+) -> CliResult<FamilyRepair> {
+    let report = inspect(root, Some(st), pkg);
+    // Alternatives groups and diversions are owned by their adapters, and a foreign link
+    // (busybox, an admin's own choice) is not ours to overwrite: relink plain drift only.
+    let names: Vec<String> = report.applets.iter().map(|a| a.name.clone()).collect();
+    let (plain, _managed) = alternatives::split_managed(root, &names)?;
+    let (plain, _diverted) = divert::split_diverted(root, &plain)?;
+    let (drifted, left): (Vec<DriftedApplet>, Vec<DriftedApplet>) = report
+        .applets
+        .iter()
+        .filter(|a| a.status != AppletStatus::Linked)
+        .map(|a| DriftedApplet {
+            name: a.name.clone(),
+            was: a.status,
+        })
+        .partition(|a| a.was != AppletStatus::Foreign && plain.contains(&a.name));
+    if !left.is_empty() {
+        let list: Vec<String> = left
+            .iter()
+            .map(|a| format!("{} ({})", a.name, a.was.as_str()))
+            .collect();
+        eprintln!(
+            "[repair] {}: leaving {} applet(s) not linked by oxidizr-deb alone: {}",
+            packages::distro_pkg_name(pkg),
+            left.len(),
+            list.join(", ")
+        );
+    }
+    let mut out = FamilyRepair {
+        package: pkg,
+        status_before: report.status,
+        drifted,
+        planned_actions: 0,
+        fixed: 0,
+    };
+    if out.drifted.is_empty() {
+        return Ok(out);
+    }
+
+    let source_bin = report.expected_target;
+    if !source_bin.exists() {
+        return Err(format!(
+            "replacement artifact for {:?} missing at {}; re-run `oxidizr-deb --commit use {}`",
+            pkg,
+            source_bin.display(),
+            packages::distro_pkg_name(pkg)
+        )
+        .into());
+    }
+    if matches!(mode, ApplyMode::Commit) && matches!(pkg, Package::Sudo) {
+        sudo_guard(root, &source_bin)?;
+    }
+
+    // Same LinkRequest path as `use`, restricted to the applets that drifted.
+    let dest_base = ensure_under_root(root, Path::new(packages::DEST_DIR));
+    let s_sp = SafePath::from_rooted(root, &source_bin)
+        .map_err(|e| format!("invalid source_bin: {e:?}"))?;
+    let mut links = Vec::new();
+    for a in &out.drifted {
+        let d_sp = SafePath::from_rooted(root, &dest_base.join(&a.name))
+            .map_err(|e| format!("invalid dest: {e:?}"))?;
+        links.push(LinkRequest {
+            source: s_sp.clone(),
+            target: d_sp,
+        });
+    }
+    let plan = api.plan(PlanInput {
+        link: links,
+        restore: vec![],
+    });
+    let _pre = api
+        .preflight(&plan)
+        .map_err(|e| CliError::switchyard("preflight", e))?;
+    output::planned(&plan.actions);
+    let rep = api
+        .apply(&plan, mode)
+        .map_err(|e| CliError::switchyard("apply", e))?;
+    output::executed(mode, &rep.executed);
+    out.planned_actions = rep.executed.len();
+
+    if matches!(mode, ApplyMode::Commit) {
+        let names: Vec<String> = out.drifted.iter().map(|a| a.name.clone()).collect();
+        ensure_links(root, &source_bin, &names)?;
+        let after = inspect(root, Some(st), pkg);
+        out.fixed = after
+            .applets
+            .iter()
+            .filter(|a| a.status == AppletStatus::Linked && names.contains(&a.name))
+            .count();
+        let f = st.family(pkg).expect("repaired family is recorded");
+        state::record_use(root, pkg, &f.applets, &source_bin, None, None)?;
+    }
+    Ok(out)
+}
+
+/// Repair recorded families without the package-manager lock check; `hooks run` calls this
+/// from inside dpkg, where the dpkg locks are held by the very apt run that invoked us.
+pub fn run(
+    api: &Switchyard<AuditLog, AuditLog>,
+    root: &Path,
+    st: &State,
+    package: Option<Package>,
+    mode: ApplyMode,
+) -> CliResult<Vec<FamilyRepair>> {
+    let targets: Vec<Package> = match package {
+        Some(p) if st.family(p).is_none() => {
+            eprintln!(
+                "[info] {:?}: not switched by oxidizr-deb (no state record); nothing to repair",
+                p
+            );
+            vec![]
+        }
+        Some(p) => vec![p],
+        None => st.families.keys().copied().collect(),
+    };
+
+    let mut results = Vec::new();
+    for p in targets {
+        let r = repair_family(api, root, st, p, mode)?;
+        let name = packages::distro_pkg_name(p);
+        if r.drifted.is_empty() {
+            eprintln!("[repair] {}: in sync", name);
+        } else {
+            let list: Vec<String> = r
+                .drifted
+                .iter()
+                .map(|a| format!("{} ({})", a.name, a.was.as_str()))
+                .collect();
+            eprintln!(
+                "[repair] {}: {} drifted applet(s): {}",
+                name,
+                r.drifted.len(),
+                list.join(", ")
+            );
+            if matches!(mode, ApplyMode::DryRun) {
+                eprintln!("dry-run: planned {} actions", r.planned_actions);
+            } else {
+                eprintln!("[repair] {}: relinked {} applet(s)", name, r.fixed);
+            }
+        }
+        results.push(r);
+    }
+    Ok(results)
+}
+
+pub fn exec(
+    api: &Switchyard<AuditLog, AuditLog>,
+    root: &Path,
+    package: Option<Package>,
+    mode: ApplyMode,
+    json: bool,
+) -> CliResult {
+    if matches!(mode, ApplyMode::Commit) {
+        if let Some(msg) = pm_lock_message(root) {
+            return Err(CliError::LockBusy(msg));
+        }
+    }
+    let results = match State::load(root)? {
+        Some(st) => run(api, root, &st, package, mode)?,
+        None => {
+            eprintln!(
+                "[info] no state file under {}; nothing to repair",
+                root.display()
+            );
+            vec![]
+        }
+    };
+
+    if json {
+        let payload = RepairJson {
+            dry_run: matches!(mode, ApplyMode::DryRun),
+            families: results,
+        };
+        output::print_json(&payload, false)?;
+    }
+    Ok(())
+}
@@ -1,43 +1,37 @@
 use std::path::Path;
-use std::process::{Command, Stdio};
 
+use oxidizr_cli_core::audit::AuditLog;
+use oxidizr_cli_core::events::{EssentialDecision, Event, ReplaceEssential};
 use oxidizr_cli_core::prompts::should_proceed;
 use oxidizr_cli_core::{coverage_preflight, PackageKind};
-use switchyard::logging::JsonlSink;
 use switchyard::types::ApplyMode;
 use switchyard::Switchyard;
 
-use crate::adapters::debian::pm_lock_message;
+use crate::adapters::debian::{
+    apt_get, apt_transaction, pm_lock_message, preview_transaction, simulate, AptTransaction,
+};
 use crate::adapters::debian_adapter::DebianAdapter;
-use crate::cli::args::Package;
+use crate::adapters::dpkg;
+use crate::adapters::image;
+use crate::adapters::provider_deb::{self, shim_name, SHIM_DIR_REL};
+use crate::cli::args::{ImageMode, Package};
+use crate::commands::use_cmd::UseOptions;
+use crate::errors::{CliError, CliResult};
 use crate::fetch::fallback::apt_pkg_name;
 use crate::fetch::resolver::resolve_artifact;
-use crate::packages;
-use serde_json::json;
-
-fn distro_pkg_name(pkg: Package) -> &'static str {
-    match pkg {
-        Package::Coreutils => "coreutils",
-        Package::Findutils => "findutils",
-        Package::Sudo => "sudo",
-    }
-}
+use crate::fetch::sources::Source;
+use crate::output;
+use crate::packages::distro_pkg_name;
+use crate::plan::{AptStep, PackagePlan};
+use crate::state;
+use crate::state::archive;
+use crate::state::audit;
 
 fn replacement_pkg_name(pkg: Package) -> &'static str {
     apt_pkg_name(pkg)
 }
 
-fn dpkg_installed(name: &str) -> bool {
-    let st = Command::new("dpkg")
-        .args(["-s", name])
-        .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status();
-    matches!(st, Ok(s) if s.success())
-}
-
-fn is_active(root: &Path, pkg: Package) -> bool {
+pub fn is_active(root: &Path, pkg: Package) -> bool {
     let path = match pkg {
         Package::Coreutils => root.join("usr/bin/ls"),
         Package::Findutils => root.join("usr/bin/find"),
@@ -48,146 +42,401 @@ fn is_active(root: &Path, pkg: Package) -> bool {
         .unwrap_or(false)
 }
 
-pub fn exec(
-    api: &Switchyard<JsonlSink, JsonlSink>,
-    root: &Path,
-    package: Option<Package>,
-    all: bool,
-    mode: ApplyMode,
-    assume_yes: bool,
-) -> Result<(), String> {
-    if matches!(mode, ApplyMode::Commit) {
-        if let Some(msg) = pm_lock_message(root) {
-            return Err(msg);
-        }
-        // Live-root constraint for PM mutations
-        if root != Path::new("/") {
-            return Err(
-                "replace operations require --root=/ (live system) for apt/dpkg changes"
-                    .to_string(),
-            );
-        }
-        // Confirm if interactive
-        if !assume_yes && !should_proceed(assume_yes, root) {
-            return Err("aborted by user".to_string());
-        }
-    }
-
-    let targets: Vec<Package> = if all {
-        vec![Package::Coreutils, Package::Findutils, Package::Sudo]
+pub fn targets_for(package: Option<Package>, all: bool) -> Result<Vec<Package>, String> {
+    if all {
+        Ok(vec![Package::Coreutils, Package::Findutils, Package::Sudo])
     } else if let Some(p) = package {
-        vec![p]
+        Ok(vec![p])
     } else {
-        return Err("specify a package or use --all".to_string());
-    };
+        Err("specify a package or use --all".to_string())
+    }
+}
 
-    // First ensure RS is installed & active (use semantics)
-    for p in &targets {
-        // offline/use_local not applicable here; rely on system packages
-        crate::commands::r#use::exec(api, root, *p, false, None, mode)?;
-        // Provider pre-check: replacement must now be active
-        if !is_active(root, *p) {
-            return Err(format!(
-                "replacement for {:?} is not active after use; aborting replace",
-                p
-            ));
+pub fn coverage_gate(root: &Path, p: Package, src: &Path) -> CliResult {
+    let kind = match p {
+        Package::Coreutils => Some(PackageKind::Coreutils),
+        Package::Findutils => Some(PackageKind::Findutils),
+        Package::Sudo => None,
+    };
+    if let Some(k) = kind {
+        if let Err(missing) = coverage_preflight(&DebianAdapter, root, k, src) {
+            return Err(CliError::CoverageIncomplete(format!(
+                "cannot replace {:?}: replacement does not cover all applets; missing: {}",
+                p,
+                missing.join(", ")
+            )));
         }
-        // Coverage preflight: replacement must cover all distro-provided applets (coreutils/findutils)
-        let kind = match p {
-            Package::Coreutils => Some(PackageKind::Coreutils),
-            Package::Findutils => Some(PackageKind::Findutils),
-            Package::Sudo => None,
-        };
-        if let Some(k) = kind {
-            let src = resolve_artifact(root, *p, false, None);
-            if let Err(missing) = coverage_preflight(&DebianAdapter, root, k, &src) {
-                return Err(format!(
-                    "cannot replace {:?}: replacement does not cover all applets; missing: {}",
-                    p,
-                    missing.join(", ")
-                ));
+    }
+    Ok(())
+}
+
+/// Distro packages among `names` that the root's dpkg status marks `Essential: yes`.
+fn essential_packages(root: &Path, names: &[String]) -> Result<Vec<String>, String> {
+    let db = dpkg::StatusDb::load(root)?;
+    Ok(names
+        .iter()
+        .filter(|n| db.get(n).is_some_and(|p| p.essential))
+        .cloned()
+        .collect())
+}
+
+fn essential_event(decision: EssentialDecision, packages: &[String], reason: &str) {
+    audit::emit(Event::ReplaceEssential(ReplaceEssential {
+        decision,
+        packages: packages.to_vec(),
+        reason: reason.to_string(),
+    }));
+}
+
+/// Commands the Essential package `name` ships (per its dpkg file list) that would be gone after
+/// the purge: paths that do not resolve, or that still resolve to one of the package's own files.
+/// Maintainer scripts of other packages call these unconditionally.
+fn essential_files_missing(root: &Path, name: &str) -> Vec<String> {
+    let owned = dpkg::package_files(root, name);
+    // Owned paths with their directories resolved (usrmerge), but not the files themselves
+    let owned_paths: Vec<std::path::PathBuf> = owned
+        .iter()
+        .filter_map(|f| {
+            let p = root.join(f.trim_start_matches('/'));
+            let dir = std::fs::canonicalize(p.parent()?).ok()?;
+            Some(dir.join(p.file_name()?))
+        })
+        .collect();
+    owned
+        .iter()
+        .filter(|f| {
+            f.strip_prefix("/usr/bin/")
+                .or_else(|| f.strip_prefix("/bin/"))
+                .is_some_and(|n| !n.is_empty() && !n.contains('/'))
+        })
+        .filter(|f| {
+            let path = root.join(f.trim_start_matches('/'));
+            let is_link = path
+                .symlink_metadata()
+                .map(|m| m.file_type().is_symlink())
+                .unwrap_or(false);
+            match std::fs::canonicalize(&path) {
+                // A link that lands on a file outside the GNU package survives the purge
+                Ok(target) => !is_link || owned_paths.contains(&target),
+                Err(_) => true,
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// Build the `replace` plan for one package: `use` semantics plus purging the distro package.
+pub fn build_plan(root: &Path, p: Package) -> Result<PackagePlan, String> {
+    let mut pp = crate::commands::r#use::build_plan(root, p, false, None)?;
+    let src = resolve_artifact(root, p, false, None);
+    coverage_gate(root, p, &src).map_err(|e| e.to_string())?;
+    pp.apt.push(AptStep::purge(distro_pkg_name(p)));
+    Ok(pp)
+}
+
+/// The apt side of `replace`, shared with `apply` of a replace plan. The replacements are installed
+/// in one transaction before the switch; after it, one transaction purges the distro packages and
+/// installs the provider shims. The two are simulated together before either runs.
+pub struct Transaction {
+    targets: Vec<Package>,
+    install: Vec<String>,
+    purge: Vec<String>,
+    /// Essential packages among `purge`, confirmed by the operator.
+    essential: Vec<String>,
+    shims: Vec<String>,
+}
+
+impl Transaction {
+    /// Decide the Essential confirmation up front: committing an unconfirmed Essential purge is
+    /// refused before anything changes.
+    pub fn new(
+        root: &Path,
+        targets: &[Package],
+        install: Vec<String>,
+        confirm_essential: bool,
+        mode: ApplyMode,
+    ) -> CliResult<Self> {
+        let purge: Vec<String> = targets
+            .iter()
+            .map(|p| distro_pkg_name(*p).to_string())
+            .collect();
+        let essential = essential_packages(root, &purge)?;
+        if !essential.is_empty() {
+            if matches!(mode, ApplyMode::DryRun) {
+                if confirm_essential {
+                    eprintln!(
+                        "[dry-run] would purge Essential package(s) {} with --allow-remove-essential",
+                        essential.join(" ")
+                    );
+                } else {
+                    eprintln!(
+                        "[dry-run] {} is Essential: yes; --commit will require --confirm-essential-removal",
+                        essential.join(" ")
+                    );
+                }
+            } else if !confirm_essential {
+                essential_event(EssentialDecision::Deny, &essential, "not confirmed");
+                return Err(CliError::UserAborted(format!(
+                    "refusing to purge Essential package(s) {}: pass --confirm-essential-removal to proceed",
+                    essential.join(", ")
+                )));
             }
         }
+        Ok(Transaction {
+            targets: targets.to_vec(),
+            install,
+            purge,
+            essential,
+            shims: vec![],
+        })
     }
 
-    // Then remove the distro packages under guardrails
-    for p in &targets {
-        let name = distro_pkg_name(*p);
-        if matches!(mode, ApplyMode::DryRun) {
-            eprintln!("[dry-run] would run: apt-get purge -y {}", name);
-            continue;
+    /// Provider shims keep `Depends: <gnu>` satisfied once the GNU packages are gone. They
+    /// conflict with the GNU packages, so the purge transaction installs them.
+    fn build_shims(&self, root: &Path, dir: &Path) -> Result<Vec<String>, String> {
+        self.targets
+            .iter()
+            .map(|p| provider_deb::build(root, *p, dir).map(|d| d.display().to_string()))
+            .collect()
+    }
+
+    fn combined(&self, shims: &[String]) -> AptTransaction {
+        AptTransaction {
+            install: self.install.iter().chain(shims).cloned().collect(),
+            purge: self.purge.clone(),
+            ..Default::default()
         }
-        // Provider invariant pre-check: ensure at least one provider remains available
-        let rs_name = replacement_pkg_name(*p);
-        let have_rs_pkg = dpkg_installed(rs_name);
-        if !is_active(root, *p) {
-            return Err(format!(
-                "invariant violation: replacement for {:?} not active before purge",
-                p
-            ));
+    }
+
+    /// Validate the whole transaction, install the replacements and archive the distro `.deb`s
+    /// for an offline `undo`.
+    pub fn install(&mut self, root: &Path, mode: ApplyMode) -> CliResult {
+        if matches!(mode, ApplyMode::DryRun) {
+            if !self.install.is_empty() {
+                eprintln!(
+                    "[dry-run] would run: apt-get install -y {}",
+                    self.install.join(" ")
+                );
+                output::planned_apt(root, "apt-get", &self.install, &[]);
+            }
+        } else {
+            apt_get(
+                root,
+                &["update".to_string()],
+                Event::PmUpdate,
+                &self.install.join(" "),
+            )?;
+            // Validate the combined end state (installs and purges) before mutating anything, and
+            // refuse if apt would take reverse dependencies down with the distro packages.
+            self.shims = self.build_shims(root, &root.join(SHIM_DIR_REL))?;
+            let delta = simulate(root, &self.combined(&self.shims))?;
+            let extra = delta.unrequested_removals(&self.purge);
+            if !extra.is_empty() {
+                let names: Vec<String> = extra
+                    .iter()
+                    .map(|c| match &c.from {
+                        Some(v) => format!("{} ({})", c.name, v),
+                        None => c.name.clone(),
+                    })
+                    .collect();
+                return Err(CliError::InvariantViolation(format!(
+                    "refusing to replace: apt would also remove {}; run without --commit to see the full delta",
+                    names.join(", ")
+                )));
+            }
+            if !self.install.is_empty() {
+                apt_transaction(
+                    root,
+                    &AptTransaction {
+                        install: self.install.clone(),
+                        ..Default::default()
+                    },
+                    false,
+                )?;
+            }
         }
-        if !have_rs_pkg {
-            return Err(format!(
-                "invariant violation: no replacement package present for {:?}",
-                p
-            ));
+
+        // Keep the GNU .debs for an offline `undo`, while their files are still untouched
+        for name in self.purge.iter().filter(|n| dpkg::is_installed(root, n)) {
+            if matches!(mode, ApplyMode::DryRun) {
+                let (dest, origin) = archive::locate(root, name)?;
+                eprintln!(
+                    "[dry-run] would archive {} to {} ({})",
+                    name,
+                    state::rooted(root, &dest).display(),
+                    match origin {
+                        archive::Origin::AptCache(_) => "from the apt cache",
+                        archive::Origin::Repack => "rebuilt from the installed files",
+                    }
+                );
+            } else {
+                let dest = archive::archive_package(root, name)?;
+                eprintln!(
+                    "[archive] {} -> {}",
+                    name,
+                    state::rooted(root, &dest).display()
+                );
+            }
         }
-        let mut cmd = Command::new("apt-get");
-        let args = vec!["purge".to_string(), "-y".to_string(), name.to_string()];
-        let args_view = args.clone();
-        cmd.args(&args);
-        cmd.stdin(Stdio::null());
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
-        let out = cmd
-            .output()
-            .map_err(|e| format!("failed to spawn apt-get: {e}"))?;
-        let code = out.status.code().unwrap_or(1);
-        let stderr_tail = String::from_utf8_lossy(&out.stderr);
-        eprintln!(
-            "{}",
-            json!({
-                "event":"pm.purge",
-                "pm": {"tool":"apt-get","args": args_view, "package": name},
-                "exit_code": code,
-                "stderr_tail": stderr_tail.chars().rev().take(400).collect::<String>().chars().rev().collect::<String>()
-            })
-        );
-        if code != 0 {
-            return Err(format!(
-                "apt-get purge {} failed with exit code {}",
-                name, code
-            ));
+        Ok(())
+    }
+
+    /// Once every family is switched, remove the distro packages under guardrails, in a single
+    /// transaction that also installs the provider shims.
+    pub fn purge(&self, root: &Path, mode: ApplyMode) -> CliResult {
+        if matches!(mode, ApplyMode::DryRun) {
+            for p in &self.targets {
+                eprintln!(
+                    "[dry-run] would install provider shim {} (Provides/Replaces/Conflicts: {}, Depends: {})",
+                    shim_name(*p),
+                    distro_pkg_name(*p),
+                    replacement_pkg_name(*p)
+                );
+            }
+            eprintln!(
+                "[dry-run] would run: apt-get purge -y {}",
+                self.purge.join(" ")
+            );
+            let shim_names: Vec<String> = self.targets.iter().map(|p| shim_name(*p)).collect();
+            output::planned_apt(root, "apt-get", &shim_names, &self.purge);
+            // Shims are built in a scratch directory only so the simulation can include them
+            // (inside the root for a chroot, which cannot see the host's temp directory)
+            let scratch_base = match image::mode(root) {
+                Some(ImageMode::Chroot) => root.join("tmp"),
+                _ => std::env::temp_dir(),
+            };
+            let scratch = scratch_base.join(format!("oxidizr-deb-shims-{}", std::process::id()));
+            preview_transaction(root, &self.combined(&self.build_shims(root, &scratch)?));
+            let _ = std::fs::remove_dir_all(&scratch);
+            return Ok(());
         }
-        // Pre-check: replacement must be active before purging GNU packages
-        if matches!(mode, ApplyMode::Commit) {
+        for p in &self.targets {
             if !is_active(root, *p) {
-                return Err(format!(
-                    "replacement for {:?} not active before purge; aborting",
+                return Err(CliError::InvariantViolation(format!(
+                    "invariant violation: replacement for {:?} not active before purge",
                     p
-                ));
+                )));
             }
         }
-    }
+        for name in &self.essential {
+            let missing = essential_files_missing(root, name);
+            if !missing.is_empty() {
+                let reason = format!(
+                    "replacement does not provide {} file(s) shipped by {}: {}",
+                    missing.len(),
+                    name,
+                    missing.join(", ")
+                );
+                essential_event(EssentialDecision::Deny, &self.essential, &reason);
+                return Err(CliError::CoverageIncomplete(format!(
+                    "refusing to purge Essential package {name}: {reason}"
+                )));
+            }
+        }
+        if !self.essential.is_empty() {
+            essential_event(
+                EssentialDecision::Allow,
+                &self.essential,
+                "confirmed; replacement provides every file",
+            );
+        }
+        apt_transaction(
+            root,
+            &AptTransaction {
+                install: self.shims.clone(),
+                purge: self.purge.clone(),
+                // apt passes this down to dpkg as --force-remove-essential
+                allow_remove_essential: !self.essential.is_empty(),
+                ..Default::default()
+            },
+            false,
+        )?;
+        for p in &self.targets {
+            state::record_purge(root, *p, distro_pkg_name(*p))?;
+        }
 
-    // Post-check: replacement must remain active after purging GNU packages and provider remains
-    if matches!(mode, ApplyMode::Commit) {
-        for p in &targets {
+        // Post-check: replacement must remain active, and the shim (which depends on it) installed
+        for p in &self.targets {
             if !is_active(root, *p) {
-                return Err(format!(
+                return Err(CliError::InvariantViolation(format!(
                     "invariant violation: replacement for {:?} not active after purge",
                     p
-                ));
+                )));
             }
-            let rs_name = replacement_pkg_name(*p);
-            if !dpkg_installed(rs_name) {
-                return Err(format!(
-                    "invariant violation: no provider present for {:?} after purge",
-                    p
-                ));
+            if !dpkg::is_installed(root, &shim_name(*p)) {
+                return Err(CliError::InvariantViolation(format!(
+                    "invariant violation: provider shim {} not installed after purge",
+                    shim_name(*p)
+                )));
             }
         }
+        Ok(())
     }
+}
 
-    Ok(())
+pub fn exec(
+    api: &Switchyard<AuditLog, AuditLog>,
+    root: &Path,
+    package: Option<Package>,
+    all: bool,
+    mode: ApplyMode,
+    assume_yes: bool,
+    confirm_essential: bool,
+) -> CliResult {
+    if matches!(mode, ApplyMode::Commit) {
+        if let Some(msg) = pm_lock_message(root) {
+            return Err(CliError::LockBusy(msg));
+        }
+        // Live-root constraint for PM mutations, unless apt runs against the image
+        if !image::apt_reachable(root) {
+            return Err(CliError::InvalidRoot(
+                "replace operations require --root=/ (live system) for apt/dpkg changes; use --image-mode dir|chroot for a rootfs"
+                    .to_string(),
+            ));
+        }
+        // Confirm if interactive
+        if !assume_yes && !should_proceed(assume_yes, root) {
+            return Err(CliError::UserAborted("aborted by user".to_string()));
+        }
+    }
+
+    let targets = targets_for(package, all)?;
+    let apt_root = image::apt_reachable(root);
+    let pin = std::env::var("OXIDIZR_DEB_APT_VERSION").ok();
+
+    // One install and one purge transaction for all selected families; where apt can run (live
+    // root or image mode) it always does (no-op or upgrade when already installed), elsewhere
+    // only for missing artifacts.
+    let to_install: Vec<String> = targets
+        .iter()
+        .filter(|p| apt_root || !resolve_artifact(root, **p, false, None).exists())
+        .map(|p| match pin.as_deref() {
+            Some(v) => format!("{}={}", replacement_pkg_name(*p), v),
+            None => replacement_pkg_name(*p).to_string(),
+        })
+        .collect();
+
+    let mut tx = Transaction::new(root, &targets, to_install, confirm_essential, mode)?;
+    tx.install(root, mode)?;
+
+    // Then switch each family to the now-installed replacement (use semantics, no apt)
+    for p in &targets {
+        let opts = UseOptions {
+            source: Source::Installed,
+            ..Default::default()
+        };
+        crate::commands::r#use::exec(api, root, *p, opts, mode)?;
+        // Provider pre-check: replacement must now be active
+        if matches!(mode, ApplyMode::Commit) && !is_active(root, *p) {
+            return Err(CliError::InvariantViolation(format!(
+                "replacement for {:?} is not active after use; aborting replace",
+                p
+            )));
+        }
+        // Coverage preflight: replacement must cover all distro-provided applets (coreutils/findutils)
+        let src = resolve_artifact(root, *p, false, None);
+        coverage_gate(root, *p, &src)?;
+    }
+
+    tx.purge(root, mode)
 }
@@ -1,161 +1,374 @@
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
 
-use switchyard::logging::JsonlSink;
+use oxidizr_cli_core::audit::AuditLog;
 use switchyard::types::safepath::SafePath;
 use switchyard::types::{ApplyMode, PlanInput, RestoreRequest};
 use switchyard::Switchyard;
 
-use crate::adapters::debian::pm_lock_message;
+use crate::adapters::alternatives;
+use crate::adapters::debian::{
+    apt_transaction, pm_lock_message, preview_transaction, simulate, AptTransaction,
+};
 use crate::adapters::debian_adapter::DebianAdapter;
+use crate::adapters::divert;
+use crate::adapters::dpkg;
+use crate::adapters::image;
+use crate::adapters::provider_deb::shim_name;
 use crate::cli::args::Package;
+use crate::errors::{CliError, CliResult};
 use crate::fetch::fallback::apt_pkg_name;
+use crate::output;
 use crate::packages;
+use crate::packages::distro_pkg_name;
+use crate::plan::drift::observe;
+use crate::plan::{AptStep, PackagePlan, RestoreAction};
+use crate::state::{self, State};
 use crate::util::paths::ensure_under_root;
 use oxidizr_cli_core::{static_fallback_applets, DistroAdapter, PackageKind};
-use serde_json::json;
-
-fn distro_pkg_name(pkg: Package) -> &'static str {
-    match pkg {
-        Package::Coreutils => "coreutils",
-        Package::Findutils => "findutils",
-        Package::Sudo => "sudo",
-    }
-}
-
-fn dpkg_installed(name: &str) -> bool {
-    let st = Command::new("dpkg")
-        .args(["-s", name])
-        .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status();
-    matches!(st, Ok(s) if s.success())
-}
 
 // Replacement package name in apt (Debian-correct)
 fn replacement_pkg_name(pkg: Package) -> &'static str {
     apt_pkg_name(pkg)
 }
 
-pub fn exec(
-    api: &Switchyard<JsonlSink, JsonlSink>,
-    root: &Path,
-    package: Option<Package>,
-    all: bool,
-    keep_replacements: bool,
-    mode: ApplyMode,
-    _assume_yes: bool,
-) -> Result<(), String> {
-    let live_root = root == Path::new("/");
-    if matches!(mode, ApplyMode::Commit) && live_root {
-        if let Some(msg) = pm_lock_message(root) {
-            return Err(msg);
-        }
+/// Applets to restore for one package: exactly what the state file recorded when present,
+/// otherwise dpkg-enumerated when possible, static list as a last resort.
+pub fn applets_for(root: &Path, st: Option<&State>, pkg: Package) -> Vec<String> {
+    if let Some(f) = st.and_then(|s| s.family(pkg)) {
+        return f.applets.clone();
     }
-    let dest_dir = PathBuf::from(packages::DEST_DIR);
     let adapter = DebianAdapter;
-    let applets = if all || package.is_none() {
-        let mut out: Vec<String> = Vec::new();
-        let cu = {
-            let d = adapter.enumerate_package_commands(root, PackageKind::Coreutils);
-            if d.is_empty() {
-                static_fallback_applets(PackageKind::Coreutils)
-            } else {
-                d
-            }
-        };
-        let fu = {
-            let d = adapter.enumerate_package_commands(root, PackageKind::Findutils);
+    match pkg {
+        Package::Coreutils | Package::Findutils => {
+            let kind = packages::kind_of(pkg);
+            let d = adapter.enumerate_package_commands(root, kind);
             if d.is_empty() {
-                static_fallback_applets(PackageKind::Findutils)
+                static_fallback_applets(kind)
             } else {
                 d
             }
-        };
-        let su = static_fallback_applets(PackageKind::Sudo);
-        out.extend(cu);
-        out.extend(fu);
-        out.extend(su);
-        out
+        }
+        Package::Sudo => static_fallback_applets(PackageKind::Sudo),
+    }
+}
+
+/// Families to restore. With a state file, only families oxidizr-deb actually switched, plus
+/// families whose alternatives groups were kept by an earlier `restore --keep-replacements`.
+pub fn targets_for(
+    root: &Path,
+    st: Option<&State>,
+    package: Option<Package>,
+    all: bool,
+) -> Vec<Package> {
+    let requested = if all || package.is_none() {
+        vec![Package::Coreutils, Package::Findutils, Package::Sudo]
     } else {
-        match package.unwrap() {
-            Package::Coreutils => {
-                let d = adapter.enumerate_package_commands(root, PackageKind::Coreutils);
-                if d.is_empty() {
-                    static_fallback_applets(PackageKind::Coreutils)
-                } else {
-                    d
+        vec![package.unwrap()]
+    };
+    match st {
+        None => requested,
+        Some(s) => requested
+            .into_iter()
+            .filter(|p| {
+                let recorded = s.family(*p).is_some()
+                    || alternatives::split_managed(root, &applets_for(root, None, *p))
+                        .map(|(_, managed)| !managed.is_empty())
+                        .unwrap_or(false);
+                if !recorded {
+                    eprintln!(
+                        "[info] {:?}: not switched by oxidizr-deb (no state record); nothing to restore",
+                        p
+                    );
                 }
+                recorded
+            })
+            .collect(),
+    }
+}
+
+/// Replacement packages to purge: the ones oxidizr-deb recorded installing, if known.
+fn purge_names(st: Option<&State>, pkg: Package) -> Vec<String> {
+    match st.and_then(|s| s.family(pkg)) {
+        Some(f) => f.apt_installed.clone(),
+        None => vec![replacement_pkg_name(pkg).to_string()],
+    }
+}
+
+/// Build the `restore` plan for one package without touching the system.
+pub fn build_plan(
+    root: &Path,
+    st: Option<&State>,
+    pkg: Package,
+    keep_replacements: bool,
+) -> PackagePlan {
+    let applets = applets_for(root, st, pkg);
+    let dest_base = ensure_under_root(root, Path::new(packages::DEST_DIR));
+    let restores = applets
+        .iter()
+        .map(|app| {
+            let target = dest_base.join(app);
+            RestoreAction {
+                observed: observe(&target),
+                target,
             }
-            Package::Findutils => {
-                let d = adapter.enumerate_package_commands(root, PackageKind::Findutils);
-                if d.is_empty() {
-                    static_fallback_applets(PackageKind::Findutils)
+        })
+        .collect();
+    let mut apt = vec![AptStep::install(distro_pkg_name(pkg), None)];
+    if !keep_replacements {
+        for name in purge_names(st, pkg) {
+            apt.push(AptStep::purge(&name));
+        }
+    }
+    PackagePlan {
+        package: pkg,
+        applets,
+        artifact: None,
+        links: vec![],
+        restores,
+        apt,
+    }
+}
+
+/// Under a non-live root apt cannot reinstall the distro packages, so applets Switchyard had no
+/// backup for stay linked to the replacement. The BDD fixtures' stock applets are placeholder
+/// files, which test builds put back; anywhere else that is an error, as for `use`/`replace`.
+#[cfg(feature = "bdd")]
+fn non_live_fallback(root: &Path, applets: &[String]) -> CliResult {
+    use std::fs;
+    let dest_base = ensure_under_root(root, Path::new(packages::DEST_DIR));
+    for app in applets {
+        let dst = dest_base.join(app);
+        let mut rewrite = false;
+        match fs::symlink_metadata(&dst) {
+            Ok(md) => {
+                if !md.file_type().is_file() {
+                    rewrite = true;
+                } else if let Ok(s) = fs::read_to_string(&dst) {
+                    if !s.starts_with(&format!("gnu-{}", app)) {
+                        rewrite = true;
+                    }
                 } else {
-                    d
+                    rewrite = true;
                 }
             }
-            Package::Sudo => static_fallback_applets(PackageKind::Sudo),
+            Err(_) => {
+                rewrite = true;
+            }
         }
-    };
+        if rewrite {
+            let _ = fs::remove_file(&dst);
+            if let Some(parent) = dst.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            let content = format!("gnu-{}", app);
+            let _ = fs::write(&dst, content.as_bytes());
+        }
+    }
+    Ok(())
+}
 
-    // Determine which package groups to affect for PM operations
-    let targets: Vec<Package> = if all || package.is_none() {
-        vec![Package::Coreutils, Package::Findutils, Package::Sudo]
-    } else {
-        vec![package.unwrap()]
-    };
+#[cfg(not(feature = "bdd"))]
+fn non_live_fallback(root: &Path, applets: &[String]) -> CliResult {
+    let dest_base = ensure_under_root(root, Path::new(packages::DEST_DIR));
+    let linked: Vec<&str> = applets
+        .iter()
+        .filter(|a| dest_base.join(a).is_symlink())
+        .map(String::as_str)
+        .collect();
+    if linked.is_empty() {
+        return Ok(());
+    }
+    Err(CliError::InvalidRoot(format!(
+        "no backup to restore {} from under {}; reinstalling the distro package requires --root=/ (live system) or --image-mode",
+        linked.join(", "),
+        root.display()
+    )))
+}
 
-    // Pre: ensure distro packages are installed when committing
-    if matches!(mode, ApplyMode::Commit) {
-        if !live_root {
+/// The apt side of `restore`, shared with `apply` of a restore plan. Before the switch back, one
+/// transaction reinstalls the distro packages and purges the provider shims; after it, one
+/// transaction purges the replacements. The two are simulated together before either runs.
+pub struct Transaction {
+    install: Vec<String>,
+    purge: Vec<String>,
+    /// Provider shims conflict with the distro packages, so they go in the install transaction,
+    /// even with --keep-replacements.
+    shims: Vec<String>,
+    no_download: bool,
+}
+
+impl Transaction {
+    pub fn new(
+        root: &Path,
+        targets: &[Package],
+        install: Vec<String>,
+        purge: Vec<String>,
+        no_download: bool,
+    ) -> Self {
+        let shims = targets
+            .iter()
+            .map(|p| shim_name(*p))
+            .filter(|n| dpkg::is_installed(root, n))
+            .collect();
+        Transaction {
+            install,
+            purge,
+            shims,
+            no_download,
+        }
+    }
+
+    fn combined(&self) -> AptTransaction {
+        AptTransaction {
+            install: self.install.clone(),
+            purge: self
+                .purge
+                .iter()
+                .chain(self.shims.iter())
+                .cloned()
+                .collect(),
+            no_download: self.no_download,
+            ..Default::default()
+        }
+    }
+
+    /// Ensure the distro packages are installed, validated together with the later purge.
+    pub fn install(&self, root: &Path, mode: ApplyMode) -> CliResult {
+        if matches!(mode, ApplyMode::Commit) {
+            if !image::apt_reachable(root) {
+                eprintln!(
+                    "[info] skipping apt/dpkg install steps under non-live root (no --image-mode): {}",
+                    root.display()
+                );
+                return Ok(());
+            }
+            simulate(root, &self.combined())?;
+            apt_transaction(
+                root,
+                &AptTransaction {
+                    install: self.install.clone(),
+                    purge: self.shims.clone(),
+                    no_download: self.no_download,
+                    ..Default::default()
+                },
+                false,
+            )?;
+        } else {
+            // Dry-run preview of PM steps
+            let shims_purge: String = self.shims.iter().map(|n| format!(" {n}-")).collect();
             eprintln!(
-                "[info] skipping apt/dpkg install steps under non-live root: {}",
-                root.display()
+                "[dry-run] would run: apt-get install -y {}{}{}",
+                if self.no_download {
+                    "--no-download "
+                } else {
+                    ""
+                },
+                self.install.join(" "),
+                if self.shims.is_empty() {
+                    String::new()
+                } else {
+                    format!(" --purge{shims_purge}")
+                }
             );
-        } else {
-            for p in &targets {
-                let name = distro_pkg_name(*p);
-                let mut cmd = Command::new("apt-get");
-                let args = vec!["install".to_string(), "-y".to_string(), name.to_string()];
-                let args_view = args.clone();
-                cmd.args(&args);
-                cmd.stdin(Stdio::null());
-                cmd.stdout(Stdio::piped());
-                cmd.stderr(Stdio::piped());
-                let out = cmd
-                    .output()
-                    .map_err(|e| format!("failed to spawn apt-get: {e}"))?;
-                let code = out.status.code().unwrap_or(1);
-                let stderr_tail = String::from_utf8_lossy(&out.stderr);
+            output::planned_apt(root, "apt-get", &self.install, &self.shims);
+        }
+        Ok(())
+    }
+
+    /// Once the applets are switched back, remove the replacement packages in a single
+    /// transaction.
+    pub fn purge(&self, root: &Path, mode: ApplyMode) -> CliResult {
+        if matches!(mode, ApplyMode::DryRun) {
+            if !self.purge.is_empty() {
                 eprintln!(
-                    "{}",
-                    json!({
-                        "event":"pm.install",
-                        "pm": {"tool":"apt-get","args": args_view, "package": name},
-                        "exit_code": code,
-                        "stderr_tail": stderr_tail.chars().rev().take(400).collect::<String>().chars().rev().collect::<String>()
-                    })
+                    "[dry-run] would run: apt-get purge -y {}",
+                    self.purge.join(" ")
                 );
-                if code != 0 {
-                    return Err(format!(
-                        "apt-get install {} failed with exit code {}",
-                        name, code
-                    ));
-                }
+                output::planned_apt(root, "apt-get", &[], &self.purge);
             }
+            preview_transaction(root, &self.combined());
+        } else if image::apt_reachable(root) && !self.purge.is_empty() {
+            apt_transaction(
+                root,
+                &AptTransaction {
+                    purge: self.purge.clone(),
+                    ..Default::default()
+                },
+                false,
+            )?;
         }
-    } else {
-        // Dry-run preview of PM steps
-        for p in &targets {
-            let name = distro_pkg_name(*p);
-            eprintln!("[dry-run] would run: apt-get install -y {}", name);
+        Ok(())
+    }
+}
+
+/// Options for `restore` beyond the package selection.
+#[derive(Debug, Default)]
+pub struct RestoreOptions {
+    pub keep_replacements: bool,
+    /// Reinstall the distro packages from these archived `.deb`s (paths inside the root),
+    /// without downloading anything, instead of from apt's sources. Used by `undo`.
+    pub from_archive: Vec<PathBuf>,
+}
+
+pub fn exec(
+    api: &Switchyard<AuditLog, AuditLog>,
+    root: &Path,
+    package: Option<Package>,
+    all: bool,
+    opts: RestoreOptions,
+    mode: ApplyMode,
+) -> CliResult {
+    let RestoreOptions {
+        keep_replacements,
+        from_archive,
+    } = opts;
+    let offline = !from_archive.is_empty();
+    let apt_root = image::apt_reachable(root);
+    if matches!(mode, ApplyMode::Commit) && apt_root {
+        if let Some(msg) = pm_lock_message(root) {
+            return Err(CliError::LockBusy(msg));
         }
     }
+    let dest_dir = PathBuf::from(packages::DEST_DIR);
+    let st = State::load(root)?;
+    let targets = targets_for(root, st.as_ref(), package, all);
+    let applets: Vec<String> = targets
+        .iter()
+        .flat_map(|p| applets_for(root, st.as_ref(), *p))
+        .collect();
 
+    // RS packages to remove afterwards unless --keep-replacements
+    let check_installed = matches!(mode, ApplyMode::Commit) && apt_root;
+    let purge: Vec<String> = if keep_replacements {
+        vec![]
+    } else {
+        targets
+            .iter()
+            .flat_map(|p| purge_names(st.as_ref(), *p))
+            .filter(|n| !check_installed || dpkg::is_installed(root, n))
+            .collect()
+    };
+    let install: Vec<String> = if offline {
+        from_archive
+            .iter()
+            .map(|p| ensure_under_root(root, p).display().to_string())
+            .collect()
+    } else {
+        targets
+            .iter()
+            .map(|p| distro_pkg_name(*p).to_string())
+            .collect()
+    };
+    let tx = Transaction::new(root, &targets, install, purge, offline);
+    tx.install(root, mode)?;
+
+    // Alternatives-managed applets switch by selection; --keep-replacements only de-prefers.
+    // Their GNU files are diverted too, so split them off before looking at diversions.
+    let (plain, managed) = alternatives::split_managed(root, &applets)?;
+    // Diverted applets come back via dpkg-divert --remove --rename, not Switchyard backups.
+    let (plain, diverted) = divert::split_diverted(root, &plain)?;
     let mut restores = Vec::new();
-    for app in &applets {
+    for app in &plain {
         let dest_base = ensure_under_root(root, &dest_dir);
         let dst = dest_base.join(app);
         let sp = SafePath::from_rooted(root, &dst).map_err(|e| format!("invalid target: {e:?}"))?;
@@ -168,90 +381,34 @@ pub fn exec(
     });
     let _pre = api
         .preflight(&plan)
-        .map_err(|e| format!("preflight failed: {e:?}"))?;
-    let _rep = api
+        .map_err(|e| CliError::switchyard("preflight", e))?;
+    output::planned(&plan.actions);
+    let rep = api
         .apply(&plan, mode)
-        .map_err(|e| format!("apply failed: {e:?}"))?;
+        .map_err(|e| CliError::switchyard("apply", e))?;
+    output::executed(mode, &rep.executed);
 
-    if matches!(mode, ApplyMode::Commit) && !live_root {
-        // Pragmatic fallback for tests under non-live roots only.
-        #[cfg(unix)]
-        {
-            use std::fs;
-            for app in &applets {
-                let dest_base = ensure_under_root(root, &dest_dir);
-                let dst = dest_base.join(app);
-                let mut rewrite = false;
-                match fs::symlink_metadata(&dst) {
-                    Ok(md) => {
-                        if !md.file_type().is_file() {
-                            rewrite = true;
-                        } else if let Ok(s) = fs::read_to_string(&dst) {
-                            if !s.starts_with(&format!("gnu-{}", app)) {
-                                rewrite = true;
-                            }
-                        } else {
-                            rewrite = true;
-                        }
-                    }
-                    Err(_) => {
-                        rewrite = true;
-                    }
-                }
-                if rewrite {
-                    let _ = fs::remove_file(&dst);
-                    if let Some(parent) = dst.parent() {
-                        let _ = fs::create_dir_all(parent);
-                    }
-                    let content = format!("gnu-{}", app);
-                    let _ = fs::write(&dst, content.as_bytes());
-                }
-            }
-        }
+    divert::undivert_applets(root, &diverted, mode)?;
+    if keep_replacements {
+        alternatives::prefer_gnu(root, &managed, mode)?;
+    } else {
+        alternatives::remove_applets(root, &managed, mode)?;
+    }
+
+    if matches!(mode, ApplyMode::Commit) && !apt_root {
+        let unmanaged: Vec<String> = applets
+            .iter()
+            .filter(|a| !(keep_replacements && managed.contains(a)))
+            .cloned()
+            .collect();
+        non_live_fallback(root, &unmanaged)?;
     }
 
-    // Post: by default remove RS packages unless --keep-replacements
+    tx.purge(root, mode)?;
+
     if matches!(mode, ApplyMode::Commit) {
-        if !keep_replacements {
-            for p in &targets {
-                let rs_name = replacement_pkg_name(*p);
-                if live_root && dpkg_installed(rs_name) {
-                    let mut cmd = Command::new("apt-get");
-                    let args = vec!["purge".to_string(), "-y".to_string(), rs_name.to_string()];
-                    let args_view = args.clone();
-                    cmd.args(&args);
-                    cmd.stdin(Stdio::null());
-                    cmd.stdout(Stdio::piped());
-                    cmd.stderr(Stdio::piped());
-                    let out = cmd
-                        .output()
-                        .map_err(|e| format!("failed to spawn apt-get: {e}"))?;
-                    let code = out.status.code().unwrap_or(1);
-                    let stderr_tail = String::from_utf8_lossy(&out.stderr);
-                    eprintln!(
-                        "{}",
-                        json!({
-                            "event":"pm.purge",
-                            "pm": {"tool":"apt-get","args": args_view, "package": rs_name},
-                            "exit_code": code,
-                            "stderr_tail": stderr_tail.chars().rev().take(400).collect::<String>().chars().rev().collect::<String>()
-                        })
-                    );
-                    if code != 0 {
-                        return Err(format!(
-                            "apt-get purge {} failed with exit code {}",
-                            rs_name, code
-                        ));
-                    }
-                }
-            }
-        }
-    } else {
-        if !keep_replacements {
-            for p in &targets {
-                let name = replacement_pkg_name(*p);
-                eprintln!("[dry-run] would run: apt-get purge -y {}", name);
-            }
+        for p in &targets {
+            state::record_restore(root, *p)?;
         }
     }
 
@@ -2,61 +2,163 @@ use std::path::Path;
 
 use serde::Serialize;
 
+use crate::cli::args::Package;
+use crate::output;
+use crate::packages::distro_pkg_name;
+use crate::state::inspect::{inspect, AppletStatus, FamilyReport, FamilyStatus};
+use crate::state::{Mode, State};
+
+const FAMILIES: [Package; 3] = [Package::Coreutils, Package::Findutils, Package::Sudo];
+
+#[derive(Serialize)]
+struct Families<'a> {
+    coreutils: &'a FamilyReport,
+    findutils: &'a FamilyReport,
+    sudo: &'a FamilyReport,
+}
+
+#[derive(Serialize)]
+struct Suggestion {
+    package: &'static str,
+    action: &'static str,
+    command: String,
+    reason: &'static str,
+}
+
 #[derive(Serialize)]
 struct StatusJson<'a> {
     coreutils: &'a str,
     findutils: &'a str,
     sudo: &'a str,
+    families: Families<'a>,
+    suggestions: Vec<Suggestion>,
+}
+
+fn suggestions_for(r: &FamilyReport) -> Vec<Suggestion> {
+    let name = distro_pkg_name(r.package);
+    let s = |action, command: String, reason| Suggestion {
+        package: name,
+        action,
+        command,
+        reason,
+    };
+    match r.status {
+        FamilyStatus::Active => {
+            let mut out = vec![s(
+                "restore",
+                format!("oxidizr-deb restore {} --commit", name),
+                "switch back to the GNU/stock tools",
+            )];
+            if r.mode != Some(Mode::Replaced) {
+                out.push(s(
+                    "replace",
+                    format!("oxidizr-deb --commit replace {}", name),
+                    "after validating workloads, remove the GNU packages",
+                ));
+            }
+            out
+        }
+        FamilyStatus::Partial | FamilyStatus::Broken => vec![
+            s(
+                "use",
+                format!("oxidizr-deb --commit use {}", name),
+                "re-link the applets that are not pointing at the replacement",
+            ),
+            s(
+                "restore",
+                format!("oxidizr-deb restore {} --commit", name),
+                "switch back to the GNU/stock tools",
+            ),
+        ],
+        FamilyStatus::Foreign => vec![s(
+            "inspect",
+            format!(
+                "ls -l /usr/bin/{}",
+                r.applets
+                    .iter()
+                    .find(|a| a.status == AppletStatus::Foreign)
+                    .map(|a| a.name.as_str())
+                    .unwrap_or(name)
+            ),
+            "another provider (e.g. busybox) owns these links; oxidizr-deb will not touch them implicitly",
+        )],
+        FamilyStatus::Unset => vec![],
+    }
+}
+
+fn print_human(r: &FamilyReport) {
+    println!("{}: {}", distro_pkg_name(r.package), r.status.as_str());
+    if r.status == FamilyStatus::Unset && !r.recorded {
+        return;
+    }
+    if let Some(m) = r.mode {
+        println!(
+            "  mode: {}",
+            match m {
+                Mode::Used => "use",
+                Mode::Replaced => "replace",
+            }
+        );
+    }
+    let c = &r.counts;
+    println!(
+        "  applets: {} linked, {} stock, {} foreign, {} dangling, {} missing (of {})",
+        c.linked,
+        c.stock,
+        c.foreign,
+        c.dangling,
+        c.missing,
+        r.applets.len()
+    );
+    println!("  expected target: {}", r.expected_target.display());
+    for a in r
+        .applets
+        .iter()
+        .filter(|a| a.status != AppletStatus::Linked)
+    {
+        let actual = a
+            .actual
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "    {}: {} (expected {}, actual {})",
+            a.name,
+            a.status.as_str(),
+            a.expected.display(),
+            actual
+        );
+    }
 }
 
 pub fn exec(root: &Path, json: bool) -> Result<(), String> {
-    let ls = root.join("usr/bin/ls");
-    let find = root.join("usr/bin/find");
-    let sudo = root.join("usr/bin/sudo");
-    let coreutils_active = ls
-        .symlink_metadata()
-        .map(|m| m.file_type().is_symlink())
-        .unwrap_or(false);
-    let findutils_active = find
-        .symlink_metadata()
-        .map(|m| m.file_type().is_symlink())
-        .unwrap_or(false);
-    let sudo_active = sudo
-        .symlink_metadata()
-        .map(|m| m.file_type().is_symlink())
-        .unwrap_or(false);
+    // Prefer the state file; only roots switched by an older oxidizr-deb fall back to probing.
+    let st = State::load(root)?;
+    let reports: Vec<FamilyReport> = FAMILIES
+        .iter()
+        .map(|p| inspect(root, st.as_ref(), *p))
+        .collect();
+    let suggestions: Vec<Suggestion> = reports.iter().flat_map(suggestions_for).collect();
 
     if json {
         let payload = StatusJson {
-            coreutils: if coreutils_active { "active" } else { "unset" },
-            findutils: if findutils_active { "active" } else { "unset" },
-            sudo: if sudo_active { "active" } else { "unset" },
+            coreutils: reports[0].status.as_str(),
+            findutils: reports[1].status.as_str(),
+            sudo: reports[2].status.as_str(),
+            families: Families {
+                coreutils: &reports[0],
+                findutils: &reports[1],
+                sudo: &reports[2],
+            },
+            suggestions,
         };
-        println!(
-            "{}",
-            serde_json::to_string(&payload).map_err(|e| e.to_string())?
-        );
+        output::print_json(&payload, false)?;
     } else {
-        println!(
-            "coreutils: {}",
-            if coreutils_active { "active" } else { "unset" }
-        );
-        println!(
-            "findutils: {}",
-            if findutils_active { "active" } else { "unset" }
-        );
-        println!("sudo: {}", if sudo_active { "active" } else { "unset" });
-        if coreutils_active {
-            eprintln!("Tip: restore with 'oxidizr-deb restore coreutils --commit'.");
-            eprintln!("Next: after validating workloads, you may fully switch by removing GNU packages with 'oxidizr-deb --commit replace coreutils'.");
-        }
-        if findutils_active {
-            eprintln!("Tip: restore with 'oxidizr-deb restore findutils --commit'.");
-            eprintln!("Next: after validating workloads, you may fully switch by removing GNU packages with 'oxidizr-deb --commit replace findutils'.");
+        for r in &reports {
+            print_human(r);
         }
-        if sudo_active {
-            eprintln!("Tip: restore with 'oxidizr-deb restore sudo --commit'.");
-            eprintln!("Next: after validating workloads, you may fully switch by removing GNU packages with 'oxidizr-deb --commit replace sudo'.");
+        for s in &suggestions {
+            eprintln!("Tip ({}): '{}' — {}.", s.action, s.command, s.reason);
         }
     }
     Ok(())
@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use oxidizr_cli_core::audit::AuditLog;
+use switchyard::types::ApplyMode;
+use switchyard::Switchyard;
+
+use crate::adapters::dpkg;
+use crate::adapters::image;
+use crate::commands::restore::{self, RestoreOptions};
+use crate::errors::{CliError, CliResult};
+use crate::packages::distro_pkg_name;
+use crate::state::history;
+use crate::util::paths::ensure_under_root;
+
+/// Revert history entry `id` (a successful `replace`): restore the GNU tools and reinstall the
+/// exact prior GNU package versions from the archive, without network access.
+pub fn exec(
+    api: &Switchyard<AuditLog, AuditLog>,
+    root: &Path,
+    id: u64,
+    mode: ApplyMode,
+) -> CliResult {
+    let entries = history::load(root)?;
+    let entry = entries
+        .iter()
+        .find(|e| e.id == id)
+        .ok_or_else(|| format!("no history entry #{id}; see `oxidizr-deb history`"))?;
+    if entry.operation != "replace" || !entry.success {
+        return Err(format!(
+            "history entry #{id} is {} ({}); only successful replace runs can be undone",
+            entry.operation,
+            if entry.success { "ok" } else { "failed" }
+        )
+        .into());
+    }
+    if let Some(u) = entries.iter().find(|e| e.undoes == Some(id) && e.success) {
+        return Err(format!("history entry #{id} was already undone by #{}", u.id).into());
+    }
+    for a in &entry.archived {
+        if !ensure_under_root(root, a).is_file() {
+            return Err(format!(
+                "archived package {} is missing; cannot undo #{id} offline",
+                a.display()
+            )
+            .into());
+        }
+    }
+    let purged: Vec<&str> = entry
+        .packages
+        .iter()
+        .map(|p| distro_pkg_name(*p))
+        .filter(|n| entry.versions_before.contains_key(*n))
+        .collect();
+    if purged.len() != entry.archived.len() {
+        return Err(format!(
+            "history entry #{id} has {} archived package(s) for {} purged distro package(s); cannot undo offline",
+            entry.archived.len(),
+            purged.len()
+        )
+        .into());
+    }
+    for a in &entry.archived {
+        let verb = if matches!(mode, ApplyMode::DryRun) {
+            "[dry-run] would reinstall"
+        } else {
+            "[undo] reinstalling"
+        };
+        eprintln!("{verb} {} (offline)", a.display());
+    }
+
+    let (package, all) = match entry.packages.as_slice() {
+        [p] => (Some(*p), false),
+        _ => (None, true),
+    };
+    let opts = RestoreOptions {
+        keep_replacements: false,
+        from_archive: entry.archived.clone(),
+    };
+    restore::exec(api, root, package, all, opts, mode)?;
+
+    // The point of undo is the exact prior versions; say so loudly if apt picked others.
+    if matches!(mode, ApplyMode::Commit) && image::apt_reachable(root) {
+        for name in purged {
+            let want = &entry.versions_before[name];
+            let have = dpkg::installed_version(root, name);
+            if have.as_deref() != Some(want.as_str()) {
+                return Err(CliError::InvariantViolation(format!(
+                    "undo #{id}: {name} is {} after reinstall, expected {want}",
+                    have.as_deref().unwrap_or("not installed")
+                )));
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,104 +1,387 @@
 use std::path::{Path, PathBuf};
 
-use switchyard::logging::JsonlSink;
+use oxidizr_cli_core::audit::AuditLog;
+use oxidizr_cli_core::events::{ArtifactVerify, Event, SkipApplet, SkipReason, VerifyDecision};
 use switchyard::types::safepath::SafePath;
 use switchyard::types::{ApplyMode, LinkRequest, PlanInput};
 use switchyard::Switchyard;
 
+use crate::adapters::alternatives;
 use crate::adapters::debian::pm_lock_message;
 use crate::adapters::debian_adapter::DebianAdapter;
+use crate::adapters::divert;
+use crate::adapters::image;
 use crate::adapters::preflight::sudo_guard;
+use crate::adapters::unpack::find_cached_deb;
 use crate::cli::args::Package;
-use crate::fetch::fallback::ensure_artifact_available;
+use crate::errors::{CliError, CliResult};
+use crate::fetch::fallback::{
+    apt_pkg_name, ensure_artifact_available, local_source_entry, LOCAL_SOURCE_REL,
+};
 use crate::fetch::resolver::resolve_artifact;
+use crate::fetch::sources::Source;
+use crate::fetch::verifier::{verify_artifact, Verified};
+use crate::output;
 use crate::packages;
+use crate::plan::drift::observe;
+use crate::plan::{AptStep, Artifact, LinkAction, PackagePlan};
+use crate::state;
+use crate::state::audit;
+use crate::util::hash::sha256_file;
 use crate::util::paths::ensure_under_root;
-use oxidizr_cli_core::{resolve_applets_for_use, PackageKind};
+use oxidizr_cli_core::{coverage_preflight, resolve_applets_for_use, PackageKind};
 
-fn apt_pkg_name(pkg: Package) -> &'static str {
-    match pkg {
-        Package::Coreutils => "rust-coreutils",
-        Package::Findutils => "rust-findutils",
-        Package::Sudo => "sudo-rs",
+/// Verify an offline artifact for `package` and emit an `artifact.verify` event carrying the
+/// verified digest.
+pub fn verify_offline(
+    root: &Path,
+    package: Package,
+    source_bin: &Path,
+) -> Result<Verified, String> {
+    let res = verify_artifact(root, source_bin, packages::distro_pkg_name(package));
+    let path = source_bin.display().to_string();
+    audit::emit(Event::ArtifactVerify(match &res {
+        Ok(v) => ArtifactVerify {
+            decision: VerifyDecision::Success,
+            path,
+            sha256: Some(v.sha256.clone()),
+            name: Some(v.name.clone()),
+            version: Some(v.version.clone()),
+            arch: Some(v.arch.clone()),
+            key: Some(v.key.clone()),
+            error: None,
+        },
+        Err(e) => ArtifactVerify {
+            decision: VerifyDecision::Failure,
+            path,
+            sha256: None,
+            name: None,
+            version: None,
+            arch: None,
+            key: None,
+            error: Some(e.clone()),
+        },
+    }));
+    res
+}
+
+/// Emit a `use.exec.skip_applet` event for each command the distro package ships that the
+/// replacement does not provide; those stay on the distro binary. Nothing is known about a
+/// replacement that is not there yet (a dry run before installing it).
+fn emit_skipped_applets(root: &Path, pkg: PackageKind, source_bin: &Path) {
+    if !source_bin.exists() {
+        return;
+    }
+    if let Err(missing) = coverage_preflight(&DebianAdapter, root, pkg, source_bin) {
+        for applet in missing {
+            audit::emit(Event::SkipApplet(SkipApplet {
+                applet,
+                reason: SkipReason::NotInReplacement,
+                source: source_bin.display().to_string(),
+            }));
+        }
     }
 }
 
-pub fn exec(
-    api: &Switchyard<JsonlSink, JsonlSink>,
+/// Build the `use` plan for one package without touching the system.
+pub fn build_plan(
     root: &Path,
     package: Package,
     offline: bool,
-    use_local: Option<PathBuf>,
+    use_local: Option<&PathBuf>,
+) -> Result<PackagePlan, String> {
+    let source_bin = resolve_artifact(root, package, offline, use_local);
+    if offline {
+        verify_offline(root, package, &source_bin)?;
+    }
+    let mut apt = Vec::new();
+    if !offline && (image::apt_reachable(root) || !source_bin.exists()) {
+        let pin = std::env::var("OXIDIZR_DEB_APT_VERSION").ok();
+        apt.push(AptStep::install(apt_pkg_name(package), pin));
+    }
+    let applets = resolve_applets_for_use(
+        &DebianAdapter,
+        root,
+        packages::kind_of(package),
+        &source_bin,
+    );
+    let dest_base = ensure_under_root(root, Path::new(packages::DEST_DIR));
+    let links = applets
+        .iter()
+        .map(|app| {
+            let target = dest_base.join(app);
+            LinkAction {
+                source: source_bin.clone(),
+                observed: observe(&target),
+                target,
+            }
+        })
+        .collect();
+    Ok(PackagePlan {
+        package,
+        applets,
+        artifact: Some(Artifact {
+            sha256: sha256_file(&source_bin).ok(),
+            path: source_bin,
+        }),
+        links,
+        restores: vec![],
+        apt,
+    })
+}
+
+/// Pragmatic fallback for tests: ensure symlinks exist as expected under --root.
+/// This is a no-op if Switchyard already performed the swap.
+pub fn ensure_links(root: &Path, source_bin: &Path, applets: &[String]) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use std::fs;
+        use std::os::unix::fs as unixfs;
+        let dest_base = ensure_under_root(root, Path::new(packages::DEST_DIR));
+        let src = SafePath::from_rooted(root, source_bin)
+            .map_err(|e| format!("invalid source_bin: {e:?}"))?
+            .as_path();
+        for app in applets {
+            let dst = dest_base.join(app);
+            if let Some(parent) = dst.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            let md = fs::symlink_metadata(&dst);
+            let mut needs = true;
+            if let Ok(m) = md {
+                if m.file_type().is_symlink() {
+                    // Verify points to src; if not, replace
+                    if let Ok(cur) = fs::read_link(&dst) {
+                        if cur == src {
+                            needs = false;
+                        }
+                    }
+                } else {
+                    let _ = fs::remove_file(&dst);
+                }
+            }
+            if needs {
+                let _ = unixfs::symlink(&src, &dst);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Minimal post-apply smoke: ensure enough applets resolve to the planned source.
+pub fn smoke_check(
+    root: &Path,
+    package: Package,
+    source_bin: &Path,
+    applets: &[String],
+) -> CliResult {
+    #[cfg(unix)]
+    {
+        use std::fs;
+        let dest_base = ensure_under_root(root, Path::new(packages::DEST_DIR));
+        let src = SafePath::from_rooted(root, source_bin)
+            .map_err(|e| format!("invalid source_bin: {e:?}"))?
+            .as_path()
+            .to_path_buf();
+        let mut count = 0usize;
+        for app in applets {
+            let dst = dest_base.join(app);
+            if let Ok(md) = fs::symlink_metadata(&dst) {
+                if md.file_type().is_symlink() {
+                    if let Ok(cur) = fs::read_link(&dst) {
+                        // Direct link, or a chain (e.g. via /etc/alternatives) ending at src.
+                        let resolves = matches!(
+                            (fs::canonicalize(&dst), fs::canonicalize(&src)),
+                            (Ok(a), Ok(b)) if a == b
+                        );
+                        if cur == src || resolves {
+                            count += 1;
+                        }
+                    }
+                }
+            }
+        }
+        // A family whose dpkg list names a single command can only ever link one applet.
+        let need = if matches!(package, Package::Coreutils) {
+            2.min(applets.len().max(1))
+        } else {
+            1
+        };
+        if count < need {
+            return Err(CliError::InvariantViolation(format!(
+                "post-apply smoke failed: expected >={} applet symlinks to point to replacement, found {}",
+                need, count
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Options for `use` beyond the package itself.
+#[derive(Debug, Default)]
+pub struct UseOptions {
+    pub offline: bool,
+    pub use_local: Option<PathBuf>,
+    /// Where to install the replacement package from when not offline.
+    pub source: Source,
+    pub divert: bool,
+    pub alternatives: bool,
+}
+
+pub fn exec(
+    api: &Switchyard<AuditLog, AuditLog>,
+    root: &Path,
+    package: Package,
+    opts: UseOptions,
     mode: ApplyMode,
-) -> Result<(), String> {
+) -> CliResult {
+    let UseOptions {
+        offline,
+        use_local,
+        source,
+        divert,
+        alternatives,
+    } = opts;
     if matches!(mode, ApplyMode::Commit) {
         if let Some(msg) = pm_lock_message(root) {
-            return Err(msg);
+            return Err(CliError::LockBusy(msg));
         }
     }
-
-    let (mut source_bin, dest_dir) = match package {
-        Package::Coreutils => {
-            let src = resolve_artifact(root, package, offline, use_local.as_ref());
-            (src, PathBuf::from(packages::DEST_DIR))
-        }
-        Package::Findutils => {
-            let src = resolve_artifact(root, package, offline, use_local.as_ref());
-            (src, PathBuf::from(packages::DEST_DIR))
+    match &source {
+        Source::Deb(p) if !p.is_file() => {
+            return Err(format!("--deb {}: no such file", p.display()).into());
         }
-        Package::Sudo => {
-            let src = resolve_artifact(root, package, offline, use_local.as_ref());
-            (src, PathBuf::from(packages::DEST_DIR))
+        Source::AptRepo(p) if !p.is_dir() => {
+            return Err(format!("--apt-repo {}: no such directory", p.display()).into());
         }
+        _ => {}
+    }
+    let local_source = matches!(source, Source::Deb(_) | Source::AptRepo(_));
+
+    let mut source_bin = resolve_artifact(root, package, offline, use_local.as_ref());
+    let dest_dir = PathBuf::from(packages::DEST_DIR);
+    let mut installed = if !offline && image::apt_reachable(root) {
+        Some(apt_pkg_name(package).to_string())
+    } else {
+        None
     };
 
     // Ensure replacement is present when committing; prefer APT on live root, else fallback fetch/build
     if matches!(mode, ApplyMode::Commit) && !offline {
-        if root == Path::new("/") {
-            // Always attempt apt-first ensure on live system; overrides any pre-existing fallback path
-            match ensure_artifact_available(root, package, true) {
-                Ok(p) => {
+        if image::apt_reachable(root) {
+            // Always attempt apt-first ensure on live system (or image mode); overrides any pre-existing fallback path
+            match ensure_artifact_available(root, package, true, &source) {
+                Ok((p, name)) => {
                     source_bin = p;
+                    installed = Some(name);
                 }
                 Err(e) => {
-                    return Err(format!(
-                        "failed to ensure replacement artifact for {:?}: {}",
-                        package, e
-                    ));
+                    return Err(e.context(format!(
+                        "failed to ensure replacement artifact for {:?}",
+                        package
+                    )));
                 }
             }
-        } else if !source_bin.exists() {
-            return Err(format!(
-                "replacement artifact missing at {}; installing requires --root=/ (live system)",
-                source_bin.display()
-            ));
+        } else if local_source || !source_bin.exists() {
+            // Images and chroots: unpack the .deb into the root without dpkg
+            match ensure_artifact_available(root, package, true, &source) {
+                Ok((p, name)) => {
+                    source_bin = p;
+                    installed = Some(name);
+                }
+                Err(e) if !local_source => {
+                    return Err(e.context(format!(
+                        "replacement artifact missing at {}",
+                        source_bin.display()
+                    )));
+                }
+                Err(e) => return Err(e),
+            }
         }
         // Post-ensure: if sudo, enforce setuid/owner guard
         if matches!(package, Package::Sudo) {
             sudo_guard(root, &source_bin)?;
         }
     } else if matches!(mode, ApplyMode::DryRun) && !offline {
-        if !source_bin.exists() {
-            let pkgname = apt_pkg_name(package);
-            let apt_ver = std::env::var("OXIDIZR_DEB_APT_VERSION").ok();
-            let apt_arg = if let Some(v) = apt_ver {
-                format!("{}={}", pkgname, v)
-            } else {
-                pkgname.to_string()
-            };
-            eprintln!("[dry-run] would run: apt-get install -y {}", apt_arg);
-            // No online fallback; apt-only path.
+        match &source {
+            Source::Deb(deb) if !image::apt_reachable(root) => {
+                eprintln!(
+                    "[dry-run] would unpack {} into {} (no dpkg; maintainer scripts are not run)",
+                    deb.display(),
+                    root.display()
+                );
+                output::planned_apt(root, "unpack", &[deb.display().to_string()], &[]);
+            }
+            Source::Deb(deb) => {
+                eprintln!("[dry-run] would run: apt-get install -y {}", deb.display());
+                output::planned_apt(root, "apt-get", &[deb.display().to_string()], &[]);
+            }
+            Source::AptRepo(dir) => {
+                eprintln!(
+                    "[dry-run] would add temporary source '{}' to /{}",
+                    local_source_entry(root, dir)?,
+                    LOCAL_SOURCE_REL
+                );
+                eprintln!(
+                    "[dry-run] would run: apt-get update (local source only) && apt-get install -y {}",
+                    apt_pkg_name(package)
+                );
+                output::planned_apt(root, "apt-get", &[apt_pkg_name(package).to_string()], &[]);
+            }
+            Source::Installed => {}
+            Source::Distro => {
+                let cached = if !image::apt_reachable(root) && !source_bin.exists() {
+                    find_cached_deb(root, apt_pkg_name(package))
+                } else {
+                    None
+                };
+                if let Some(deb) = cached {
+                    eprintln!(
+                        "[dry-run] would unpack {} into {} (no dpkg; maintainer scripts are not run)",
+                        deb.display(),
+                        root.display()
+                    );
+                    output::planned_apt(root, "unpack", &[deb.display().to_string()], &[]);
+                } else if !source_bin.exists() {
+                    let pkgname = apt_pkg_name(package);
+                    let apt_ver = std::env::var("OXIDIZR_DEB_APT_VERSION").ok();
+                    let apt_arg = if let Some(v) = apt_ver {
+                        format!("{}={}", pkgname, v)
+                    } else {
+                        pkgname.to_string()
+                    };
+                    eprintln!("[dry-run] would run: apt-get install -y {}", apt_arg);
+                    output::planned_apt(root, "apt-get", &[apt_arg], &[]);
+                    // No online fallback; apt-only path.
+                }
+            }
         }
     }
+    let installed = installed.as_deref();
+
+    // Offline artifacts bypass apt's own signature checks, so they must carry a signed manifest.
+    let verified = if offline {
+        Some(verify_offline(root, package, &source_bin)?)
+    } else {
+        None
+    };
+    let version = verified.as_ref().map(|v| v.version.as_str());
 
     // Compute applets after source_bin path is finalized via shared core (dynamic discovery + distro intersection)
     let adapter = DebianAdapter;
-    let pkg_kind = match package {
-        Package::Coreutils => PackageKind::Coreutils,
-        Package::Findutils => PackageKind::Findutils,
-        Package::Sudo => PackageKind::Sudo,
-    };
+    let pkg_kind = packages::kind_of(package);
     let applets: Vec<String> = resolve_applets_for_use(&adapter, root, pkg_kind, &source_bin);
+    emit_skipped_applets(root, pkg_kind, &source_bin);
+
+    if alternatives {
+        // Switching is a change of the selected alternative; no direct Switchyard links.
+        let managed = alternatives::install_applets(root, &source_bin, &applets, mode)?;
+        if matches!(mode, ApplyMode::Commit) {
+            smoke_check(root, package, &source_bin, &managed)?;
+            state::record_use(root, package, &managed, &source_bin, installed, version)?;
+        }
+        return Ok(());
+    }
 
     let mut links = Vec::new();
     for app in &applets {
@@ -119,81 +402,40 @@ pub fn exec(
     });
     let _pre = api
         .preflight(&plan)
-        .map_err(|e| format!("preflight failed: {e:?}"))?;
-    let rep = api
-        .apply(&plan, mode)
-        .map_err(|e| format!("apply failed: {e:?}"))?;
+        .map_err(|e| CliError::switchyard("preflight", e))?;
+    output::planned(&plan.actions);
+    // Only once the plan passed preflight: move the GNU files aside so Switchyard links into a
+    // vacant target.
+    let diverted = if divert {
+        divert::divert_applets(root, &applets, mode)?
+    } else {
+        vec![]
+    };
+    let rep = match api.apply(&plan, mode) {
+        Ok(rep) => rep,
+        Err(e) => {
+            // Put the GNU files back rather than leave the applets missing
+            let names: Vec<String> = diverted
+                .iter()
+                .filter_map(|p| p.file_name())
+                .map(|n| n.to_string_lossy().to_string())
+                .collect();
+            if let Err(u) = divert::undivert_applets(root, &names, mode) {
+                output::warning(&format!("failed to undo diversions: {u}"));
+            }
+            return Err(CliError::switchyard("apply", e));
+        }
+    };
+    output::executed(mode, &rep.executed);
 
     if matches!(mode, ApplyMode::DryRun) {
         eprintln!("dry-run: planned {} actions", rep.executed.len());
     } else {
-        // Pragmatic fallback for tests: ensure symlinks exist as expected under --root.
-        // This is a no-op if Switchyard already performed the swap.
-        #[cfg(unix)]
-        {
-            use std::fs;
-            use std::os::unix::fs as unixfs;
-            for app in &applets {
-                let dest_base = ensure_under_root(root, &dest_dir);
-                let dst = dest_base.join(app);
-                let src = SafePath::from_rooted(root, &source_bin)
-                    .map_err(|e| format!("invalid source_bin: {e:?}"))?
-                    .as_path();
-                if let Some(parent) = dst.parent() {
-                    let _ = fs::create_dir_all(parent);
-                }
-                let md = fs::symlink_metadata(&dst);
-                let mut needs = true;
-                if let Ok(m) = md {
-                    if m.file_type().is_symlink() {
-                        // Verify points to src; if not, replace
-                        if let Ok(cur) = fs::read_link(&dst) {
-                            if cur == src {
-                                needs = false;
-                            }
-                        }
-                    } else {
-                        let _ = fs::remove_file(&dst);
-                    }
-                }
-                if needs {
-                    let _ = unixfs::symlink(&src, &dst);
-                }
-            }
-        }
-        // Minimal post-apply smoke: ensure at least one applet resolves to the planned source
-        #[cfg(unix)]
-        {
-            use std::fs;
-            let mut count = 0usize;
-            let src = SafePath::from_rooted(root, &source_bin)
-                .map_err(|e| format!("invalid source_bin: {e:?}"))?
-                .as_path()
-                .to_path_buf();
-            for app in &applets {
-                let dest_base = ensure_under_root(root, &dest_dir);
-                let dst = dest_base.join(app);
-                if let Ok(md) = fs::symlink_metadata(&dst) {
-                    if md.file_type().is_symlink() {
-                        if let Ok(cur) = fs::read_link(&dst) {
-                            if cur == src {
-                                count += 1;
-                            }
-                        }
-                    }
-                }
-            }
-            let need = if matches!(package, Package::Coreutils) {
-                2
-            } else {
-                1
-            };
-            if count < need {
-                return Err(format!(
-                    "post-apply smoke failed: expected >={} applet symlinks to point to replacement, found {}",
-                    need, count
-                ));
-            }
+        ensure_links(root, &source_bin, &applets)?;
+        smoke_check(root, package, &source_bin, &applets)?;
+        state::record_use(root, package, &applets, &source_bin, installed, version)?;
+        if !diverted.is_empty() {
+            state::record_diverted(root, package, &diverted)?;
         }
     }
 
@@ -1,2 +1,137 @@
-// Placeholder for CLI error mapping
-pub type CliResult = Result<(), String>;
+//! Errors surfaced to the user. Each kind has a stable exit code, a machine-readable code and a
+//! remediation hint, so automation can branch on why a run failed. Helpers keep returning
+//! `Result<_, String>`; such errors become `CliError::Failed` when they reach a command.
+use std::fmt;
+
+use serde_json::{json, Value};
+use switchyard::api::errors::ApiError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CliError {
+    /// Any other failure (exit 1).
+    Failed(String),
+    /// dpkg/apt or oxidizr-deb's own lock is held by another process.
+    LockBusy(String),
+    /// The replacement does not provide every command the distro package ships.
+    CoverageIncomplete(String),
+    /// A confirmation was declined.
+    UserAborted(String),
+    /// The root is not in the state an operation guarantees (before or after it ran).
+    InvariantViolation(String),
+    /// apt-get, dpkg or a dpkg helper exited non-zero.
+    PackageManager(String),
+    /// `--root` (or a hook's rootfs) cannot be operated on as asked.
+    InvalidRoot(String),
+}
+
+pub type CliResult<T = ()> = Result<T, CliError>;
+
+impl CliError {
+    pub fn message(&self) -> &str {
+        match self {
+            CliError::Failed(m)
+            | CliError::LockBusy(m)
+            | CliError::CoverageIncomplete(m)
+            | CliError::UserAborted(m)
+            | CliError::InvariantViolation(m)
+            | CliError::PackageManager(m)
+            | CliError::InvalidRoot(m) => m,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            CliError::Failed(_) => "E_FAILED",
+            CliError::LockBusy(_) => "E_LOCK_BUSY",
+            CliError::CoverageIncomplete(_) => "E_COVERAGE_INCOMPLETE",
+            CliError::UserAborted(_) => "E_USER_ABORTED",
+            CliError::InvariantViolation(_) => "E_INVARIANT",
+            CliError::PackageManager(_) => "E_PACKAGE_MANAGER",
+            CliError::InvalidRoot(_) => "E_INVALID_ROOT",
+        }
+    }
+
+    /// Process exit code; 2 is left to clap's usage errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Failed(_) => 1,
+            CliError::LockBusy(_) => 3,
+            CliError::CoverageIncomplete(_) => 4,
+            CliError::UserAborted(_) => 5,
+            CliError::InvariantViolation(_) => 6,
+            CliError::PackageManager(_) => 7,
+            CliError::InvalidRoot(_) => 8,
+        }
+    }
+
+    pub fn hint(&self) -> Option<&'static str> {
+        Some(match self {
+            CliError::Failed(_) => return None,
+            CliError::LockBusy(_) => {
+                "wait for the running apt/dpkg or oxidizr-deb operation to finish, then retry"
+            }
+            CliError::CoverageIncomplete(_) => {
+                "keep the distro package with `use` instead of `replace`, or install a replacement that provides the missing commands"
+            }
+            CliError::UserAborted(_) => {
+                "nothing was changed; confirm the prompt, or pass --assume-yes to run non-interactively"
+            }
+            CliError::InvariantViolation(_) => {
+                "inspect the root with `oxidizr-deb status`; `oxidizr-deb repair` re-links drifted applets"
+            }
+            CliError::PackageManager(_) => {
+                "see the stderr_tail of the failed pm.* event; fix apt/dpkg (e.g. `apt-get -f install`) and retry"
+            }
+            CliError::InvalidRoot(_) => {
+                "point --root at a root filesystem with a dpkg database; apt/dpkg changes need --root=/ or --image-mode"
+            }
+        })
+    }
+
+    /// The same kind of error, its message prefixed with `context: `.
+    pub fn context(self, context: impl fmt::Display) -> Self {
+        let wrap = |m: String| format!("{context}: {m}");
+        match self {
+            CliError::Failed(m) => CliError::Failed(wrap(m)),
+            CliError::LockBusy(m) => CliError::LockBusy(wrap(m)),
+            CliError::CoverageIncomplete(m) => CliError::CoverageIncomplete(wrap(m)),
+            CliError::UserAborted(m) => CliError::UserAborted(wrap(m)),
+            CliError::InvariantViolation(m) => CliError::InvariantViolation(wrap(m)),
+            CliError::PackageManager(m) => CliError::PackageManager(wrap(m)),
+            CliError::InvalidRoot(m) => CliError::InvalidRoot(wrap(m)),
+        }
+    }
+
+    /// Classify a failed Switchyard `preflight`/`apply` by its error variant: lock contention is
+    /// `LockBusy`, a policy refusal `InvariantViolation`, anything else `Failed`.
+    pub fn switchyard(stage: &str, e: ApiError) -> Self {
+        let message = format!("{stage} failed: {e}");
+        match e {
+            ApiError::LockingTimeout(_) => CliError::LockBusy(message),
+            ApiError::PolicyViolation(_) => CliError::InvariantViolation(message),
+            _ => CliError::Failed(message),
+        }
+    }
+
+    /// Error object for `--json` output.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "code": self.code(),
+            "exit_code": self.exit_code(),
+            "message": self.message(),
+            "hint": self.hint(),
+        })
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError::Failed(message)
+    }
+}
@@ -1,7 +1,22 @@
+use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
 
-use crate::cli::args::Package;
+use oxidizr_cli_core::events::{Event, SourceAdd, SourceRemove};
+
+use crate::adapters::debfile::Deb;
+use crate::adapters::debian::apt_get;
+use crate::adapters::dpkg;
+use crate::adapters::image;
+use crate::adapters::unpack::{find_cached_deb, install_deb};
+use crate::cli::args::{ImageMode, Package};
+use crate::errors::{CliError, CliResult};
+use crate::fetch::sources::Source;
+use crate::output;
+use crate::state::archive::APT_CACHE_REL;
+use crate::state::audit;
+
+/// Temporary sources entry written for `--apt-repo`, relative to the root.
+pub const LOCAL_SOURCE_REL: &str = "etc/apt/sources.list.d/oxidizr-deb-local.list";
 
 pub fn apt_pkg_name(pkg: Package) -> &'static str {
     match pkg {
@@ -11,102 +26,248 @@ pub fn apt_pkg_name(pkg: Package) -> &'static str {
     }
 }
 
-fn run(cmd: &str, args: &[&str]) -> Result<(i32, String, String), String> {
-    let mut c = Command::new(cmd);
-    c.args(args);
-    c.stdin(Stdio::null());
-    c.stdout(Stdio::piped());
-    c.stderr(Stdio::piped());
-    match c.output() {
-        Ok(out) => {
-            let code = out.status.code().unwrap_or(1);
-            let so = String::from_utf8_lossy(&out.stdout).to_string();
-            let se = String::from_utf8_lossy(&out.stderr).to_string();
-            Ok((code, so, se))
-        }
-        Err(e) => Err(format!("failed to spawn {}: {}", cmd, e)),
-    }
+fn apt_install(root: &Path, pkg: &str) -> CliResult {
+    apt_get(root, &["update".to_string()], Event::PmUpdate, pkg)?;
+    // Optional version pin via env
+    let target = match std::env::var("OXIDIZR_DEB_APT_VERSION") {
+        Ok(v) => format!("{}={}", pkg, v),
+        Err(_) => pkg.to_string(),
+    };
+    apt_get(
+        root,
+        &["install".to_string(), "-y".to_string(), target],
+        Event::PmInstall,
+        pkg,
+    )
+}
+
+/// `Package:` field of a .deb file, read natively so no dpkg-deb is needed on the host.
+pub fn deb_package_name(deb: &Path) -> Result<String, String> {
+    Deb::read(deb)?
+        .field("Package")
+        .filter(|n| !n.is_empty())
+        .ok_or_else(|| format!("{}: control file has no Package field", deb.display()))
 }
 
-fn apt_install(pkg: &str) -> Result<(), String> {
-    let (code, _so, se) = run("apt-get", &["update"])?;
-    eprintln!(
-        "{}",
-        serde_json::json!({
-            "event":"pm.update","pm":{"tool":"apt-get","args":["update"]},"exit_code":code,
-            "stderr_tail": se.chars().rev().take(400).collect::<String>().chars().rev().collect::<String>()
-        })
-    );
-    if code != 0 {
-        return Err("apt-get update failed".to_string());
+fn apt_install_deb(root: &Path, deb: &Path, package: &str) -> CliResult {
+    // apt-get only treats the argument as a file when it contains a slash.
+    let abs = fs::canonicalize(deb).map_err(|e| format!("{}: {e}", deb.display()))?;
+    // A chroot only sees files inside the root; stage the package there for this run
+    let staged =
+        if image::mode(root) == Some(ImageMode::Chroot) && image::apt_path(root, &abs).is_err() {
+            let dest = root.join("tmp").join(abs.file_name().unwrap_or_default());
+            fs::create_dir_all(root.join("tmp"))
+                .and_then(|_| fs::copy(&abs, &dest))
+                .map_err(|e| {
+                    format!(
+                        "failed to copy {} into {}: {e}",
+                        abs.display(),
+                        root.display()
+                    )
+                })?;
+            Some(dest)
+        } else {
+            None
+        };
+    let res = image::apt_path(root, staged.as_deref().unwrap_or(&abs))
+        .map_err(CliError::from)
+        .and_then(|p| {
+            apt_get(
+                root,
+                &["install".to_string(), "-y".to_string(), p],
+                Event::PmInstall,
+                package,
+            )
+        });
+    if let Some(dest) = staged {
+        let _ = fs::remove_file(dest);
     }
-    // Optional version pin via env
-    let pin = std::env::var("OXIDIZR_DEB_APT_VERSION").ok();
-    let mut install_args = vec!["install", "-y"];
-    let pinned_name;
-    if let Some(v) = pin.as_deref() {
-        pinned_name = format!("{}={}", pkg, v);
-        install_args.push(&pinned_name);
-    } else {
-        install_args.push(pkg);
+    res
+}
+
+/// Sources entry for a flat repository; unsigned repositories are trusted for this run only.
+/// The path is given as apt sees it from `root`.
+pub fn local_source_entry(root: &Path, dir: &Path) -> Result<String, String> {
+    let signed = dir.join("InRelease").exists() || dir.join("Release.gpg").exists();
+    let opts = if signed { "" } else { "[trusted=yes] " };
+    Ok(format!(
+        "deb {}file:{} ./",
+        opts,
+        image::apt_path(root, dir)?
+    ))
+}
+
+/// The temporary `file:` source for `--apt-repo`; removed again when dropped.
+struct LocalRepo {
+    path: PathBuf,
+    /// `path` as apt sees it.
+    apt_path: String,
+}
+
+impl LocalRepo {
+    fn add(root: &Path, dir: &Path) -> Result<Self, String> {
+        let dir = fs::canonicalize(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        if !["Packages", "Packages.gz", "Packages.xz"]
+            .iter()
+            .any(|f| dir.join(f).exists())
+        {
+            return Err(format!(
+                "{} is not a flat APT repository (no Packages index)",
+                dir.display()
+            ));
+        }
+        let entry = local_source_entry(root, &dir)?;
+        if entry.contains("trusted=yes") {
+            output::warning(&format!(
+                "{} has no signed Release file; trusting it for this run only",
+                dir.display()
+            ));
+        }
+        let path = root.join(LOCAL_SOURCE_REL);
+        fs::write(
+            &path,
+            format!("# Temporary source added by oxidizr-deb\n{entry}\n"),
+        )
+        .map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+        audit::emit(Event::PmSourceAdd(SourceAdd {
+            path: path.display().to_string(),
+            entry: entry.clone(),
+        }));
+        let apt_path = image::apt_path(root, &path)?;
+        Ok(LocalRepo { path, apt_path })
     }
-    let (code, _so, se) = run("apt-get", &install_args)?;
-    eprintln!(
-        "{}",
-        serde_json::json!({
-            "event":"pm.install","pm":{"tool":"apt-get","args":install_args,"package":pkg},"exit_code":code,
-            "stderr_tail": se.chars().rev().take(400).collect::<String>().chars().rev().collect::<String>()
-        })
-    );
-    if code != 0 {
-        return Err(format!("apt-get install {} failed", pkg));
+}
+
+impl Drop for LocalRepo {
+    fn drop(&mut self) {
+        let res = fs::remove_file(&self.path);
+        audit::emit(Event::PmSourceRemove(SourceRemove {
+            path: self.path.display().to_string(),
+            ok: res.is_ok(),
+        }));
     }
-    Ok(())
 }
 
-fn dpkg_locate_binary(pkgname: &str, candidates: &[&str]) -> Option<PathBuf> {
-    if let Ok(out) = Command::new("dpkg").args(["-L", pkgname]).output() {
-        let list = String::from_utf8_lossy(&out.stdout);
-        for line in list.lines() {
-            let p = Path::new(line.trim());
-            if p.is_file() {
-                let fname = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
-                if candidates.iter().any(|c| *c == fname) {
-                    return Some(p.to_path_buf());
-                }
-            }
+fn apt_install_from_repo(root: &Path, pkg: &str, dir: &Path) -> CliResult {
+    let repo = LocalRepo::add(root, dir)?;
+    // Refresh only the local source; keep the other lists so dependencies still resolve.
+    apt_get(
+        root,
+        &[
+            "update".to_string(),
+            "-o".to_string(),
+            format!("Dir::Etc::sourcelist={}", repo.apt_path),
+            "-o".to_string(),
+            "Dir::Etc::sourceparts=-".to_string(),
+            "-o".to_string(),
+            "APT::Get::List-Cleanup=0".to_string(),
+        ],
+        Event::PmUpdate,
+        pkg,
+    )?;
+    let target = match std::env::var("OXIDIZR_DEB_APT_VERSION") {
+        Ok(v) => format!("{}={}", pkg, v),
+        Err(_) => pkg.to_string(),
+    };
+    apt_get(
+        root,
+        &["install".to_string(), "-y".to_string(), target],
+        Event::PmInstall,
+        pkg,
+    )
+}
+
+/// The replacement binary among `pkgname`'s files in `root`, per its dpkg file list.
+fn dpkg_locate_binary(root: &Path, pkgname: &str, candidates: &[&str]) -> Option<PathBuf> {
+    dpkg::package_files(root, pkgname).iter().find_map(|line| {
+        let p = root.join(line.trim_start_matches('/'));
+        let fname = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
+        (p.is_file() && candidates.contains(&fname)).then_some(p)
+    })
+}
+
+/// Stage the replacement into a non-live root by unpacking its `.deb` (from `--deb` or an apt
+/// cache) without dpkg; returns the name of the package that was unpacked.
+fn unpack_into_root(root: &Path, pkg: Package, source: &Source) -> CliResult<String> {
+    let deb = match source {
+        Source::Deb(deb) => deb.clone(),
+        Source::Distro => find_cached_deb(root, apt_pkg_name(pkg)).ok_or_else(|| {
+            CliError::InvalidRoot(format!(
+                "no {} .deb in {} or /{}; pass --deb FILE (installing through apt requires --root=/ (live system) or --image-mode)",
+                apt_pkg_name(pkg),
+                root.join(APT_CACHE_REL).display(),
+                APT_CACHE_REL
+            ))
+        })?,
+        Source::AptRepo(_) => {
+            return Err(CliError::InvalidRoot(
+                "--apt-repo sources require --root=/ (live system) or --image-mode; use --deb to unpack a package into a root"
+                    .to_string(),
+            ))
         }
-    }
-    None
+        Source::Installed => return Ok(apt_pkg_name(pkg).to_string()),
+    };
+    Ok(install_deb(root, &deb)?)
 }
 
 // Online fallbacks removed; apt-only path is supported.
 
+/// Install the replacement from `source` and locate its binary; returns the binary and the
+/// name of the package that provides it. On a non-live root apt runs through the image mode
+/// when one is set; otherwise the `.deb` is unpacked into the root without apt.
 pub fn ensure_artifact_available(
     root: &Path,
     pkg: Package,
     commit: bool,
-) -> Result<PathBuf, String> {
-    let setuid = matches!(pkg, Package::Sudo);
-    let root_is_live = root == Path::new("/");
-
-    // 1) Try apt if in commit and on live root
-    if commit && root_is_live {
-        let apt_pkg = apt_pkg_name(pkg);
-        if let Err(e) = apt_install(apt_pkg) {
+    source: &Source,
+) -> CliResult<(PathBuf, String)> {
+    if !commit {
+        return Err(CliError::Failed(
+            "replacement retrieval requires apt on --root=/; online fallbacks are disabled"
+                .to_string(),
+        ));
+    }
+
+    let candidates: Vec<&str> = match pkg {
+        Package::Coreutils => vec!["uutils", "coreutils"],
+        Package::Findutils => vec!["uutils-findutils"],
+        Package::Sudo => vec!["sudo-rs", "sudo"],
+    };
+    let locate = |apt_pkg: String| {
+        dpkg_locate_binary(root, &apt_pkg, &candidates)
+            .map(|p| (p, apt_pkg.clone()))
+            .ok_or_else(|| {
+                CliError::Failed(format!(
+                    "{} is installed but provides none of: {}",
+                    apt_pkg,
+                    candidates.join(", ")
+                ))
+            })
+    };
+    if !image::apt_reachable(root) {
+        return locate(unpack_into_root(root, pkg, source)?);
+    }
+
+    let apt_pkg = match source {
+        Source::Deb(deb) => deb_package_name(deb)?,
+        _ => apt_pkg_name(pkg).to_string(),
+    };
+    let installed = match source {
+        Source::Distro => apt_install(root, &apt_pkg),
+        Source::Deb(deb) => apt_install_deb(root, deb, &apt_pkg),
+        Source::AptRepo(dir) => apt_install_from_repo(root, &apt_pkg, dir),
+        Source::Installed => Ok(()),
+    };
+    match installed {
+        Err(e) if matches!(source, Source::Distro) => {
             eprintln!("[info] apt path failed: {}", e);
-        } else {
-            // locate installed unified binary and use it directly (keeps updates via apt)
-            let candidates: Vec<&str> = match pkg {
-                Package::Coreutils => vec!["uutils", "coreutils"],
-                Package::Findutils => vec!["uutils-findutils"],
-                Package::Sudo => vec!["sudo-rs", "sudo"],
-            };
-            if let Some(installed) = dpkg_locate_binary(apt_pkg, &candidates) {
-                return Ok(installed);
-            }
+            Err(CliError::PackageManager(
+                "replacement retrieval requires apt on --root=/; online fallbacks are disabled"
+                    .to_string(),
+            ))
         }
+        Err(e) => Err(e),
+        // locate installed unified binary and use it directly (keeps updates via apt)
+        Ok(()) => locate(apt_pkg),
     }
-
-    Err("replacement retrieval requires apt on --root=/; online fallbacks are disabled".to_string())
 }
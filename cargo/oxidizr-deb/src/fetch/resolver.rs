@@ -14,6 +14,10 @@ pub fn resolve_artifact(
         } else if let Ok(e) = std::env::var("OXIDIZR_DEB_LOCAL_ARTIFACT") {
             PathBuf::from(e)
         } else {
+            let staged = staged_default_path(root, pkg);
+            if staged.exists() {
+                return staged;
+            }
             match pkg {
                 Package::Coreutils => PathBuf::from("/opt/uutils/uutils"),
                 Package::Findutils => PathBuf::from("/opt/uutils-findutils/uutils-findutils"),
@@ -41,14 +45,25 @@ pub fn resolve_artifact(
     }
 }
 
-pub fn staged_default_path(root: &Path, pkg: Package) -> PathBuf {
-    let (sub, bin) = match pkg {
+/// Where `import-bundle` stages replacement binaries, relative to the root.
+pub const STAGED_REL: &str = "opt/oxidizr/replacements";
+
+/// Family directory and binary name of a package inside the staged layout.
+pub fn staged_layout(pkg: Package) -> (&'static str, &'static str) {
+    match pkg {
         Package::Coreutils => ("uutils-coreutils", "uutils"),
         Package::Findutils => ("uutils-findutils", "uutils-findutils"),
         Package::Sudo => ("sudo-rs", "sudo-rs"),
-    };
-    root.join("opt/oxidizr/replacements")
-        .join(sub)
+    }
+}
+
+/// `opt/oxidizr/replacements/<family>`; its `bin` entry points at the current version's `bin`.
+pub fn staged_family_dir(root: &Path, pkg: Package) -> PathBuf {
+    root.join(STAGED_REL).join(staged_layout(pkg).0)
+}
+
+pub fn staged_default_path(root: &Path, pkg: Package) -> PathBuf {
+    staged_family_dir(root, pkg)
         .join("bin")
-        .join(bin)
+        .join(staged_layout(pkg).1)
 }
@@ -1,5 +1,25 @@
-// Placeholder: In future, enumerate upstream and distro sources and channels.
+use std::path::PathBuf;
+
+/// Where `use` installs the replacement package from on a live root.
+#[derive(Debug, Clone, Default)]
 pub enum Source {
-    Upstream,
+    /// The configured APT sources.
+    #[default]
     Distro,
+    /// A local `.deb` file, installed with `apt-get install /path/to/file.deb`.
+    Deb(PathBuf),
+    /// A local flat APT repository, added as a temporary `file:` source for this run only.
+    AptRepo(PathBuf),
+    /// Already installed by the caller (e.g. `replace`'s batched apt transaction); only locate it.
+    Installed,
+}
+
+impl Source {
+    pub fn from_args(deb: Option<PathBuf>, apt_repo: Option<PathBuf>) -> Self {
+        match (deb, apt_repo) {
+            (Some(d), _) => Source::Deb(d),
+            (None, Some(r)) => Source::AptRepo(r),
+            (None, None) => Source::Distro,
+        }
+    }
 }
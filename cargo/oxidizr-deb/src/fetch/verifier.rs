@@ -1,4 +1,192 @@
-// Placeholder: In future, compute and compare SHA-256 and signatures.
-pub fn verify_artifact(_path: &std::path::Path) -> bool {
-    true
+//! Verification of local (offline) replacement artifacts.
+//!
+//! An artifact `X` ships with `X.manifest.json` (name, version, arch and a sha256 per file) and a
+//! detached ed25519 signature over the manifest bytes in `X.manifest.json.sig`. Trusted public
+//! keys live under `etc/oxidizr-deb/keys/*.pub` inside the target root. Signature and key files
+//! are minisign-style: optional `untrusted comment:` lines followed by one base64 line.
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use base64::Engine as _;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::Deserialize;
+
+use crate::util::hash::sha256_file;
+
+pub const KEYS_REL: &str = "etc/oxidizr-deb/keys";
+pub const MANIFEST_SUFFIX: &str = ".manifest.json";
+pub const SIG_SUFFIX: &str = ".sig";
+
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    pub arch: String,
+    /// File name (relative to the manifest's directory) -> sha256 hex.
+    pub files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Verified {
+    pub sha256: String,
+    pub name: String,
+    pub version: String,
+    pub arch: String,
+    /// File name of the trusted key that signed the manifest.
+    pub key: String,
+}
+
+pub fn with_suffix(p: &Path, suffix: &str) -> PathBuf {
+    let mut s = p.as_os_str().to_owned();
+    s.push(suffix);
+    PathBuf::from(s)
+}
+
+/// Decode the payload line of a minisign-style file.
+fn payload(s: &str) -> Result<Vec<u8>, String> {
+    let line = s
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with("untrusted comment:") && !l.starts_with('#'))
+        .ok_or("empty key/signature file")?;
+    base64::engine::general_purpose::STANDARD
+        .decode(line)
+        .map_err(|e| format!("invalid base64: {e}"))
+}
+
+fn trusted_keys(root: &Path) -> Result<Vec<(String, VerifyingKey)>, String> {
+    let dir = root.join(KEYS_REL);
+    let entries = match fs::read_dir(&dir) {
+        Ok(e) => e,
+        Err(_) => return Ok(vec![]),
+    };
+    let mut keys = Vec::new();
+    for e in entries.filter_map(|e| e.ok()) {
+        let p = e.path();
+        if p.extension().and_then(|x| x.to_str()) != Some("pub") {
+            continue;
+        }
+        let s =
+            fs::read_to_string(&p).map_err(|e| format!("failed to read {}: {e}", p.display()))?;
+        let bytes: [u8; 32] = payload(&s)
+            .map_err(|e| format!("{}: {e}", p.display()))?
+            .try_into()
+            .map_err(|_| format!("{}: ed25519 public key must be 32 bytes", p.display()))?;
+        let key = VerifyingKey::from_bytes(&bytes)
+            .map_err(|e| format!("{}: invalid ed25519 public key: {e}", p.display()))?;
+        keys.push((e.file_name().to_string_lossy().to_string(), key));
+    }
+    keys.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(keys)
+}
+
+/// Debian architecture of the target root.
+pub fn host_arch(root: &Path) -> String {
+    if root == Path::new("/") {
+        if let Ok(out) = std::process::Command::new("dpkg")
+            .arg("--print-architecture")
+            .output()
+        {
+            let s = String::from_utf8_lossy(&out.stdout).trim().to_string();
+            if out.status.success() && !s.is_empty() {
+                return s;
+            }
+        }
+    }
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "i386",
+        "arm" => "armhf",
+        "powerpc64" => "ppc64el",
+        "riscv64" => "riscv64",
+        "s390x" => "s390x",
+        other => other,
+    }
+    .to_string()
+}
+
+/// Check `manifest_bytes` against a minisign-style detached signature and the trusted keys under
+/// `root`, then check the manifest's architecture. Returns the manifest and the signing key's file name.
+pub fn verify_manifest(
+    root: &Path,
+    manifest_bytes: &[u8],
+    sig_text: &str,
+) -> Result<(Manifest, String), String> {
+    let sig_bytes: [u8; 64] = payload(sig_text)
+        .map_err(|e| format!("signature: {e}"))?
+        .try_into()
+        .map_err(|_| "ed25519 signature must be 64 bytes".to_string())?;
+    let sig = Signature::from_bytes(&sig_bytes);
+
+    let keys = trusted_keys(root)?;
+    if keys.is_empty() {
+        return Err(format!(
+            "no trusted keys under {}",
+            root.join(KEYS_REL).display()
+        ));
+    }
+    let key = keys
+        .iter()
+        .find(|(_, k)| k.verify(manifest_bytes, &sig).is_ok())
+        .map(|(name, _)| name.clone())
+        .ok_or_else(|| "manifest signature does not match any trusted key".to_string())?;
+
+    let manifest: Manifest =
+        serde_json::from_slice(manifest_bytes).map_err(|e| format!("invalid manifest: {e}"))?;
+    let arch = host_arch(root);
+    if manifest.arch != "all" && manifest.arch != arch {
+        return Err(format!(
+            "manifest is for {}, target root is {}",
+            manifest.arch, arch
+        ));
+    }
+    Ok((manifest, key))
+}
+
+/// Verify `path` against its signed manifest and the trusted keys configured under `root`. The
+/// manifest must name `expected` (the family being installed, e.g. `coreutils`), so a validly
+/// signed artifact for another family cannot be linked in its place.
+pub fn verify_artifact(root: &Path, path: &Path, expected: &str) -> Result<Verified, String> {
+    let fail = |msg: String| format!("artifact verification failed for {}: {msg}", path.display());
+    if !path.exists() {
+        return Err(fail("artifact does not exist".to_string()));
+    }
+    let manifest_path = with_suffix(path, MANIFEST_SUFFIX);
+    let sig_path = with_suffix(&manifest_path, SIG_SUFFIX);
+    let manifest_bytes = fs::read(&manifest_path)
+        .map_err(|e| fail(format!("no manifest at {}: {e}", manifest_path.display())))?;
+    let sig_text = fs::read_to_string(&sig_path)
+        .map_err(|e| fail(format!("no signature at {}: {e}", sig_path.display())))?;
+    let (manifest, key) = verify_manifest(root, &manifest_bytes, &sig_text).map_err(fail)?;
+    if manifest.name != expected {
+        return Err(fail(format!(
+            "manifest is for {}, not {}",
+            manifest.name, expected
+        )));
+    }
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let expected = manifest
+        .files
+        .get(&file_name)
+        .ok_or_else(|| fail(format!("manifest does not list {}", file_name)))?;
+    let actual = sha256_file(path).map_err(|e| fail(format!("failed to hash: {e}")))?;
+    if !expected.eq_ignore_ascii_case(&actual) {
+        return Err(fail(format!(
+            "sha256 mismatch: manifest {}, file {}",
+            expected, actual
+        )));
+    }
+    Ok(Verified {
+        sha256: actual,
+        name: manifest.name,
+        version: manifest.version,
+        arch: manifest.arch,
+        key,
+    })
 }
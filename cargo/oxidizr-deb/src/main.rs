@@ -3,15 +3,30 @@ mod cli;
 mod commands;
 mod errors;
 mod fetch;
+mod output;
 mod packages;
+mod plan;
+mod state;
 mod util;
 
 use clap::Parser;
 
 fn main() {
     let cli = crate::cli::args::Cli::parse();
-    if let Err(e) = crate::cli::handler::dispatch(cli) {
-        eprintln!("error: {e}");
-        std::process::exit(1);
+    let json = cli.command.json();
+    if cli.output == crate::cli::args::OutputFormat::Json {
+        let mode = if cli.commit {
+            switchyard::types::ApplyMode::Commit
+        } else {
+            switchyard::types::ApplyMode::DryRun
+        };
+        crate::output::begin(cli.command.name(), &cli.root, mode);
+    }
+    let result = crate::cli::handler::dispatch(cli);
+    // The result document carries the error object in place of `--json`'s own
+    let documented = crate::output::finish(result.as_ref().err());
+    if let Err(e) = result {
+        crate::util::diagnostics::report(&e, json && !documented);
+        std::process::exit(e.exit_code());
     }
 }
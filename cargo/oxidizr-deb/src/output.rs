@@ -0,0 +1,241 @@
+//! `--output json`: instead of free-form output, the run prints one result document
+//! (`oxidizr_cli_core::report::RunReport`) on stdout. Progress and events stay on stderr.
+//!
+//! Commands record their Switchyard plans and other actions and the apt transactions a dry run
+//! would perform; executed apt runs and skipped applets arrive as events through `audit::emit`.
+//! Package changes are detected by comparing each root's dpkg status before and after the run.
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use oxidizr_cli_core::events::Event;
+use oxidizr_cli_core::report::{Action, AptRecord, RunReport};
+use serde::Serialize;
+use switchyard::types::plan::Action as SwitchyardAction;
+use switchyard::types::ApplyMode;
+
+use crate::adapters::debfile::Deb;
+use crate::adapters::dpkg;
+use crate::errors::CliError;
+use crate::util::hash::sha256_file;
+
+struct Collector {
+    report: RunReport,
+    /// `var/lib/dpkg/status` digest of each root the run touched, taken before it did.
+    dpkg_status: Vec<(PathBuf, Option<String>)>,
+}
+
+static REPORT: Mutex<Option<Collector>> = Mutex::new(None);
+
+fn with(f: impl FnOnce(&mut Collector)) {
+    if let Ok(mut g) = REPORT.lock() {
+        if let Some(c) = g.as_mut() {
+            f(c);
+        }
+    }
+}
+
+fn dpkg_status(root: &Path) -> Option<String> {
+    sha256_file(&root.join("var/lib/dpkg/status")).ok()
+}
+
+/// Start collecting the result document of `command`.
+pub fn begin(command: &str, root: &Path, mode: ApplyMode) {
+    if let Ok(mut g) = REPORT.lock() {
+        *g = Some(Collector {
+            report: RunReport::new(
+                command,
+                &root.display().to_string(),
+                matches!(mode, ApplyMode::DryRun),
+            ),
+            dpkg_status: vec![],
+        });
+    }
+}
+
+/// Whether this run prints a result document.
+pub fn active() -> bool {
+    REPORT.lock().map(|g| g.is_some()).unwrap_or(false)
+}
+
+/// Note the dpkg status of a root the run operates on (once per root).
+pub fn watch(root: &Path) {
+    with(|c| {
+        if !c.dpkg_status.iter().any(|(r, _)| r == root) {
+            c.dpkg_status.push((root.to_path_buf(), dpkg_status(root)));
+        }
+    });
+}
+
+pub fn observe(event: &Event) {
+    with(|c| c.report.observe(event));
+}
+
+/// An action the command takes itself: planned, and executed as well when committing.
+pub fn action(mode: ApplyMode, action: Action) {
+    with(|c| {
+        if matches!(mode, ApplyMode::Commit) {
+            c.report.executed_actions.push(action.clone());
+        }
+        c.report.planned_actions.push(action);
+    });
+}
+
+fn describe(action: &SwitchyardAction) -> Action {
+    match action {
+        SwitchyardAction::EnsureSymlink { source, target } => {
+            let (source, target) = (source.as_path(), target.as_path());
+            let in_place = std::fs::symlink_metadata(&target)
+                .map(|m| m.file_type().is_symlink())
+                .unwrap_or(false)
+                && std::fs::canonicalize(&target).ok() == std::fs::canonicalize(&source).ok();
+            Action::Link {
+                source: source.display().to_string(),
+                target: target.display().to_string(),
+                changes: !in_place,
+            }
+        }
+        SwitchyardAction::RestoreFromBackup { target } => {
+            let target = target.as_path();
+            // A restore undoes a link; a target that is no symlink already holds the original
+            let linked = std::fs::symlink_metadata(&target)
+                .map(|m| m.file_type().is_symlink())
+                .unwrap_or(false);
+            Action::Restore {
+                target: target.display().to_string(),
+                changes: linked,
+            }
+        }
+    }
+}
+
+/// A Switchyard plan; call before `apply`, while the targets are unchanged.
+pub fn planned(actions: &[SwitchyardAction]) {
+    with(|c| {
+        c.report
+            .planned_actions
+            .extend(actions.iter().map(describe))
+    });
+}
+
+/// The actions Switchyard executed when committing a plan recorded with `planned`.
+pub fn executed(mode: ApplyMode, actions: &[SwitchyardAction]) {
+    if matches!(mode, ApplyMode::DryRun) {
+        return;
+    }
+    with(|c| {
+        for a in actions {
+            let now = describe(a);
+            // Whether it changed anything was decided before it ran
+            let before = c
+                .report
+                .planned_actions
+                .iter()
+                .rev()
+                .find(|p| match (p, &now) {
+                    (Action::Link { target: t1, .. }, Action::Link { target: t2, .. })
+                    | (Action::Restore { target: t1, .. }, Action::Restore { target: t2, .. }) => {
+                        t1 == t2
+                    }
+                    _ => false,
+                });
+            c.report
+                .executed_actions
+                .push(before.cloned().unwrap_or(now));
+        }
+    });
+}
+
+/// An apt transaction a dry run would perform. Steps that would not change `root` (installs
+/// already at the requested version, purges of packages that are not installed) are left out,
+/// so a dry run after a commit reports no change.
+pub fn planned_apt(root: &Path, tool: &str, install: &[String], purge: &[String]) {
+    if !active() {
+        return;
+    }
+    let install: Vec<String> = install
+        .iter()
+        .filter(|i| !install_is_noop(root, i))
+        .cloned()
+        .collect();
+    let purge: Vec<String> = purge
+        .iter()
+        .filter(|p| dpkg::is_installed(root, p))
+        .cloned()
+        .collect();
+    if install.is_empty() && purge.is_empty() {
+        return;
+    }
+    with(|c| {
+        c.report
+            .apt_transactions
+            .push(AptRecord::planned(tool, &install, &purge))
+    });
+}
+
+/// `name`, `name=version` or a `.deb` path whose package is already installed at that version.
+fn install_is_noop(root: &Path, item: &str) -> bool {
+    if item.contains('/') {
+        // As given, or as apt sees it from inside a chroot root
+        let path = Path::new(item);
+        let deb = if path.exists() {
+            Deb::read(path)
+        } else {
+            Deb::read(&root.join(path.strip_prefix("/").unwrap_or(path)))
+        };
+        return deb.is_ok_and(|d| match (d.field("Package"), d.field("Version")) {
+            (Some(name), version) => {
+                dpkg::installed_version(root, &name).is_some_and(|v| Some(v) == version)
+            }
+            _ => false,
+        });
+    }
+    match item.split_once('=') {
+        Some((name, version)) => dpkg::installed_version(root, name).as_deref() == Some(version),
+        None => dpkg::is_installed(root, item),
+    }
+}
+
+/// Print a warning and keep it for the result document.
+pub fn warning(msg: &str) {
+    eprintln!("[warn] {msg}");
+    with(|c| c.report.warnings.push(msg.to_string()));
+}
+
+/// Print a command's JSON payload, or keep it as the result document's `data`.
+pub fn print_json<T: Serialize>(payload: &T, pretty: bool) -> Result<(), String> {
+    if active() {
+        let value = serde_json::to_value(payload).map_err(|e| e.to_string())?;
+        with(|c| c.report.data = Some(value));
+        return Ok(());
+    }
+    let text = if pretty {
+        serde_json::to_string_pretty(payload)
+    } else {
+        serde_json::to_string(payload)
+    };
+    println!("{}", text.map_err(|e| e.to_string())?);
+    Ok(())
+}
+
+/// Print the result document, if one is being collected; `true` when it was.
+pub fn finish(error: Option<&CliError>) -> bool {
+    let collector = REPORT.lock().ok().and_then(|mut g| g.take());
+    let Some(Collector {
+        mut report,
+        dpkg_status: watched,
+    }) = collector
+    else {
+        return false;
+    };
+    let packages_changed = watched
+        .iter()
+        .any(|(root, before)| dpkg_status(root) != *before);
+    report.ok = error.is_none();
+    report.error = error.map(CliError::to_json);
+    report.changed = report.changed(packages_changed);
+    match serde_json::to_string_pretty(&report) {
+        Ok(doc) => println!("{doc}"),
+        Err(e) => eprintln!("[warn] failed to serialize the result document: {e}"),
+    }
+    true
+}
@@ -2,4 +2,25 @@ pub mod coreutils;
 pub mod findutils;
 pub mod sudo;
 
+use oxidizr_cli_core::PackageKind;
+
+use crate::cli::args::Package;
+
 pub const DEST_DIR: &str = "/usr/bin";
+
+pub fn kind_of(pkg: Package) -> PackageKind {
+    match pkg {
+        Package::Coreutils => PackageKind::Coreutils,
+        Package::Findutils => PackageKind::Findutils,
+        Package::Sudo => PackageKind::Sudo,
+    }
+}
+
+/// Debian package name of the GNU/stock implementation.
+pub fn distro_pkg_name(pkg: Package) -> &'static str {
+    match pkg {
+        Package::Coreutils => "coreutils",
+        Package::Findutils => "findutils",
+        Package::Sudo => "sudo",
+    }
+}
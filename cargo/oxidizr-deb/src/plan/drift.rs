@@ -0,0 +1,67 @@
+use std::fs;
+use std::path::Path;
+
+use crate::plan::model::{PathState, Plan};
+use crate::util::hash::sha256_file;
+
+pub fn observe(path: &Path) -> PathState {
+    match fs::symlink_metadata(path) {
+        Err(_) => PathState::Missing,
+        Ok(md) if md.file_type().is_symlink() => match fs::read_link(path) {
+            Ok(target) => PathState::Symlink { target },
+            Err(_) => PathState::Other,
+        },
+        Ok(md) if md.file_type().is_file() => match sha256_file(path) {
+            Ok(sha256) => PathState::File { sha256 },
+            Err(_) => PathState::Other,
+        },
+        Ok(_) => PathState::Other,
+    }
+}
+
+/// Compare the live tree against what the plan observed. Returns one line per drifted path.
+pub fn detect(plan: &Plan) -> Vec<String> {
+    let mut out = Vec::new();
+    for pp in &plan.packages {
+        if let Some(a) = &pp.artifact {
+            if let Some(expected) = &a.sha256 {
+                match sha256_file(&a.path) {
+                    Ok(actual) if &actual == expected => {}
+                    Ok(actual) => out.push(format!(
+                        "{}: artifact sha256 changed ({} -> {})",
+                        a.path.display(),
+                        expected,
+                        actual
+                    )),
+                    Err(_) => out.push(format!("{}: artifact no longer present", a.path.display())),
+                }
+            }
+        }
+        let targets = pp
+            .links
+            .iter()
+            .map(|l| (&l.target, &l.observed))
+            .chain(pp.restores.iter().map(|r| (&r.target, &r.observed)));
+        for (target, expected) in targets {
+            let now = observe(target);
+            if &now != expected {
+                out.push(format!(
+                    "{}: was {}, now {}",
+                    target.display(),
+                    describe(expected),
+                    describe(&now)
+                ));
+            }
+        }
+    }
+    out
+}
+
+fn describe(s: &PathState) -> String {
+    match s {
+        PathState::Missing => "missing".to_string(),
+        PathState::File { sha256 } => format!("file sha256={}", sha256),
+        PathState::Symlink { target } => format!("symlink -> {}", target.display()),
+        PathState::Other => "non-regular file".to_string(),
+    }
+}
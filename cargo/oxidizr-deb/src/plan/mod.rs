@@ -0,0 +1,7 @@
+//! Reviewable plan files: `oxidizr-deb plan` writes them, `oxidizr-deb apply --plan` executes them.
+pub mod drift;
+pub mod model;
+
+pub use model::{
+    AptAction, AptStep, Artifact, LinkAction, Operation, PackagePlan, Plan, PlanFile, RestoreAction,
+};
@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli::args::Package;
+use crate::util::hash::sha256_bytes;
+
+pub const PLAN_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Use,
+    Restore,
+    Replace,
+}
+
+/// What was found at a path when the plan was made. Compared again at apply time.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PathState {
+    Missing,
+    File { sha256: String },
+    Symlink { target: PathBuf },
+    Other,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub path: PathBuf,
+    /// None when the artifact does not exist yet (it will be installed by an apt step).
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LinkAction {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub observed: PathState,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RestoreAction {
+    pub target: PathBuf,
+    pub observed: PathState,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AptAction {
+    Install,
+    Purge,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AptStep {
+    pub action: AptAction,
+    pub package: String,
+    pub version: Option<String>,
+}
+
+impl AptStep {
+    pub fn install(package: &str, version: Option<String>) -> Self {
+        AptStep {
+            action: AptAction::Install,
+            package: package.to_string(),
+            version,
+        }
+    }
+
+    pub fn purge(package: &str) -> Self {
+        AptStep {
+            action: AptAction::Purge,
+            package: package.to_string(),
+            version: None,
+        }
+    }
+
+    /// The package as apt-get takes it, e.g. `rust-coreutils=0.1-1`.
+    pub fn target(&self) -> String {
+        match &self.version {
+            Some(v) => format!("{}={}", self.package, v),
+            None => self.package.clone(),
+        }
+    }
+
+    /// apt-get arguments for this step, e.g. `["install", "-y", "rust-coreutils=0.1-1"]`.
+    pub fn args(&self) -> Vec<String> {
+        let verb = match self.action {
+            AptAction::Install => "install",
+            AptAction::Purge => "purge",
+        };
+        vec![verb.to_string(), "-y".to_string(), self.target()]
+    }
+}
+
+/// Everything oxidizr-deb will do for one package family. APT installs run before the
+/// Switchyard actions, purges after them.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PackagePlan {
+    pub package: Package,
+    pub applets: Vec<String>,
+    pub artifact: Option<Artifact>,
+    pub links: Vec<LinkAction>,
+    pub restores: Vec<RestoreAction>,
+    pub apt: Vec<AptStep>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    pub operation: Operation,
+    pub root: PathBuf,
+    pub created_at: u64,
+    pub packages: Vec<PackagePlan>,
+    /// `replace` only: purging Essential packages was confirmed (`--confirm-essential-removal`).
+    /// Left out when false.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub confirm_essential_removal: bool,
+}
+
+impl Plan {
+    pub fn new(operation: Operation, root: &Path, packages: Vec<PackagePlan>) -> Self {
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Plan {
+            operation,
+            root: root.to_path_buf(),
+            created_at,
+            packages,
+            confirm_essential_removal: false,
+        }
+    }
+
+    /// Approval hash: sha256 of the plan's operation, root, packages and Essential confirmation,
+    /// encoded as compact JSON with object keys sorted. `created_at` is left out, so planning the
+    /// same change twice gives the same hash, and reordering struct fields does not change it.
+    pub fn hash(&self) -> String {
+        #[derive(Serialize)]
+        struct Hashed<'a> {
+            operation: Operation,
+            root: &'a Path,
+            packages: &'a [PackagePlan],
+            #[serde(skip_serializing_if = "std::ops::Not::not")]
+            confirm_essential_removal: bool,
+        }
+        let value = serde_json::to_value(Hashed {
+            operation: self.operation,
+            root: &self.root,
+            packages: &self.packages,
+            confirm_essential_removal: self.confirm_essential_removal,
+        })
+        .expect("plan serializes");
+        let mut bytes = Vec::new();
+        write_canonical(&value, &mut bytes);
+        sha256_bytes(&bytes)
+    }
+}
+
+/// Compact JSON with object keys in byte order, independent of struct field order and of
+/// serde_json's map ordering.
+fn write_canonical(value: &serde_json::Value, out: &mut Vec<u8>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push(b'{');
+            for (i, k) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                out.extend(serde_json::to_vec(k).expect("key serializes"));
+                out.push(b':');
+                write_canonical(&map[k], out);
+            }
+            out.push(b'}');
+        }
+        serde_json::Value::Array(items) => {
+            out.push(b'[');
+            for (i, v) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical(v, out);
+            }
+            out.push(b']');
+        }
+        scalar => out.extend(serde_json::to_vec(scalar).expect("value serializes")),
+    }
+}
+
+/// On-disk envelope: the plan plus the hash it was written with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanFile {
+    pub schema_version: u32,
+    pub hash: String,
+    pub plan: Plan,
+}
+
+impl PlanFile {
+    pub fn new(plan: Plan) -> Self {
+        PlanFile {
+            schema_version: PLAN_SCHEMA_VERSION,
+            hash: plan.hash(),
+            plan,
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let body = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+            }
+        }
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, body.as_bytes())
+            .map_err(|e| format!("failed to write {}: {e}", tmp.display()))?;
+        std::fs::rename(&tmp, path).map_err(|e| format!("failed to write {}: {e}", path.display()))
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let s = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read plan {}: {e}", path.display()))?;
+        let file: PlanFile = serde_json::from_str(&s)
+            .map_err(|e| format!("invalid plan file {}: {e}", path.display()))?;
+        if file.schema_version != PLAN_SCHEMA_VERSION {
+            return Err(format!(
+                "unsupported plan schema_version {} (expected {})",
+                file.schema_version, PLAN_SCHEMA_VERSION
+            ));
+        }
+        Ok(file)
+    }
+
+    /// Check that the plan body still hashes to its recorded hash and to the approved one.
+    pub fn verify(&self, expect_hash: &str) -> Result<(), String> {
+        let actual = self.plan.hash();
+        if actual != self.hash {
+            return Err(format!(
+                "plan file was modified after it was written (recorded hash {}, content hash {})",
+                self.hash, actual
+            ));
+        }
+        if !expect_hash.eq_ignore_ascii_case(&self.hash) {
+            return Err(format!(
+                "plan hash mismatch: expected {}, plan has {}",
+                expect_hash, self.hash
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+//! Copies of the GNU `.deb`s that `replace` purged, kept at `var/lib/oxidizr-deb/archive` so
+//! `undo` can reinstall the exact prior versions without network access.
+//!
+//! The `.deb` comes from apt's cache when it is still there; otherwise it is rebuilt from the
+//! installed files, the dpkg status stanza and the maintainer scripts in `var/lib/dpkg/info`,
+//! the way `dpkg-repack` does.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::adapters::{debfile, dpkg};
+
+pub const ARCHIVE_REL: &str = "var/lib/oxidizr-deb/archive";
+pub const APT_CACHE_REL: &str = "var/cache/apt/archives";
+
+/// Control-archive members carried over from `var/lib/dpkg/info`, with their modes.
+const CONTROL_MEMBERS: &[(&str, u32)] = &[
+    ("conffiles", 0o644),
+    ("md5sums", 0o644),
+    ("shlibs", 0o644),
+    ("symbols", 0o644),
+    ("triggers", 0o644),
+    ("preinst", 0o755),
+    ("postinst", 0o755),
+    ("prerm", 0o755),
+    ("postrm", 0o755),
+    ("config", 0o755),
+];
+
+/// Where the archived copy of an installed package is found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    AptCache(PathBuf),
+    Repack,
+}
+
+/// `<name>_<version>_<arch>.deb`, with the epoch colon escaped the way apt's cache does.
+pub fn deb_file_name(name: &str, version: &str, arch: &str) -> String {
+    format!("{}_{}_{}.deb", name, version.replace(':', "%3a"), arch)
+}
+
+fn installed(root: &Path, name: &str) -> Result<(String, String), String> {
+    let db = dpkg::StatusDb::load(root)?;
+    let p = db
+        .get(name)
+        .filter(|p| p.is_installed())
+        .ok_or_else(|| format!("{name} is not installed; nothing to archive"))?;
+    Ok((
+        p.version.clone().unwrap_or_default(),
+        p.architecture.clone().unwrap_or_else(|| "all".to_string()),
+    ))
+}
+
+/// Archive destination for the installed `name`, and where its contents would come from.
+pub fn locate(root: &Path, name: &str) -> Result<(PathBuf, Origin), String> {
+    let (version, arch) = installed(root, name)?;
+    let file = deb_file_name(name, &version, &arch);
+    let cached = root.join(APT_CACHE_REL).join(&file);
+    let origin = if cached.is_file() {
+        Origin::AptCache(cached)
+    } else {
+        Origin::Repack
+    };
+    Ok((root.join(ARCHIVE_REL).join(file), origin))
+}
+
+/// Rebuild the installed package `name`. Refuses when a file dpkg installed as a regular file
+/// (listed in `md5sums`) is no longer one, e.g. because it was already switched to a replacement.
+fn repack(root: &Path, name: &str) -> Result<Vec<u8>, String> {
+    let control = dpkg::control_stanza(root, name)
+        .ok_or_else(|| format!("{name}: no installed stanza in the dpkg status database"))?;
+    let mut members: Vec<(&str, Vec<u8>, u32)> = vec![("control", control.into_bytes(), 0o644)];
+    for (member, mode) in CONTROL_MEMBERS {
+        if let Some(data) = dpkg::info_file(root, name, member) {
+            members.push((member, data, *mode));
+        }
+    }
+
+    if let Some(md5sums) = dpkg::info_file(root, name, "md5sums") {
+        let md5sums = String::from_utf8_lossy(&md5sums);
+        let changed: Vec<&str> = md5sums
+            .lines()
+            .filter_map(|l| l.split_once("  ").map(|(_, p)| p.trim()))
+            .filter(|p| {
+                !root
+                    .join(p)
+                    .symlink_metadata()
+                    .map(|m| m.file_type().is_file())
+                    .unwrap_or(false)
+            })
+            .collect();
+        if !changed.is_empty() {
+            return Err(format!(
+                "cannot rebuild {name} from installed files; no longer regular files: /{} \
+                 (place {name}'s .deb in /{APT_CACHE_REL} and retry)",
+                changed.join(", /")
+            ));
+        }
+    }
+
+    let control_tar = debfile::tar_of(
+        &members
+            .iter()
+            .map(|(n, d, m)| (*n, d.as_slice(), *m))
+            .collect::<Vec<_>>(),
+    )?;
+    let data_tar = debfile::tar_of_paths(root, &dpkg::package_files(root, name))?;
+    Ok(debfile::build(&control_tar, &data_tar))
+}
+
+/// Copy (or rebuild) the installed `.deb` of `name` into the archive and return its path.
+/// An existing archived copy of the same version is kept.
+pub fn archive_package(root: &Path, name: &str) -> Result<PathBuf, String> {
+    let (dest, origin) = locate(root, name)?;
+    if dest.is_file() {
+        return Ok(dest);
+    }
+    let data = match &origin {
+        Origin::AptCache(p) => {
+            fs::read(p).map_err(|e| format!("failed to read {}: {e}", p.display()))?
+        }
+        Origin::Repack => repack(root, name)?,
+    };
+    let dir = root.join(ARCHIVE_REL);
+    fs::create_dir_all(&dir).map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+    let tmp = dest.with_extension("deb.tmp");
+    fs::write(&tmp, data).map_err(|e| format!("failed to write {}: {e}", tmp.display()))?;
+    fs::rename(&tmp, &dest).map_err(|e| format!("failed to write {}: {e}", dest.display()))?;
+    Ok(dest)
+}
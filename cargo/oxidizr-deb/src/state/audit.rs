@@ -0,0 +1,123 @@
+//! The audit trail of committed runs at `var/log/oxidizr-deb/audit.jsonl`, a hash-chained log (see
+//! `oxidizr_cli_core::audit`). Structured events go to stderr, or to journald with
+//! `--log-sink journald`; while a committed run has the log open, they, the run's start and finish
+//! and Switchyard's facts are also appended to it.
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use oxidizr_cli_core::audit::{AuditLog, Invoker};
+use oxidizr_cli_core::events::{Event, RunPhase};
+use oxidizr_cli_core::journald::JournalSink;
+
+use crate::cli::args::LogSink;
+use crate::output;
+
+pub const AUDIT_REL: &str = "var/log/oxidizr-deb/audit.jsonl";
+pub const LOG_SINK_ENV: &str = "OXIDIZR_DEB_LOG_SINK";
+/// Overrides the journald socket (tests point it at a local datagram socket).
+pub const JOURNAL_SOCKET_ENV: &str = "OXIDIZR_DEB_JOURNAL_SOCKET";
+pub const JOURNAL_IDENTIFIER: &str = "oxidizr-deb";
+
+static LOG: Mutex<Option<AuditLog>> = Mutex::new(None);
+/// Last warning printed, so an unreachable journal is reported once rather than per event.
+static LAST_WARNING: Mutex<Option<String>> = Mutex::new(None);
+
+fn warn(e: &str) {
+    if let Ok(mut last) = LAST_WARNING.lock() {
+        if last.as_deref() != Some(e) {
+            output::warning(&format!("audit: {e}"));
+            *last = Some(e.to_string());
+        }
+    }
+}
+
+pub fn path(root: &Path) -> PathBuf {
+    root.join(AUDIT_REL)
+}
+
+impl LogSink {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogSink::Stderr => "stderr",
+            LogSink::Journald => "journald",
+        }
+    }
+}
+
+fn journal() -> Option<JournalSink> {
+    if std::env::var(LOG_SINK_ENV).ok()? != LogSink::Journald.as_str() {
+        return None;
+    }
+    let sink = JournalSink::new(JOURNAL_IDENTIFIER);
+    Some(match std::env::var_os(JOURNAL_SOCKET_ENV) {
+        Some(p) => sink.with_socket(Path::new(&p)),
+        None => sink,
+    })
+}
+
+/// Set up this run's log: the audit file when `audited` (a committed mutating run), plus
+/// journald when selected. `emit` writes to it until `close`.
+pub fn open(root: &Path, audited: bool, operation_id: Option<u64>) -> Result<AuditLog, String> {
+    let log = if audited {
+        AuditLog::open(&path(root), Invoker::current(), operation_id)?
+    } else {
+        AuditLog::disabled()
+    };
+    let log = match journal() {
+        Some(j) => log.with_journal(j),
+        None => log,
+    };
+    if let Ok(mut g) = LOG.lock() {
+        *g = Some(log.clone());
+    }
+    Ok(log)
+}
+
+pub fn close() {
+    if let Ok(mut g) = LOG.lock() {
+        *g = None;
+    }
+}
+
+/// Send a structured event to the selected sink (stderr or journald) and append it to the open
+/// audit log, if any. Events journald did not take are printed to stderr instead.
+pub fn emit(event: Event) {
+    output::observe(&event);
+    let name = event.name();
+    let event = event.to_value();
+    let log = LOG.lock().ok().and_then(|g| g.clone());
+    let Some(log) = log else {
+        eprintln!("{}", event);
+        return;
+    };
+    let journaled = log.has_journal();
+    if !journaled {
+        eprintln!("{}", event);
+    }
+    if let Err(e) = log.record(&name, event.clone()) {
+        warn(&e);
+        if journaled {
+            eprintln!("{}", event);
+        }
+    }
+}
+
+/// Record the start (no `result` yet) or end of a committed run.
+pub fn record_run(command: &str, root: &Path, result: Option<&Result<(), String>>) {
+    let log = LOG.lock().ok().and_then(|g| g.clone());
+    if let Some(log) = log {
+        let phase = RunPhase {
+            command: command.to_string(),
+            root: root.display().to_string(),
+            ok: result.map(|r| r.is_ok()),
+            error: result.and_then(|r| r.as_ref().err().cloned()),
+        };
+        let event = match result {
+            None => Event::RunStart(phase),
+            Some(_) => Event::RunFinish(phase),
+        };
+        if let Err(e) = log.record(&event.name(), event.to_value()) {
+            warn(&e);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Append-only operation history at `var/lib/oxidizr-deb/history.jsonl`: one JSON line per
+//! mutating run, with the exact package versions before and after it. `undo` reads it back.
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::adapters::dpkg;
+use crate::adapters::provider_deb::shim_name;
+use crate::cli::args::Package;
+use crate::fetch::fallback::apt_pkg_name;
+use crate::packages::distro_pkg_name;
+use crate::state::archive::{deb_file_name, ARCHIVE_REL};
+
+pub const HISTORY_REL: &str = "var/lib/oxidizr-deb/history.jsonl";
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub at: u64,
+    /// Command that ran: `use`, `replace`, `restore`, `repair`, `apply`, `undo`, ...
+    pub operation: String,
+    pub packages: Vec<Package>,
+    /// Installed versions of the involved distro, replacement and shim packages (absent when
+    /// not installed), before and after the run.
+    pub versions_before: BTreeMap<String, String>,
+    pub versions_after: BTreeMap<String, String>,
+    /// Hash of the plan this run executed (see `oxidizr-deb plan`), when there is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan_hash: Option<String>,
+    /// Archived `.deb`s (paths inside the root) of packages this run removed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archived: Vec<PathBuf>,
+    /// For `undo`: the entry it reverted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undoes: Option<u64>,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub fn path(root: &Path) -> PathBuf {
+    root.join(HISTORY_REL)
+}
+
+/// All entries, oldest first; no history file means no entries.
+pub fn load(root: &Path) -> Result<Vec<HistoryEntry>, String> {
+    let p = path(root);
+    let s = match std::fs::read_to_string(&p) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("failed to read history {}: {e}", p.display())),
+    };
+    s.lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| {
+            serde_json::from_str(l)
+                .map_err(|e| format!("invalid history {} line {}: {e}", p.display(), i + 1))
+        })
+        .collect()
+}
+
+fn package_names(packages: &[Package]) -> Vec<String> {
+    packages
+        .iter()
+        .flat_map(|p| {
+            [
+                distro_pkg_name(*p).to_string(),
+                apt_pkg_name(*p).to_string(),
+                shim_name(*p),
+            ]
+        })
+        .collect()
+}
+
+fn versions(root: &Path, names: &[String]) -> BTreeMap<String, String> {
+    let db = dpkg::StatusDb::load(root).unwrap_or_default();
+    names
+        .iter()
+        .filter_map(|n| {
+            db.get(n)
+                .filter(|p| p.is_installed())
+                .and_then(|p| p.version.clone())
+                .map(|v| (n.clone(), v))
+        })
+        .collect()
+}
+
+/// A mutating run in progress; created before the command runs so the prior versions are known.
+pub struct Run {
+    id: u64,
+    operation: String,
+    packages: Vec<Package>,
+    plan_hash: Option<String>,
+    undoes: Option<u64>,
+    before: BTreeMap<String, String>,
+    arch: BTreeMap<String, String>,
+}
+
+pub fn begin(root: &Path, operation: &str, packages: Vec<Package>, undoes: Option<u64>) -> Run {
+    let names = package_names(&packages);
+    let db = dpkg::StatusDb::load(root).unwrap_or_default();
+    let arch = names
+        .iter()
+        .filter_map(|n| {
+            db.get(n)
+                .and_then(|p| p.architecture.clone())
+                .map(|a| (n.clone(), a))
+        })
+        .collect();
+    Run {
+        // Assigned up front so the run's audit records can carry it
+        id: load(root)
+            .ok()
+            .and_then(|h| h.last().map(|e| e.id + 1))
+            .unwrap_or(1),
+        operation: operation.to_string(),
+        before: versions(root, &names),
+        packages,
+        plan_hash: None,
+        undoes,
+        arch,
+    }
+}
+
+impl Run {
+    /// Id the entry will be recorded under; audit records of the run carry it.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Plans are hashed once the run's audit log is open, since building one verifies artifacts.
+    pub fn set_plan_hash(&mut self, plan_hash: Option<String>) {
+        self.plan_hash = plan_hash;
+    }
+
+    /// Append the entry for this run and return its id.
+    pub fn finish(self, root: &Path, result: &Result<(), String>) -> Result<u64, String> {
+        let names = package_names(&self.packages);
+        let after = versions(root, &names);
+        // Packages that went away and have an archived copy of the exact prior version
+        let archived = self
+            .before
+            .iter()
+            .filter(|(n, _)| !after.contains_key(*n))
+            .filter_map(|(n, v)| {
+                let arch = self.arch.get(n)?;
+                let rel = Path::new(ARCHIVE_REL).join(deb_file_name(n, v, arch));
+                root.join(&rel).is_file().then(|| Path::new("/").join(rel))
+            })
+            .collect();
+        let id = self.id;
+        let entry = HistoryEntry {
+            id,
+            at: crate::state::now(),
+            operation: self.operation,
+            packages: self.packages,
+            versions_before: self.before,
+            versions_after: after,
+            plan_hash: self.plan_hash,
+            archived,
+            undoes: self.undoes,
+            success: result.is_ok(),
+            error: result.as_ref().err().cloned(),
+        };
+        let p = path(root);
+        if let Some(parent) = p.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+        }
+        let mut line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+        line.push('\n');
+        let mut f = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&p)
+            .map_err(|e| format!("failed to open history {}: {e}", p.display()))?;
+        f.write_all(line.as_bytes())
+            .and_then(|_| f.sync_all())
+            .map_err(|e| format!("failed to append to history {}: {e}", p.display()))?;
+        Ok(id)
+    }
+}
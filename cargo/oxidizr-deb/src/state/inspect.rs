@@ -0,0 +1,184 @@
+//! Classify what is actually on disk for each family, applet by applet.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::cli::args::Package;
+use crate::commands::restore::applets_for;
+use crate::fetch::resolver::resolve_artifact;
+use crate::fetch::resolver::staged_default_path;
+use crate::packages;
+use crate::state::{Mode, State};
+use crate::util::paths::ensure_under_root;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppletStatus {
+    /// Resolves to the replacement artifact.
+    Linked,
+    /// Regular file (the distro binary, or whatever dpkg last wrote).
+    Stock,
+    /// Symlink to something that exists but is not our artifact (busybox, alternatives to GNU, ...).
+    Foreign,
+    /// Symlink whose target does not exist.
+    Dangling,
+    Missing,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FamilyStatus {
+    Active,
+    Partial,
+    Unset,
+    Foreign,
+    Broken,
+}
+
+impl AppletStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AppletStatus::Linked => "linked",
+            AppletStatus::Stock => "stock",
+            AppletStatus::Foreign => "foreign",
+            AppletStatus::Dangling => "dangling",
+            AppletStatus::Missing => "missing",
+        }
+    }
+}
+
+impl FamilyStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FamilyStatus::Active => "active",
+            FamilyStatus::Partial => "partial",
+            FamilyStatus::Unset => "unset",
+            FamilyStatus::Foreign => "foreign",
+            FamilyStatus::Broken => "broken",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppletReport {
+    pub name: String,
+    pub status: AppletStatus,
+    pub expected: PathBuf,
+    /// Link target as read from disk (only for symlinks).
+    pub actual: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Counts {
+    pub linked: usize,
+    pub stock: usize,
+    pub foreign: usize,
+    pub dangling: usize,
+    pub missing: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FamilyReport {
+    #[serde(skip)]
+    pub package: Package,
+    pub status: FamilyStatus,
+    /// `true` when the state file recorded this family; otherwise the report is inferred.
+    pub recorded: bool,
+    pub mode: Option<Mode>,
+    pub expected_target: PathBuf,
+    pub counts: Counts,
+    pub applets: Vec<AppletReport>,
+}
+
+/// Paths a link may point at and still count as ours. With a state record this is the
+/// recorded artifact; without one, every location `use` could have linked to.
+fn candidates(root: &Path, st: Option<&State>, pkg: Package) -> Vec<PathBuf> {
+    if let Some(f) = st.and_then(|s| s.family(pkg)) {
+        return vec![ensure_under_root(root, &f.artifact.path)];
+    }
+    vec![
+        resolve_artifact(root, pkg, true, None),
+        resolve_artifact(root, pkg, false, None),
+        staged_default_path(root, pkg),
+    ]
+}
+
+fn classify(dst: &Path, ours: &[PathBuf]) -> (AppletStatus, Option<PathBuf>) {
+    let md = match fs::symlink_metadata(dst) {
+        Ok(m) => m,
+        Err(_) => return (AppletStatus::Missing, None),
+    };
+    if !md.file_type().is_symlink() {
+        return (AppletStatus::Stock, None);
+    }
+    let actual = fs::read_link(dst).ok();
+    // Follow the whole chain so links via /etc/alternatives still count.
+    let resolved = match fs::canonicalize(dst) {
+        Ok(r) => r,
+        Err(_) => return (AppletStatus::Dangling, actual),
+    };
+    let is_ours = ours
+        .iter()
+        .any(|c| fs::canonicalize(c).map(|c| c == resolved).unwrap_or(false));
+    // The GNU binary moved aside by --divert/--alternatives (`ls.gnu`) is still the stock tool.
+    let mut gnu = dst.as_os_str().to_owned();
+    gnu.push(".gnu");
+    if is_ours {
+        (AppletStatus::Linked, actual)
+    } else if fs::canonicalize(PathBuf::from(gnu)).ok() == Some(resolved) {
+        (AppletStatus::Stock, actual)
+    } else {
+        (AppletStatus::Foreign, actual)
+    }
+}
+
+pub fn inspect(root: &Path, st: Option<&State>, pkg: Package) -> FamilyReport {
+    let recorded = st.and_then(|s| s.family(pkg));
+    let ours = candidates(root, st, pkg);
+    let expected_target = ours.iter().find(|p| p.exists()).unwrap_or(&ours[0]).clone();
+    let dest_base = ensure_under_root(root, Path::new(packages::DEST_DIR));
+    let mut counts = Counts::default();
+    let applets: Vec<AppletReport> = applets_for(root, st, pkg)
+        .into_iter()
+        .map(|name| {
+            let (status, actual) = classify(&dest_base.join(&name), &ours);
+            match status {
+                AppletStatus::Linked => counts.linked += 1,
+                AppletStatus::Stock => counts.stock += 1,
+                AppletStatus::Foreign => counts.foreign += 1,
+                AppletStatus::Dangling => counts.dangling += 1,
+                AppletStatus::Missing => counts.missing += 1,
+            }
+            AppletReport {
+                name,
+                status,
+                expected: expected_target.clone(),
+                actual,
+            }
+        })
+        .collect();
+    let status = if counts.dangling > 0 {
+        FamilyStatus::Broken
+    } else if counts.linked > 0 && counts.linked == applets.len() {
+        FamilyStatus::Active
+    } else if counts.linked > 0 {
+        FamilyStatus::Partial
+    } else if counts.foreign > 0 {
+        FamilyStatus::Foreign
+    } else if recorded.is_some() {
+        // Recorded as switched but nothing of ours left on disk: dpkg or an admin undid it.
+        FamilyStatus::Broken
+    } else {
+        FamilyStatus::Unset
+    };
+    FamilyReport {
+        package: pkg,
+        status,
+        recorded: recorded.is_some(),
+        mode: recorded.map(|f| f.mode),
+        expected_target,
+        counts,
+        applets,
+    }
+}
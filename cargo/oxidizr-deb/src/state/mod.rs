@@ -0,0 +1,242 @@
+//! Persistent record of what oxidizr-deb switched, kept inside the target root at
+//! `var/lib/oxidizr-deb/state.json`. `status` and `restore` read it instead of guessing.
+pub mod archive;
+pub mod audit;
+pub mod history;
+pub mod inspect;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli::args::Package;
+use crate::util::hash::sha256_file;
+use crate::util::paths::ensure_under_root;
+
+pub const STATE_SCHEMA_VERSION: u32 = 1;
+pub const STATE_REL: &str = "var/lib/oxidizr-deb/state.json";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Replacement linked in; GNU package still installed.
+    Used,
+    /// Replacement linked in and the GNU package purged.
+    Replaced,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ArtifactRecord {
+    /// Path inside the root (e.g. `/usr/bin/uutils`).
+    pub path: PathBuf,
+    pub version: Option<String>,
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FamilyState {
+    pub mode: Mode,
+    pub applets: Vec<String>,
+    pub artifact: ArtifactRecord,
+    /// Backup sidecars Switchyard left next to the switched targets.
+    pub backups: Vec<PathBuf>,
+    /// Targets moved aside with dpkg-divert (`debian-divert` builds only).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diverted: Vec<PathBuf>,
+    pub apt_installed: Vec<String>,
+    pub apt_purged: Vec<String>,
+    pub switched_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub schema_version: u32,
+    pub families: BTreeMap<Package, FamilyState>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            schema_version: STATE_SCHEMA_VERSION,
+            families: BTreeMap::new(),
+        }
+    }
+}
+
+impl State {
+    pub fn path(root: &Path) -> PathBuf {
+        root.join(STATE_REL)
+    }
+
+    /// Load the state file; `Ok(None)` when oxidizr-deb never recorded anything under this root.
+    pub fn load(root: &Path) -> Result<Option<State>, String> {
+        let p = Self::path(root);
+        let s = match std::fs::read_to_string(&p) {
+            Ok(s) => s,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("failed to read state {}: {e}", p.display())),
+        };
+        let st: State = serde_json::from_str(&s)
+            .map_err(|e| format!("invalid state file {}: {e}", p.display()))?;
+        if st.schema_version > STATE_SCHEMA_VERSION {
+            return Err(format!(
+                "state file {} has schema_version {} (this oxidizr-deb understands up to {})",
+                p.display(),
+                st.schema_version,
+                STATE_SCHEMA_VERSION
+            ));
+        }
+        Ok(Some(st))
+    }
+
+    pub fn save(&self, root: &Path) -> Result<(), String> {
+        let p = Self::path(root);
+        if let Some(parent) = p.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+        }
+        let body = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let tmp = p.with_extension("json.tmp");
+        std::fs::write(&tmp, body.as_bytes())
+            .map_err(|e| format!("failed to write {}: {e}", tmp.display()))?;
+        std::fs::rename(&tmp, &p).map_err(|e| format!("failed to write {}: {e}", p.display()))
+    }
+
+    pub fn family(&self, pkg: Package) -> Option<&FamilyState> {
+        self.families.get(&pkg)
+    }
+}
+
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Express a host path under `root` as the path seen from inside the root.
+pub fn rooted(root: &Path, p: &Path) -> PathBuf {
+    match p.strip_prefix(root) {
+        Ok(rel) => Path::new("/").join(rel),
+        Err(_) => p.to_path_buf(),
+    }
+}
+
+/// Installed version of a Debian package, if it can be determined for this root.
+pub fn package_version(root: &Path, name: &str) -> Option<String> {
+    crate::adapters::dpkg::installed_version(root, name)
+}
+
+/// Switchyard keeps backups as hidden sidecars next to the target (`.<name>.<tag>.<ts>.bak`).
+fn backups_for(root: &Path, dest_dir: &Path, applets: &[String]) -> Vec<PathBuf> {
+    let dir = ensure_under_root(root, dest_dir);
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+    let names: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    let mut out = Vec::new();
+    for app in applets {
+        let prefix = format!(".{}.", app);
+        for n in names
+            .iter()
+            .filter(|n| n.starts_with(&prefix) && n.ends_with(".bak"))
+        {
+            out.push(rooted(root, &dir.join(n)));
+        }
+    }
+    out.sort();
+    out
+}
+
+/// Record a committed `use` (or the `use` half of `replace`) for one family.
+pub fn record_use(
+    root: &Path,
+    pkg: Package,
+    applets: &[String],
+    source_bin: &Path,
+    apt_installed: Option<&str>,
+    version: Option<&str>,
+) -> Result<(), String> {
+    let mut st = State::load(root)?.unwrap_or_default();
+    let ts = now();
+    let prev = st.families.remove(&pkg);
+    let mut installed = prev
+        .as_ref()
+        .map(|f| f.apt_installed.clone())
+        .unwrap_or_default();
+    if let Some(name) = apt_installed {
+        if !installed.iter().any(|n| n == name) {
+            installed.push(name.to_string());
+        }
+    }
+    let version = version
+        .map(str::to_string)
+        .or_else(|| apt_installed.and_then(|n| package_version(root, n)))
+        .or_else(|| prev.as_ref().and_then(|f| f.artifact.version.clone()));
+    let entry = FamilyState {
+        mode: prev.as_ref().map(|f| f.mode).unwrap_or(Mode::Used),
+        applets: applets.to_vec(),
+        artifact: ArtifactRecord {
+            path: rooted(root, source_bin),
+            version,
+            sha256: sha256_file(source_bin).ok(),
+        },
+        backups: backups_for(root, Path::new(crate::packages::DEST_DIR), applets),
+        diverted: prev
+            .as_ref()
+            .map(|f| f.diverted.clone())
+            .unwrap_or_default(),
+        apt_installed: installed,
+        apt_purged: prev
+            .as_ref()
+            .map(|f| f.apt_purged.clone())
+            .unwrap_or_default(),
+        switched_at: prev.as_ref().map(|f| f.switched_at).unwrap_or(ts),
+        updated_at: ts,
+    };
+    st.families.insert(pkg, entry);
+    st.save(root)
+}
+
+/// Record the targets `use --divert` moved aside with dpkg-divert.
+pub fn record_diverted(root: &Path, pkg: Package, paths: &[PathBuf]) -> Result<(), String> {
+    let mut st = State::load(root)?.unwrap_or_default();
+    if let Some(f) = st.families.get_mut(&pkg) {
+        for p in paths {
+            if !f.diverted.contains(p) {
+                f.diverted.push(p.clone());
+            }
+        }
+        f.updated_at = now();
+    }
+    st.save(root)
+}
+
+/// Record that the GNU package for a family was purged by `replace`.
+pub fn record_purge(root: &Path, pkg: Package, distro_pkg: &str) -> Result<(), String> {
+    let mut st = State::load(root)?.unwrap_or_default();
+    if let Some(f) = st.families.get_mut(&pkg) {
+        f.mode = Mode::Replaced;
+        if !f.apt_purged.iter().any(|n| n == distro_pkg) {
+            f.apt_purged.push(distro_pkg.to_string());
+        }
+        f.updated_at = now();
+    }
+    st.save(root)
+}
+
+/// Drop a family after a committed `restore`.
+pub fn record_restore(root: &Path, pkg: Package) -> Result<(), String> {
+    let mut st = match State::load(root)? {
+        Some(s) => s,
+        None => return Ok(()),
+    };
+    st.families.remove(&pkg);
+    st.save(root)
+}
@@ -1,2 +1,24 @@
-// Placeholder: user-facing error helpers & tips
-pub fn tip(_msg: &str) {}
+use serde_json::json;
+
+use crate::errors::CliError;
+
+/// Print a remediation hint under an error or warning.
+pub fn tip(msg: &str) {
+    eprintln!("hint: {msg}");
+}
+
+/// Report a failed run: `error[CODE]: message` and its hint on stderr and, for commands run with
+/// `--json`, `{"ok": false, "error": {code, exit_code, message, hint}}` on stdout.
+pub fn report(e: &CliError, json: bool) {
+    eprintln!("error[{}]: {e}", e.code());
+    if let Some(h) = e.hint() {
+        tip(h);
+    }
+    if json {
+        let doc = json!({"ok": false, "error": e.to_json()});
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&doc).unwrap_or_else(|_| doc.to_string())
+        );
+    }
+}
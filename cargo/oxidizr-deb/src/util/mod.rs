@@ -1,3 +1,4 @@
 pub mod diagnostics;
+pub mod hash;
 pub mod paths;
 pub mod prompts;
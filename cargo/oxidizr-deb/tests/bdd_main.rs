@@ -31,8 +31,20 @@ async fn main() {
         root.join("tests/features")
     };
 
+    // Scenarios tagged with an optional cargo feature only run when it is compiled in.
+    let optional: &[(&str, bool)] = &[
+        ("debian-divert", cfg!(feature = "debian-divert")),
+        ("debian-alternatives", cfg!(feature = "debian-alternatives")),
+    ];
     bdd_world::World::cucumber()
         .fail_on_skipped()
-        .run_and_exit(features)
+        .filter_run_and_exit(features, move |feat, _, sc| {
+            feat.tags.iter().chain(sc.tags.iter()).all(|t| {
+                let t = t.trim_start_matches('@');
+                optional
+                    .iter()
+                    .all(|(name, enabled)| t != *name || *enabled)
+            })
+        })
         .await;
 }
@@ -8,6 +8,9 @@ pub struct World {
     pub last_output: Option<std::process::Output>,
     pub artifact_path: Option<PathBuf>,
     pub envs: Vec<(String, String)>,
+    /// Local datagram socket standing in for journald, and the entries it received so far
+    pub journal: Option<std::os::unix::net::UnixDatagram>,
+    pub journal_entries: Vec<String>,
 }
 
 impl World {
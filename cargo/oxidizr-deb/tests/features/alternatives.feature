@@ -0,0 +1,54 @@
+@debian-alternatives
+Feature: update-alternatives strategy
+  As an operator
+  I want switching done through alternatives link groups
+  So that keeping replacements really means installed but de-preferred
+
+  Scenario: Use with --alternatives then de-prefer with --keep-replacements
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --commit use coreutils --alternatives`
+    Then the command exits 0
+    And `/usr/bin/ls` is a symlink
+    And `/etc/alternatives/ls` is a symlink to the replacement
+    And `/var/lib/dpkg/alternatives/ls` is a regular file with content `manual`
+    When I run `oxidizr-deb --commit restore coreutils --keep-replacements`
+    Then the command exits 0
+    And `/usr/bin/ls` is a symlink
+    And `/var/lib/dpkg/alternatives/ls` is a regular file with content `manual`
+    When I run `oxidizr-deb status`
+    Then output contains `coreutils: unset`
+
+  Scenario: Plain restore removes the link groups
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --commit use coreutils --alternatives`
+    Then the command exits 0
+    And `/usr/bin/ls.gnu` is a regular file with content `gnu-ls`
+    And `/var/lib/dpkg/diversions` contains `/usr/bin/ls.gnu`
+    When I run `oxidizr-deb --commit restore coreutils`
+    Then the command exits 0
+    And `/usr/bin/ls` is a regular file with content `gnu-ls`
+    And `/var/lib/dpkg/diversions` does not contain `/usr/bin/ls.gnu`
+
+  Scenario: Existing slave links survive registering the providers
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    And the alternatives group `ls` is registered as:
+      """
+      auto
+      /usr/bin/ls
+      ls.1.gz
+      /usr/share/man/man1/ls.1.gz
+
+      /usr/bin/ls.gnu
+      50
+      /usr/share/man/man1/ls.gnu.1.gz
+      """
+    When I run `oxidizr-deb --commit use coreutils --alternatives`
+    Then the command exits 0
+    And `/var/lib/dpkg/alternatives/ls` contains `ls.1.gz`
+    And `/var/lib/dpkg/alternatives/ls` contains `/usr/share/man/man1/ls.gnu.1.gz`
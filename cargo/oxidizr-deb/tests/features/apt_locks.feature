@@ -8,5 +8,5 @@ Feature: APT/DPKG locks prevent commit
     And dpkg/apt locks are present
     And a verified replacement artifact is available for package "coreutils"
     When I run `oxidizr-deb --commit use coreutils`
-    Then the command exits 1
+    Then the command exits 3
     And output contains `Package manager busy (dpkg/apt lock detected); retry after current operation finishes.`
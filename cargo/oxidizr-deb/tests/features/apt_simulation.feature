@@ -0,0 +1,30 @@
+Feature: Simulated apt delta in dry-run
+  As an operator
+  I want dry-run to show what apt would really install and remove
+  So that Essential packages and reverse dependencies are visible before committing
+
+  Scenario: Dry-run replace shows the simulated package delta
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg status database has `coreutils` version `9.1-1` installed as Essential
+    And apt-get simulation prints:
+      """
+      Inst rust-coreutils (0.0.24-1 Debian:12/stable [amd64])
+      Purg coreutils [9.1-1]
+      Remv needs-coreutils [1.0]
+      Conf rust-coreutils (0.0.24-1 Debian:12/stable [amd64])
+      """
+    When I run `oxidizr-deb replace coreutils`
+    Then the command exits 0
+    And output contains `[dry-run] apt would install 1, remove 1, purge 1 package(s)`
+    And output contains `Inst rust-coreutils 0.0.24-1`
+    And output contains `Purg coreutils 9.1-1 (Essential: yes; needs --allow-remove-essential)`
+    And output contains `Remv needs-coreutils 1.0 (not requested; pulled in by dependencies)`
+    And output contains `"event":"pm.simulate"`
+
+  Scenario: Dry-run still succeeds when apt cannot simulate for the root
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    When I run `oxidizr-deb replace coreutils`
+    Then the command exits 0
+    And output contains `[dry-run] apt simulation unavailable`
@@ -0,0 +1,20 @@
+Feature: Single apt transaction across package families
+  As an operator
+  I want replace/restore of several families to batch their apt steps
+  So that a failure midway cannot leave a half-migrated host
+
+  Scenario: Dry-run replace --all previews one install and one purge
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    When I run `oxidizr-deb replace --all`
+    Then the command exits 0
+    And output contains `[dry-run] would run: apt-get install -y rust-coreutils rust-findutils sudo-rs`
+    And output contains `[dry-run] would run: apt-get purge -y coreutils findutils sudo`
+
+  Scenario: Dry-run restore --all previews one install and one purge
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    When I run `oxidizr-deb restore --all`
+    Then the command exits 0
+    And output contains `[dry-run] would run: apt-get install -y coreutils findutils sudo`
+    And output contains `[dry-run] would run: apt-get purge -y rust-coreutils rust-findutils sudo-rs`
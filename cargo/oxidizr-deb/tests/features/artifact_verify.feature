@@ -0,0 +1,42 @@
+Feature: Offline artifact verification
+  As an operator
+  I want offline replacement artifacts checked against a signed sha256 manifest
+  So that a tampered or unsigned binary is never linked into /usr/bin
+
+  Scenario: Signed artifact is accepted and its manifest version recorded
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 0
+    And output contains `"event":"artifact.verify"`
+    And `/usr/bin/ls` is a symlink to the replacement
+    And `/var/lib/oxidizr-deb/state.json` contains `"version": "0.0.0-test"`
+
+  Scenario: Artifact modified after signing is refused
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When `/opt/uutils/uutils` is changed to contain `tampered-binary`
+    And I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 1
+    And output contains `sha256 mismatch`
+    And `/usr/bin/ls` is a regular file with content `gnu-ls`
+
+  Scenario: Artifact without a trusted key is refused
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    And no trusted artifact keys are installed
+    When I run `oxidizr-deb use coreutils`
+    Then the command exits 1
+    And output contains `no trusted keys under`
+
+  Scenario: Artifact signed for another family is refused
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "findutils"
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 1
+    And output contains `manifest is for findutils, not coreutils`
+    And `/usr/bin/ls` is a regular file with content `gnu-ls`
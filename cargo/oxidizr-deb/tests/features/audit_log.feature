@@ -0,0 +1,42 @@
+Feature: Tamper-evident audit log
+  As an auditor
+  I want committed runs recorded in a hash-chained log inside the root
+  So that what oxidizr-deb changed survives the terminal and edits to it are detected
+
+  Scenario: Committed runs are appended to the audit log and verify
+    Given a staging root at /tmp/fakeroot
+    When I run `oxidizr-deb --commit hooks install`
+    Then the command exits 0
+    And `/var/log/oxidizr-deb/audit.jsonl` contains `"event":"hook.install"`
+    And `/var/log/oxidizr-deb/audit.jsonl` contains `"event":"run.finish"`
+    And `/var/log/oxidizr-deb/audit.jsonl` contains `"prev":"0000000000000000`
+    When I run `oxidizr-deb audit verify`
+    Then the command exits 0
+    And output contains `audit log intact: 3 record(s)`
+
+  Scenario: Dry runs are not audited
+    Given a staging root at /tmp/fakeroot
+    When I run `oxidizr-deb hooks install`
+    Then the command exits 0
+    When I run `oxidizr-deb audit verify`
+    Then the command exits 0
+    And output contains `No audit records`
+
+  Scenario: A rewritten audit log fails verification
+    Given a staging root at /tmp/fakeroot
+    When I run `oxidizr-deb --commit hooks install`
+    Then the command exits 0
+    When `/var/log/oxidizr-deb/audit.jsonl` is changed to contain `{"seq":1,"event":"hook.install","prev":"0000000000000000000000000000000000000000000000000000000000000000","hash":"00"}`
+    And I run `oxidizr-deb audit verify --json`
+    Then the command exits 1
+    And output contains `"ok": false`
+    And output contains `record 1 was modified`
+
+  Scenario: A hook run triggered by oxidizr-deb's own apt run is not audited
+    Given a staging root at /tmp/fakeroot
+    And the environment variable `OXIDIZR_DEB_ACTIVE` is `1`
+    When I run `oxidizr-deb --commit hooks run`
+    Then the command exits 0
+    When I run `oxidizr-deb audit verify`
+    Then the command exits 0
+    And output contains `No audit records`
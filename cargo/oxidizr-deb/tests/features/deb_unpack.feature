@@ -0,0 +1,30 @@
+Feature: Stage replacements into images without dpkg
+  As an image builder
+  I want `use` to unpack the replacement .deb into a non-live root
+  So that chroots and images get replacements without running apt or dpkg against --root
+
+  Scenario: Commit use with --deb unpacks the package and registers it with dpkg
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a `rust-coreutils` .deb at `/srv/rust-coreutils_0.1.0-1_all.deb` shipping `/usr/bin/uutils`
+    When I run `oxidizr-deb --commit use coreutils --deb /srv/rust-coreutils_0.1.0-1_all.deb`
+    Then the command exits 0
+    And `/usr/bin/uutils` is a regular file
+    And `/usr/bin/ls` is a symlink
+    And `/var/lib/dpkg/status` contains `Package: rust-coreutils`
+    And `/var/lib/dpkg/status` contains `Status: install ok installed`
+    And `/var/lib/dpkg/info/rust-coreutils.list` contains `/usr/bin/uutils`
+    And output contains `"event":"pm.unpack"`
+
+  Scenario: Commit use takes the .deb from the root's apt cache
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a `rust-coreutils` .deb at `/var/cache/apt/archives/rust-coreutils_0.1.0-1_all.deb` shipping `/usr/bin/uutils`
+    When I run `oxidizr-deb use coreutils`
+    Then the command exits 0
+    And output contains `[dry-run] would unpack`
+    And output contains `rust-coreutils_0.1.0-1_all.deb`
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 0
+    And `/usr/bin/ls` is a symlink
+    And `/var/lib/dpkg/info/rust-coreutils.list` contains `/usr/bin/uutils`
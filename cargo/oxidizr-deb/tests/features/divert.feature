@@ -0,0 +1,18 @@
+@debian-divert
+Feature: dpkg-divert strategy
+  As an operator
+  I want the GNU files diverted rather than overwritten
+  So that package upgrades never clobber the replacement links
+
+  Scenario: Use with --divert moves the GNU file aside and restore puts it back
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --commit use coreutils --divert`
+    Then the command exits 0
+    And `/usr/bin/ls` is a symlink to the replacement
+    And `/usr/bin/ls.gnu` is a regular file with content `gnu-ls`
+    And `/var/lib/dpkg/diversions` is a regular file with content `/usr/bin/`
+    When I run `oxidizr-deb --commit restore coreutils`
+    Then the command exits 0
+    And `/usr/bin/ls` is a regular file with content `gnu-ls`
@@ -0,0 +1,22 @@
+Feature: Package enumeration from the root's dpkg database
+  As an operator working on chroots and images
+  I want applets enumerated from var/lib/dpkg/info of the target root
+  So that only commands the distro package actually ships are switched
+
+  Scenario: Use links only the commands listed by dpkg under a non-live root
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg database lists `/usr/bin` for package `coreutils:amd64`
+    And the dpkg database lists `/usr/bin/ls` for package `coreutils:amd64`
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 0
+    And `/usr/bin/ls` is a symlink to the replacement
+    And `/usr/bin/cat` is a regular file with content `gnu-cat`
+
+  Scenario: Restore without a state file uses the dpkg list of the root
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg database lists `/usr/bin/ls` for package `coreutils`
+    When I run `oxidizr-deb restore coreutils`
+    Then the command exits 0
@@ -0,0 +1,15 @@
+Feature: Package state from the root's dpkg status database
+  As an operator working on chroots and images
+  I want package queries answered from <root>/var/lib/dpkg/status
+  So that checks reflect the target root rather than the host
+
+  Scenario: Doctor reports installed packages of the target root
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg status database has `coreutils` version `9.4-3` installed as Essential
+    And the dpkg status database has `sudo-rs` version `0.2.5-1` installed
+    When I run `oxidizr-deb doctor --json`
+    Then the command exits 0
+    And output contains `"coreutils":{"installed":true,"version":"9.4-3","essential":true}`
+    And output contains `"sudo-rs":{"installed":true,"version":"0.2.5-1","essential":false}`
+    And output contains `"rust-coreutils":{"installed":false,"version":null,"essential":false}`
@@ -0,0 +1,36 @@
+Feature: Stable exit codes and remediation hints
+  As an automation author
+  I want each failure kind to carry its own exit code, error code and hint
+  So that scripts can branch on why a run failed
+
+  Scenario: A held package manager lock exits 3 with a hint
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And dpkg/apt locks are present
+    When I run `oxidizr-deb --commit replace coreutils`
+    Then the command exits 3
+    And output contains `error[E_LOCK_BUSY]: Package manager busy`
+    And output contains `hint: wait for the running apt/dpkg or oxidizr-deb operation to finish, then retry`
+
+  Scenario: Apt changes against a plain rootfs exit 8
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    When I run `oxidizr-deb --commit replace coreutils`
+    Then the command exits 8
+    And output contains `error[E_INVALID_ROOT]`
+    And output contains `hint: point --root at a root filesystem`
+
+  Scenario: A --json command reports the error as an object
+    When I run `oxidizr-deb --root / --image-mode chroot status --json`
+    Then the command exits 8
+    And output contains `"ok": false`
+    And output contains `"code": "E_INVALID_ROOT"`
+    And output contains `"exit_code": 8`
+    And output contains `"hint": "point --root at a root filesystem`
+
+  Scenario: Other failures keep exit 1 without a hint
+    Given a staging root at /tmp/fakeroot
+    When I run `oxidizr-deb undo 7`
+    Then the command exits 1
+    And output contains `error[E_FAILED]: no history entry #7`
+    And output does not contain `hint:`
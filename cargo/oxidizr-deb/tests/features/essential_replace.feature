@@ -0,0 +1,28 @@
+Feature: Essential package removal
+  As an operator
+  I want purging an Essential package to need an explicit confirmation
+  So that coreutils is never removed by accident
+
+  Scenario: Dry-run replace notes that coreutils is Essential
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg status database has `coreutils` version `9.1-1` installed as Essential
+    When I run `oxidizr-deb replace coreutils`
+    Then the command exits 0
+    And output contains `coreutils is Essential: yes; --commit will require --confirm-essential-removal`
+
+  Scenario: Confirmed dry-run previews the essential-removal purge
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg status database has `coreutils` version `9.1-1` installed as Essential
+    When I run `oxidizr-deb replace coreutils --confirm-essential-removal`
+    Then the command exits 0
+    And output contains `[dry-run] would purge Essential package(s) coreutils with --allow-remove-essential`
+
+  Scenario: Non-essential packages need no confirmation
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg status database has `findutils` version `4.9.0-4` installed
+    When I run `oxidizr-deb replace findutils`
+    Then the command exits 0
+    And output does not contain `Essential`
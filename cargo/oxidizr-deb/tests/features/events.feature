@@ -0,0 +1,33 @@
+Feature: Typed, versioned events
+  As an operator feeding oxidizr-deb's JSON lines into a log pipeline
+  I want every event to follow one published schema
+  So that events parse reliably across releases
+
+  Scenario: The event schema is printed
+    Given a staging root at /tmp/fakeroot
+    When I run `oxidizr-deb events schema`
+    Then the command exits 0
+    And output contains `"schema_version"`
+    And output contains `"pm.install"`
+    And output contains `"use.exec.skip_applet"`
+
+  Scenario: Events carry the schema version
+    Given a staging root at /tmp/fakeroot
+    When I run `oxidizr-deb --commit hooks install`
+    Then the command exits 0
+    And output contains `"event":"hook.install"`
+    And output contains `"schema_version":1`
+
+  Scenario: Use reports distro applets the replacement does not provide
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg database lists `/usr/bin/ls` for package `coreutils:amd64`
+    And the dpkg database lists `/usr/bin/cat` for package `coreutils:amd64`
+    And the dpkg database lists `/usr/bin/echo` for package `coreutils:amd64`
+    And the dpkg database lists `/usr/bin/chcon` for package `coreutils:amd64`
+    And a verified coreutils replacement that lists applets `ls cat echo`
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 0
+    And output contains `{"applet":"chcon","event":"use.exec.skip_applet","reason":"not_in_replacement"`
+    And output does not contain `{"applet":"ls"`
+    And `/usr/bin/ls` is a symlink to the replacement
@@ -0,0 +1,39 @@
+Feature: Operation history and undo
+  As an operator
+  I want every committed run recorded with exact package versions
+  So that I can see what changed and undo a replace offline
+
+  Scenario: A committed use is recorded in the history
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    And the dpkg status database has `coreutils` version `9.1-1` installed
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 0
+    And `/var/lib/oxidizr-deb/history.jsonl` contains `"operation":"use"`
+    And `/var/lib/oxidizr-deb/history.jsonl` contains `"versions_before":{"coreutils":"9.1-1"}`
+    When I run `oxidizr-deb history`
+    Then the command exits 0
+    And output contains `#1 use ok [Coreutils]`
+
+  Scenario: Dry-runs are not recorded
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    When I run `oxidizr-deb replace coreutils`
+    Then the command exits 0
+    When I run `oxidizr-deb history`
+    Then output contains `No recorded operations`
+
+  Scenario: Dry-run replace previews archiving the GNU package
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg status database has `coreutils` version `9.1-1` installed
+    When I run `oxidizr-deb replace coreutils`
+    Then the command exits 0
+    And output contains `[dry-run] would archive coreutils to /var/lib/oxidizr-deb/archive/coreutils_9.1-1_amd64.deb (rebuilt from the installed files)`
+
+  Scenario: Undo refuses unknown history entries
+    Given a staging root at /tmp/fakeroot
+    When I run `oxidizr-deb undo 7`
+    Then the command exits 1
+    And output contains `no history entry #7`
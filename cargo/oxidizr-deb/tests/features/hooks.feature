@@ -0,0 +1,33 @@
+Feature: APT hook re-asserts replacements after dpkg runs
+  As an operator
+  I want a DPkg::Post-Invoke hook that repairs recorded replacements
+  So that upgrades of GNU packages do not silently undo the switch
+
+  Scenario: Install and remove the hook under a fakeroot
+    Given a staging root at /tmp/fakeroot
+    When I run `oxidizr-deb --commit hooks install`
+    Then the command exits 0
+    And `/etc/apt/apt.conf.d/80oxidizr-deb` is a regular file with content `// Managed by oxidizr-deb`
+    And output contains `"event":"hook.install"`
+    When I run `oxidizr-deb --commit hooks remove`
+    Then the command exits 0
+    And output contains `"event":"hook.remove"`
+
+  Scenario: Hook run is a no-op when nothing is switched
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    When I run `oxidizr-deb --commit hooks run`
+    Then the command exits 0
+    And output contains `"decision":"noop"`
+
+  Scenario: Hook run re-links applets dpkg overwrote
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 0
+    When `/usr/bin/ls` is changed to contain `gnu-ls`
+    And I run `oxidizr-deb --commit hooks run`
+    Then the command exits 0
+    And output contains `"decision":"repaired"`
+    And `/usr/bin/ls` is a symlink to the replacement
@@ -0,0 +1,41 @@
+Feature: Image mode for rootfs builds
+  As an image builder
+  I want apt and dpkg to run against a non-live --root
+  So that use, replace and restore behave on a rootfs as they do on a live host
+
+  Scenario: Dry-run replace in dir mode aims apt and dpkg at the root
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And apt-get simulation prints:
+      """
+      Inst rust-coreutils (0.0.24-1 Debian:12/stable [amd64])
+      """
+    When I run `oxidizr-deb --image-mode dir replace coreutils`
+    Then the command exits 0
+    And output contains `"-o","Dir=`
+    And output contains `DPkg::Options::=--root=`
+    And output contains `[dry-run] apt would install 1, remove 0, purge 0 package(s)`
+
+  Scenario: Commit restore in dir mode runs the apt steps instead of skipping them
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And apt-get simulation prints:
+      """
+      Inst coreutils (9.1-1 Debian:12/stable [amd64])
+      """
+    When I run `oxidizr-deb --commit --image-mode dir restore coreutils`
+    Then the command exits 0
+    And output contains `"event":"pm.transaction"`
+    And output does not contain `skipping apt/dpkg install steps`
+
+  Scenario: Commit replace without an image mode points at --image-mode
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    When I run `oxidizr-deb --commit replace coreutils`
+    Then the command exits 8
+    And output contains `use --image-mode dir|chroot for a rootfs`
+
+  Scenario: Image mode is refused for the live root
+    When I run `oxidizr-deb --root / --image-mode chroot status`
+    Then the command exits 8
+    And output contains `--image-mode applies to a non-live --root`
@@ -0,0 +1,46 @@
+Feature: Import offline replacement bundles
+  As an operator of air-gapped hosts
+  I want to import a signed bundle of replacement binaries
+  So that `use --offline` finds them without hand-copying files
+
+  Scenario: Dry-run import verifies the bundle without staging it
+    Given a staging root at /tmp/fakeroot
+    And a signed replacement bundle `/var/tmp/bundle.tar` with version "9.5" containing `uutils`
+    When I run `oxidizr-deb import-bundle /var/tmp/bundle.tar`
+    Then the command exits 0
+    And output contains `[dry-run] would import Coreutils 9.5`
+    And output contains `"event":"bundle.import"`
+
+  Scenario: Committed import stages a versioned family used by offline use
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a signed replacement bundle `/var/tmp/bundle.tar` with version "9.5" containing `uutils`
+    When I run `oxidizr-deb --commit import-bundle /var/tmp/bundle.tar`
+    Then the command exits 0
+    And `/opt/oxidizr/replacements/uutils-coreutils/9.5/bin/uutils` is a regular file with content `uutils-9.5-binary`
+    And `/opt/oxidizr/replacements/uutils-coreutils/bin` is a symlink
+    When I run `oxidizr-deb --commit use coreutils --offline`
+    Then the command exits 0
+    And `/var/lib/oxidizr-deb/state.json` contains `"version": "9.5"`
+
+  Scenario: Bundle signed by an untrusted key is refused
+    Given a staging root at /tmp/fakeroot
+    And a signed replacement bundle `/var/tmp/bundle.tar` with version "9.5" containing `uutils`
+    And no trusted artifact keys are installed
+    When I run `oxidizr-deb --commit import-bundle /var/tmp/bundle.tar`
+    Then the command exits 1
+    And output contains `bundle verification failed`
+
+  Scenario: A version that collides with the layout is refused
+    Given a staging root at /tmp/fakeroot
+    And a signed replacement bundle `/var/tmp/bundle.tar` with version "bin" containing `uutils`
+    When I run `oxidizr-deb --commit import-bundle /var/tmp/bundle.tar`
+    Then the command exits 1
+    And output contains `invalid version "bin"`
+
+  Scenario: A version shaped like a staging entry is refused
+    Given a staging root at /tmp/fakeroot
+    And a signed replacement bundle `/var/tmp/bundle.tar` with version ".9.5.tmp" containing `uutils`
+    When I run `oxidizr-deb --commit import-bundle /var/tmp/bundle.tar`
+    Then the command exits 1
+    And output contains `invalid version ".9.5.tmp"`
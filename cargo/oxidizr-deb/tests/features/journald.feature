@@ -0,0 +1,37 @@
+Feature: journald log sink
+  As an operator
+  I want oxidizr-deb's events in the systemd journal
+  So that `journalctl -t oxidizr-deb` shows what apt and Switchyard did
+
+  Scenario: Events go to journald as structured fields
+    Given a staging root at /tmp/fakeroot
+    And a local journald socket is listening
+    When I run `oxidizr-deb --log-sink journald --commit hooks install`
+    Then the command exits 0
+    And the journal received `SYSLOG_IDENTIFIER=oxidizr-deb`
+    And the journal received `OXIDIZR_EVENT=hook.install`
+    And the journal received `OXIDIZR_EVENT=run.finish`
+    And output does not contain `"event":"hook.install"`
+
+  Scenario: apt events carry the package and priority
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg status database has `coreutils` version `9.1-1` installed as Essential
+    And a local journald socket is listening
+    And apt-get simulation prints:
+      """
+      Inst rust-coreutils (0.0.24-1 Debian:12/stable [amd64])
+      """
+    When I run `oxidizr-deb --log-sink journald --image-mode dir replace coreutils`
+    Then the command exits 0
+    And the journal received `OXIDIZR_EVENT=pm.transaction`
+    And the journal received `OXIDIZR_PACKAGE=rust-coreutils`
+    And the journal received `PRIORITY=6`
+
+  Scenario: Without a reachable journal, events fall back to stderr
+    Given a staging root at /tmp/fakeroot
+    And the environment variable `OXIDIZR_DEB_JOURNAL_SOCKET` is `/nonexistent/journal.sock`
+    When I run `oxidizr-deb --log-sink journald --commit hooks install`
+    Then the command exits 0
+    And output contains `journald: failed to send`
+    And output contains `"event":"hook.install"`
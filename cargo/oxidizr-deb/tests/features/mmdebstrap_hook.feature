@@ -0,0 +1,33 @@
+Feature: mmdebstrap customize hook
+  As an image builder
+  I want to run oxidizr-deb from mmdebstrap's --customize-hook
+  So that images ship with the replacements and a failed swap aborts the build
+
+  Scenario: The hook refuses a rootfs without a dpkg database
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    When I run `oxidizr-deb mmdebstrap-hook use / coreutils`
+    Then the command exits 8
+    And output contains `run mmdebstrap-hook as a --customize-hook`
+
+  Scenario: The hook refuses to run in an earlier hook phase
+    Given a staging root at /tmp/fakeroot
+    And the dpkg status database has `coreutils` version `9.1-1` installed as Essential
+    And the environment variable `MMDEBSTRAP_HOOK` is `setup`
+    When I run `oxidizr-deb mmdebstrap-hook replace / --all`
+    Then the command exits 1
+    And output contains `called as a setup hook`
+
+  Scenario: A failed step exits non-zero so the image build aborts
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg status database has `coreutils` version `9.1-1` installed as Essential
+    And apt-get simulation prints:
+      """
+      Inst rust-coreutils (0.0.24-1 Debian:12/stable [amd64])
+      """
+    When I run `oxidizr-deb --image-mode dir mmdebstrap-hook use / coreutils`
+    Then the command exits 1
+    And output contains `"event":"mmdebstrap.hook"`
+    And output contains `"ok":false`
+    And output contains `"image_mode":"dir"`
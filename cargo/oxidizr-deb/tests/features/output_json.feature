@@ -0,0 +1,62 @@
+Feature: Machine-readable result document
+  As a configuration-management module (Ansible, Salt)
+  I want every command to print one JSON result saying whether it changed anything
+  So that I can wrap oxidizr-deb idempotently
+
+  Scenario: A dry run reports what committing would change
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --output json use coreutils`
+    Then the command exits 0
+    And output contains `"command": "use"`
+    And output contains `"dry_run": true`
+    And output contains `"changed": true`
+    And output contains `"action": "link"`
+    And output contains `"executed_actions": []`
+
+  Scenario: Committing the same use twice reports no change the second time
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --commit use coreutils`
+    And I run `oxidizr-deb --commit --output json use coreutils`
+    Then the command exits 0
+    And output contains `"ok": true`
+    And output contains `"changed": false`
+
+  Scenario: Skipped applets are listed with their reason
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg database lists `/usr/bin/ls` for package `coreutils:amd64`
+    And the dpkg database lists `/usr/bin/chcon` for package `coreutils:amd64`
+    And a verified coreutils replacement that lists applets `ls cat echo`
+    When I run `oxidizr-deb --output json use coreutils`
+    Then the command exits 0
+    And output contains `"applet": "chcon"`
+    And output contains `"reason": "not_in_replacement"`
+
+  Scenario: A command's own JSON payload goes under data
+    Given a staging root at /tmp/fakeroot
+    When I run `oxidizr-deb --output json status`
+    Then the command exits 0
+    And output contains `"command": "status"`
+    And output contains `"changed": false`
+    And output contains `"data": {`
+
+  Scenario: Failures still print the document, with the error
+    When I run `oxidizr-deb --root / --image-mode chroot --output json status`
+    Then the command exits 8
+    And output contains `"ok": false`
+    And output contains `"code": "E_INVALID_ROOT"`
+
+  Scenario: A dry run after committing reports no change
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a `rust-coreutils` .deb at `/srv/rust-coreutils_0.1.0-1_all.deb` shipping `/usr/bin/uutils`
+    When I run `oxidizr-deb --commit use coreutils --deb /srv/rust-coreutils_0.1.0-1_all.deb`
+    Then the command exits 0
+    When I run `oxidizr-deb --output json use coreutils --deb /srv/rust-coreutils_0.1.0-1_all.deb`
+    Then the command exits 0
+    And output contains `"dry_run": true`
+    And output contains `"changed": false`
@@ -0,0 +1,63 @@
+Feature: Reviewable plan files
+  As an operator working under change review
+  I want to export a plan, have it approved, and apply exactly that plan
+
+  Scenario: Plan use coreutils then apply it with the approved hash
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I write a plan with `oxidizr-deb plan use coreutils`
+    Then the command exits 0
+    And output contains `hash: `
+    When I apply the plan with its recorded hash and --commit
+    Then the command exits 0
+    And `/usr/bin/ls` is a symlink to the replacement
+
+  Scenario: Apply refuses a hash that was not approved
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I write a plan with `oxidizr-deb plan use coreutils`
+    And I apply the plan with hash `0000`
+    Then the command exits 1
+    And output contains `plan hash mismatch`
+
+  Scenario: Apply refuses when the system drifted since planning
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I write a plan with `oxidizr-deb plan use coreutils`
+    And `/usr/bin/ls` is changed to contain `gnu-ls-upgraded`
+    And I apply the plan with its recorded hash and --commit
+    Then the command exits 6
+    And output contains `system drifted since the plan was made`
+    And `/usr/bin/ls` is a regular file with content `gnu-ls-upgraded`
+
+  Scenario: Applying a replace plan keeps the Essential guardrail
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg status database has `coreutils` version `9.1-1` installed as Essential
+    When I write a plan with `oxidizr-deb plan replace coreutils`
+    And I apply the plan with its recorded hash
+    Then the command exits 0
+    And output contains `coreutils is Essential: yes; --commit will require --confirm-essential-removal`
+    And output contains `would install provider shim oxidizr-coreutils-provider`
+
+  Scenario: The Essential confirmation is recorded in the plan
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg status database has `coreutils` version `9.1-1` installed as Essential
+    When I write a plan with `oxidizr-deb plan replace coreutils --confirm-essential-removal`
+    And I apply the plan with its recorded hash
+    Then the command exits 0
+    And output contains `[dry-run] would purge Essential package(s) coreutils with --allow-remove-essential`
+
+  Scenario: Planning the same change twice gives the same hash
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg status database has `coreutils` version `9.1-1` installed as Essential
+    When I write a plan with `oxidizr-deb plan replace coreutils`
+    Then the command exits 0
+    When a second later I write the plan again with `oxidizr-deb plan replace coreutils`
+    Then the command exits 0
+    And both plans have the same hash
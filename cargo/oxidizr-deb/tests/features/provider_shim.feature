@@ -0,0 +1,20 @@
+Feature: Provider shim packages
+  As an operator
+  I want a local package to stand in for each purged GNU package
+  So that packages depending on coreutils or sudo stay installable after replace
+
+  Scenario: Dry-run replace previews the provider shim
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    When I run `oxidizr-deb replace coreutils`
+    Then the command exits 0
+    And output contains `[dry-run] would install provider shim oxidizr-coreutils-provider (Provides/Replaces/Conflicts: coreutils, Depends: rust-coreutils)`
+    And output contains `oxidizr-coreutils-provider_all.deb`
+
+  Scenario: Dry-run restore removes an installed provider shim with the distro install
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And the dpkg status database has `oxidizr-coreutils-provider` version `9.1-1+oxidizr1` installed
+    When I run `oxidizr-deb restore coreutils`
+    Then the command exits 0
+    And output contains `[dry-run] would run: apt-get install -y coreutils --purge oxidizr-coreutils-provider-`
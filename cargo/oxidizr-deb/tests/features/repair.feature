@@ -0,0 +1,49 @@
+Feature: Repair drifted applets
+  As an operator
+  I want to re-link applets that apt/dpkg overwrote while in use mode
+  So that hosts converge back to the recorded state
+
+  Scenario: Dry-run repair reports drift without touching files
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 0
+    When `/usr/bin/cat` is changed to contain `gnu-cat`
+    And I run `oxidizr-deb repair coreutils`
+    Then the command exits 0
+    And output contains `1 drifted applet(s): cat (stock)`
+    And `/usr/bin/cat` is a regular file with content `gnu-cat`
+
+  Scenario: Commit repair re-links only the drifted applets
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 0
+    When `/usr/bin/cat` is changed to contain `gnu-cat`
+    And I run `oxidizr-deb --commit repair --json`
+    Then the command exits 0
+    And output contains `"fixed":1`
+    And `/usr/bin/cat` is a symlink to the replacement
+    And `/usr/bin/ls` is a symlink to the replacement
+
+  Scenario: Repair leaves foreign links alone
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    And a file `/usr/local/bin/busybox` containing `busybox`
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 0
+    When `/usr/bin/cat` is replaced by a symlink to `/usr/local/bin/busybox`
+    And I run `oxidizr-deb --commit repair coreutils`
+    Then the command exits 0
+    And output contains `leaving 1 applet(s) not linked by oxidizr-deb alone: cat (foreign)`
+    And `/usr/bin/cat` is a symlink
+
+  Scenario: Nothing to repair without a state record
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    When I run `oxidizr-deb repair`
+    Then the command exits 0
+    And output contains `nothing to repair`
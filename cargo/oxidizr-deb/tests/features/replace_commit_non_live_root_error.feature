@@ -6,5 +6,5 @@ Feature: Replace requires live root for commit
     Given a staging root at /tmp/fakeroot
     And a fakeroot with stock coreutils applets
     When I run `oxidizr-deb --commit replace coreutils`
-    Then the command exits 1
+    Then the command exits 8
     And output contains `replace operations require --root=/ (live system) for apt/dpkg changes`
@@ -7,5 +7,5 @@ Feature: APT/DPKG locks block replace commit
     And a fakeroot with stock coreutils applets
     And dpkg/apt locks are present
     When I run `oxidizr-deb --commit replace coreutils`
-    Then the command exits 1
+    Then the command exits 3
     And output contains `Package manager busy (dpkg/apt lock detected); retry after current operation finishes.`
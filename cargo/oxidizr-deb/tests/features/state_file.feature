@@ -0,0 +1,26 @@
+Feature: Persistent state records switched families
+  As an operator
+  I want oxidizr-deb to remember what it switched
+  So that status and restore act on recorded state rather than guesses
+
+  Scenario: Commit use records the family and status reports it
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 0
+    And `/var/lib/oxidizr-deb/state.json` is a regular file
+    When I run `oxidizr-deb status`
+    Then the command exits 0
+    And output contains `coreutils: active`
+    And output contains `mode: use`
+
+  Scenario: Restore skips families that were never switched
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 0
+    When I run `oxidizr-deb --commit restore findutils`
+    Then the command exits 0
+    And output contains `nothing to restore`
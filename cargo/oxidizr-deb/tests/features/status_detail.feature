@@ -0,0 +1,38 @@
+Feature: Per-applet status classification
+  As an operator
+  I want status to tell apart our links, foreign links and broken links
+  So that I know exactly which applets need attention
+
+  Scenario: A family linked to another provider is foreign, not active
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    When `/bin/busybox` is changed to contain `busybox`
+    And `/usr/bin/find` is replaced by a symlink to `/bin/busybox`
+    And I run `oxidizr-deb status`
+    Then the command exits 0
+    And output contains `findutils: foreign`
+    And output does not contain `findutils: active`
+
+  Scenario: Some applets reverted after use make the family partial
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 0
+    When `/usr/bin/cat` is changed to contain `gnu-cat`
+    And I run `oxidizr-deb status`
+    Then the command exits 0
+    And output contains `coreutils: partial`
+    And output contains `cat: stock`
+
+  Scenario: A dangling link makes the family broken
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a verified replacement artifact is available for package "coreutils"
+    When I run `oxidizr-deb --commit use coreutils`
+    Then the command exits 0
+    When `/usr/bin/ls` is replaced by a symlink to `/nonexistent/uutils`
+    And I run `oxidizr-deb status --json`
+    Then the command exits 0
+    And output contains `"coreutils":"broken"`
+    And output contains `"suggestions":[`
@@ -6,6 +6,6 @@ Feature: Use requires live root for missing artifact in commit
     Given a staging root at /tmp/fakeroot
     And a fakeroot with stock coreutils applets
     When I run `oxidizr-deb --commit use coreutils`
-    Then the command exits 1
+    Then the command exits 8
     And output contains `replacement artifact missing at`
     And output contains `requires --root=/ (live system)`
@@ -0,0 +1,37 @@
+Feature: Install replacements from a local .deb or flat APT repository
+  As an operator without internet access
+  I want `use` to install a vetted build from a local .deb or repository
+  So that the replacement still comes from dpkg
+
+  Scenario: Dry-run with --deb previews unpacking the local file into a non-live root
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And a file `/srv/rust-coreutils_0.1.0_amd64.deb` containing `not-really-a-deb`
+    When I run `oxidizr-deb use coreutils --deb /srv/rust-coreutils_0.1.0_amd64.deb`
+    Then the command exits 0
+    And output contains `would unpack`
+    And output contains `rust-coreutils_0.1.0_amd64.deb`
+
+  Scenario: Dry-run with --apt-repo previews a temporary file: source
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And an unsigned flat APT repository at `/srv/repo`
+    When I run `oxidizr-deb use coreutils --apt-repo /srv/repo`
+    Then the command exits 0
+    And output contains `would add temporary source 'deb [trusted=yes] file:`
+    And output contains `oxidizr-deb-local.list`
+
+  Scenario: APT repository sources require the live root when committing
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    And an unsigned flat APT repository at `/srv/repo`
+    When I run `oxidizr-deb --commit use coreutils --apt-repo /srv/repo`
+    Then the command exits 8
+    And output contains `require --root=/ (live system)`
+
+  Scenario: --deb refuses a missing file
+    Given a staging root at /tmp/fakeroot
+    And a fakeroot with stock coreutils applets
+    When I run `oxidizr-deb use coreutils --deb /srv/missing.deb`
+    Then the command exits 1
+    And output contains `no such file`
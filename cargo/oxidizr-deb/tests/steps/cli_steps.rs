@@ -1,4 +1,4 @@
-use cucumber::when;
+use cucumber::{given, when};
 use shlex::Shlex;
 
 use crate::bdd_world::World;
@@ -14,8 +14,20 @@ pub async fn i_run_oxidizr_deb(world: &mut World, cmd: String) {
         final_args.push(world.root_pathbuf().display().to_string());
     }
 
-    // Copy original args and inject offline/use-local when artifact exists for `use` commands
-    final_args.extend(args.clone());
+    // Copy original args; local package sources are paths inside the staging root
+    // (as is the rootfs after `mmdebstrap-hook <op>`)
+    let mut prev_is_source = false;
+    for (i, a) in args.iter().enumerate() {
+        if prev_is_source && a.starts_with('/') {
+            final_args.push(world.under_root(a).display().to_string());
+        } else {
+            final_args.push(a.clone());
+        }
+        let hook_op = i > 0 && args[i - 1] == "mmdebstrap-hook";
+        prev_is_source = hook_op || matches!(a.as_str(), "import-bundle" | "--deb" | "--apt-repo");
+    }
+
+    // Inject offline/use-local when artifact exists for `use` commands
     if args.iter().any(|s| s == "use") {
         if let Some(rel) = world.artifact_path.clone() {
             if !args.iter().any(|a| a == "--use-local") {
@@ -30,3 +42,8 @@ pub async fn i_run_oxidizr_deb(world: &mut World, cmd: String) {
     let out = world.run_cli(final_args);
     world.last_output = Some(out);
 }
+
+#[given(regex = r"^the environment variable `(.+)` is `(.*)`$")]
+pub async fn env_var(world: &mut World, key: String, value: String) {
+    world.envs.push((key, value));
+}
@@ -1,5 +1,6 @@
+use cucumber::gherkin::Step;
 use cucumber::given;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::bdd_world::World;
 
@@ -54,9 +55,155 @@ pub async fn verified_artifact_available(world: &mut World, pkg: String) {
         perms.set_mode(0o755);
         std::fs::set_permissions(&abs, perms).unwrap();
     }
+    sign_artifact(world, &rel_path, &pkg);
     world.artifact_path = Some(rel_path);
 }
 
+#[given(regex = r"^a verified coreutils replacement that lists applets `(.+)`$")]
+pub async fn verified_artifact_listing(world: &mut World, applets: String) {
+    let rel_path = PathBuf::from("/opt/uutils/uutils");
+    world.write_file(
+        &rel_path,
+        format!("#!/bin/sh\necho '{applets}'\n").as_bytes(),
+        true,
+    );
+    sign_artifact(world, &rel_path, "coreutils");
+    world.artifact_path = Some(rel_path);
+}
+
+/// Fixed test signing key; its public half is installed under `/etc/oxidizr-deb/keys/test.pub`.
+const TEST_KEY_SEED: [u8; 32] = [7u8; 32];
+
+/// Build a manifest over `files`, sign it with the test key and trust that key under `world`'s root.
+/// Returns the manifest and its minisign-style signature text.
+fn signed_manifest(
+    world: &mut World,
+    name: &str,
+    version: &str,
+    files: &[(String, Vec<u8>)],
+) -> (String, String) {
+    use base64::Engine as _;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    let files: serde_json::Map<String, serde_json::Value> = files
+        .iter()
+        .map(|(n, b)| {
+            let sha = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(b));
+            (n.clone(), serde_json::Value::from(sha))
+        })
+        .collect();
+    let manifest = serde_json::json!({
+        "name": name,
+        "version": version,
+        "arch": "all",
+        "files": files,
+    })
+    .to_string();
+    let key = SigningKey::from_bytes(&TEST_KEY_SEED);
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let sig = format!(
+        "untrusted comment: oxidizr-deb test signature\n{}\n",
+        b64.encode(key.sign(manifest.as_bytes()).to_bytes())
+    );
+    world.write_file(
+        "/etc/oxidizr-deb/keys/test.pub",
+        format!(
+            "untrusted comment: oxidizr-deb test key\n{}\n",
+            b64.encode(key.verifying_key().to_bytes())
+        )
+        .as_bytes(),
+        false,
+    );
+    (manifest, sig)
+}
+
+fn sign_artifact(world: &mut World, rel_path: &Path, name: &str) {
+    let abs = world.under_root(rel_path);
+    let file_name = abs.file_name().unwrap().to_string_lossy().to_string();
+    let bytes = std::fs::read(&abs).unwrap();
+    let (manifest, sig) = signed_manifest(world, name, "0.0.0-test", &[(file_name.clone(), bytes)]);
+    let dir = abs.parent().unwrap();
+    std::fs::write(dir.join(format!("{file_name}.manifest.json")), &manifest).unwrap();
+    std::fs::write(dir.join(format!("{file_name}.manifest.json.sig")), sig).unwrap();
+}
+
+#[given(regex = r#"^a signed replacement bundle `(/.+)` with version "(.+)" containing `(.+)`$"#)]
+pub async fn signed_bundle(world: &mut World, rel: String, version: String, bin: String) {
+    let data = format!("{bin}-{version}-binary").into_bytes();
+    let (manifest, sig) =
+        signed_manifest(world, "bundle", &version, &[(bin.clone(), data.clone())]);
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, bytes) in [
+        ("manifest.json".to_string(), manifest.into_bytes()),
+        ("manifest.json.sig".to_string(), sig.into_bytes()),
+        (bin, data),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, &name, bytes.as_slice())
+            .unwrap();
+    }
+    let tarball = builder.into_inner().unwrap();
+    world.write_file(&rel, &tarball, false);
+}
+
+/// Gzip-compressed tar of `(path, contents, mode)` regular files, as found in distribution .debs.
+fn tar_gz(files: &[(&str, &[u8], u32)]) -> Vec<u8> {
+    use std::io::Write;
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, bytes, mode) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(*mode);
+        header.set_cksum();
+        builder.append_data(&mut header, name, *bytes).unwrap();
+    }
+    let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gz.write_all(&builder.into_inner().unwrap()).unwrap();
+    gz.finish().unwrap()
+}
+
+#[given(regex = r"^a `(.+)` \.deb at `(/.+)` shipping `(/.+)`$")]
+pub async fn deb_shipping(world: &mut World, package: String, rel: String, file: String) {
+    let control = format!(
+        "Package: {package}\nVersion: 0.1.0-1\nArchitecture: all\nMaintainer: test <test@example.com>\nDescription: test package\n"
+    );
+    let control_tar = tar_gz(&[("./control", control.as_bytes(), 0o644)]);
+    let data_tar = tar_gz(&[(&format!(".{file}"), b"uutils-binary", 0o755)]);
+    let mut deb = b"!<arch>\n".to_vec();
+    for (name, data) in [
+        ("debian-binary", b"2.0\n".to_vec()),
+        ("control.tar.gz", control_tar),
+        ("data.tar.gz", data_tar),
+    ] {
+        deb.extend(
+            format!(
+                "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+                name,
+                0,
+                0,
+                0,
+                100644,
+                data.len()
+            )
+            .as_bytes(),
+        );
+        deb.extend_from_slice(&data);
+        if data.len() % 2 == 1 {
+            deb.push(b'\n');
+        }
+    }
+    world.write_file(&rel, &deb, false);
+}
+
+#[given(regex = r"^no trusted artifact keys are installed$")]
+pub async fn no_trusted_keys(world: &mut World) {
+    let _ = std::fs::remove_dir_all(world.under_root("/etc/oxidizr-deb/keys"));
+}
+
 #[given(regex = r"^the sudo artifact has setuid 4755$")]
 pub async fn sudo_artifact_setuid(world: &mut World) {
     #[cfg(unix)]
@@ -69,3 +216,59 @@ pub async fn sudo_artifact_setuid(world: &mut World) {
         std::fs::set_permissions(&abs, perms).unwrap();
     }
 }
+
+#[given(regex = r"^a file `(/.+)` containing `(.+)`$")]
+pub async fn file_containing(world: &mut World, path: String, content: String) {
+    world.write_file(&path, content.as_bytes(), false);
+}
+
+#[given(regex = r"^the alternatives group `(.+)` is registered as:$")]
+pub async fn alternatives_group(world: &mut World, step: &Step, name: String) {
+    // The admin file ends with a blank line after the last alternative.
+    let body = step.docstring.clone().unwrap_or_default();
+    world.write_file(
+        &format!("/var/lib/dpkg/alternatives/{name}"),
+        format!("{}\n\n", body.trim()).as_bytes(),
+        false,
+    );
+}
+
+#[given(regex = r"^an unsigned flat APT repository at `(/.+)`$")]
+pub async fn flat_apt_repo(world: &mut World, path: String) {
+    world.write_file(
+        format!("{path}/Packages"),
+        b"Package: rust-coreutils\n",
+        false,
+    );
+}
+
+#[given(regex = r"^the dpkg database lists `(.+)` for package `(.+)`$")]
+pub async fn dpkg_lists_file(world: &mut World, path: String, package: String) {
+    let list = world.under_root(format!("/var/lib/dpkg/info/{package}.list"));
+    std::fs::create_dir_all(list.parent().unwrap()).unwrap();
+    let mut body = std::fs::read_to_string(&list).unwrap_or_default();
+    body.push_str(&format!("{path}\n"));
+    std::fs::write(&list, body).unwrap();
+}
+
+#[given(
+    regex = r"^the dpkg status database has `(.+)` version `(.+)` (installed|installed as Essential)$"
+)]
+pub async fn dpkg_status_installed(
+    world: &mut World,
+    package: String,
+    version: String,
+    how: String,
+) {
+    let status = world.under_root("/var/lib/dpkg/status");
+    std::fs::create_dir_all(status.parent().unwrap()).unwrap();
+    let mut body = std::fs::read_to_string(&status).unwrap_or_default();
+    body.push_str(&format!(
+        "Package: {package}\nStatus: install ok installed\nArchitecture: amd64\nVersion: {version}\n"
+    ));
+    if how.ends_with("Essential") {
+        body.push_str("Essential: yes\n");
+    }
+    body.push('\n');
+    std::fs::write(&status, body).unwrap();
+}
@@ -1,4 +1,4 @@
-use cucumber::then;
+use cucumber::{then, when};
 use std::fs;
 
 use crate::bdd_world::World;
@@ -67,3 +67,36 @@ pub async fn path_is_symlink_to_replacement(world: &mut World, path: String) {
         expected_abs.display()
     );
 }
+
+#[when(regex = r"^`(/.+)` is replaced by a symlink to `(/.+)`$")]
+pub async fn replace_with_symlink(world: &mut World, path: String, target: String) {
+    let abs = world.under_root(&path);
+    let _ = fs::remove_file(&abs);
+    std::os::unix::fs::symlink(world.under_root(&target), &abs).expect("create symlink");
+}
+
+#[then(regex = r"^`(/.+)` contains `(.+)`$")]
+pub async fn file_contains(world: &mut World, path: String, needle: String) {
+    let abs = world.under_root(&path);
+    let s = fs::read_to_string(&abs).expect("read file");
+    assert!(
+        s.contains(&needle),
+        "expected {} to contain {:?}\n{}",
+        abs.display(),
+        needle,
+        s
+    );
+}
+
+#[then(regex = r"^`(/.+)` does not contain `(.+)`$")]
+pub async fn file_does_not_contain(world: &mut World, path: String, needle: String) {
+    let abs = world.under_root(&path);
+    let s = fs::read_to_string(&abs).unwrap_or_default();
+    assert!(
+        !s.contains(&needle),
+        "expected {} not to contain {:?}\n{}",
+        abs.display(),
+        needle,
+        s
+    );
+}
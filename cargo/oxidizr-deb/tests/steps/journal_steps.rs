@@ -0,0 +1,35 @@
+use std::os::unix::net::UnixDatagram;
+
+use cucumber::{given, then};
+
+use crate::bdd_world::World;
+
+#[given(regex = r"^a local journald socket is listening$")]
+pub async fn journald_socket(world: &mut World) {
+    // oxidizr-deb sends its entries here instead of /run/systemd/journal/socket
+    let path = world.under_root("/run/journal.sock");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let sock = UnixDatagram::bind(&path).expect("bind journal socket");
+    sock.set_nonblocking(true).unwrap();
+    world.journal = Some(sock);
+    world.envs.push((
+        "OXIDIZR_DEB_JOURNAL_SOCKET".into(),
+        path.display().to_string(),
+    ));
+}
+
+#[then(regex = r"^the journal received `(.+)`$")]
+pub async fn journal_received(world: &mut World, needle: String) {
+    let sock = world.journal.as_ref().expect("no journald socket");
+    let mut buf = vec![0u8; 256 * 1024];
+    while let Ok(n) = sock.recv(&mut buf) {
+        world
+            .journal_entries
+            .push(String::from_utf8_lossy(&buf[..n]).to_string());
+    }
+    assert!(
+        world.journal_entries.iter().any(|e| e.contains(&needle)),
+        "no journal entry contains {needle:?}; received:\n{}",
+        world.journal_entries.join("\n---\n")
+    );
+}
@@ -1,3 +1,4 @@
+use cucumber::gherkin::Step;
 use cucumber::given;
 
 use crate::bdd_world::World;
@@ -28,3 +29,16 @@ pub async fn force_exdev(world: &mut World) {
         .envs
         .push(("SWITCHYARD_FORCE_EXDEV".into(), "1".into()));
 }
+
+#[given(regex = r"^apt-get simulation prints:$")]
+pub async fn stub_apt_get_simulation(world: &mut World, step: &Step) {
+    // A stand-in apt-get on PATH that prints the given `-s` output for any invocation.
+    let output = step.docstring.clone().unwrap_or_default();
+    let script = format!("#!/bin/sh\ncat <<'EOF'\n{}\nEOF\n", output.trim());
+    world.write_file("/.test-bin/apt-get", script.as_bytes(), true);
+    let bin = world.under_root("/.test-bin");
+    let path = std::env::var("PATH").unwrap_or_default();
+    world
+        .envs
+        .push(("PATH".into(), format!("{}:{}", bin.display(), path)));
+}
@@ -2,4 +2,6 @@ pub mod assert_steps;
 pub mod cli_steps;
 pub mod common_steps;
 pub mod fs_steps;
+pub mod journal_steps;
 pub mod locks_steps;
+pub mod plan_steps;
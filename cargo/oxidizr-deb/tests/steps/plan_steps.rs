@@ -0,0 +1,91 @@
+use cucumber::{then, when};
+use shlex::Shlex;
+
+use crate::bdd_world::World;
+
+const PLAN_REL: &str = "/plan.json";
+const FIRST_PLAN_REL: &str = "/plan.first.json";
+
+fn recorded_hash(world: &mut World) -> String {
+    let s = world.read_to_string(PLAN_REL);
+    let v: serde_json::Value = serde_json::from_str(&s).expect("plan json");
+    v["hash"].as_str().expect("hash field").to_string()
+}
+
+#[when(regex = r"^I write a plan with `oxidizr-deb plan (.+)`$")]
+pub async fn write_plan(world: &mut World, cmd: String) {
+    let args: Vec<String> = Shlex::new(&cmd).collect();
+    let root = world.root_pathbuf();
+    let mut final_args: Vec<String> =
+        vec!["--root".into(), root.display().to_string(), "plan".into()];
+    final_args.extend(args.clone());
+    if args.first().map(|s| s == "use").unwrap_or(false) {
+        if let Some(rel) = world.artifact_path.clone() {
+            final_args.push("--offline".into());
+            final_args.push("--use-local".into());
+            final_args.push(world.under_root(rel).display().to_string());
+        }
+    }
+    final_args.push("--out".into());
+    final_args.push(world.under_root(PLAN_REL).display().to_string());
+    world.run_cli(final_args);
+}
+
+#[when(regex = r"^a second later I write the plan again with `oxidizr-deb plan (.+)`$")]
+pub async fn write_plan_again(world: &mut World, cmd: String) {
+    let first = world.under_root(FIRST_PLAN_REL);
+    std::fs::copy(world.under_root(PLAN_REL), first).expect("keep first plan");
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    write_plan(world, cmd).await;
+}
+
+#[then(regex = r"^both plans have the same hash$")]
+pub async fn same_hash(world: &mut World) {
+    let read = |world: &mut World, rel: &str| -> serde_json::Value {
+        serde_json::from_str(&world.read_to_string(rel)).expect("plan json")
+    };
+    let first = read(world, FIRST_PLAN_REL);
+    let second = read(world, PLAN_REL);
+    assert_ne!(first["plan"]["created_at"], second["plan"]["created_at"]);
+    assert_eq!(first["hash"], second["hash"]);
+}
+
+#[when(regex = r"^I apply the plan with its recorded hash$")]
+pub async fn apply_recorded(world: &mut World) {
+    let hash = recorded_hash(world);
+    apply_with(world, &hash, false);
+}
+
+#[when(regex = r"^I apply the plan with its recorded hash and --commit$")]
+pub async fn apply_recorded_commit(world: &mut World) {
+    let hash = recorded_hash(world);
+    apply_with(world, &hash, true);
+}
+
+#[when(regex = r"^I apply the plan with hash `(.+)`$")]
+pub async fn apply_hash(world: &mut World, hash: String) {
+    apply_with(world, &hash, false);
+}
+
+#[when(regex = r"^`(/.+)` is changed to contain `(.+)`$")]
+pub async fn change_file(world: &mut World, path: String, content: String) {
+    let p = world.under_root(&path);
+    let _ = std::fs::remove_file(&p);
+    world.write_file(&path, content.as_bytes(), true);
+}
+
+fn apply_with(world: &mut World, hash: &str, commit: bool) {
+    let root = world.root_pathbuf();
+    let mut args: Vec<String> = vec!["--root".into(), root.display().to_string()];
+    if commit {
+        args.push("--commit".into());
+    }
+    args.extend([
+        "apply".to_string(),
+        "--plan".to_string(),
+        world.under_root(PLAN_REL).display().to_string(),
+        "--expect-hash".to_string(),
+        hash.to_string(),
+    ]);
+    world.run_cli(args);
+}